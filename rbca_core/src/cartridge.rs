@@ -1,14 +1,40 @@
 //! Functionality related to Game Boy cartridges.
-use std::{fmt::Debug, fmt::Display, fs::File, io::Read};
+use std::{
+    fmt::Debug,
+    fmt::Display,
+    fs::File,
+    io::Read,
+    time::{Duration, SystemTime},
+};
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
 
+mod archive;
 mod empty;
+mod header;
+mod huc1;
+mod huc3;
 mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod patch;
+mod pocket_camera;
+mod rom_builder;
 mod rom_only;
+mod tama5;
 
 // Re-exports
 pub use empty::CartEmpty;
+use header::rom_size_fn;
+pub use header::{CartridgeHeader, CgbFlag, Destination};
+pub use patch::apply_patch;
+pub use pocket_camera::{ImageSource, StillImage, CAMERA_HEIGHT, CAMERA_WIDTH};
+pub use rom_builder::RomBuilder;
 
 const BYTES_IN_KIB: u32 = 1024;
 
@@ -18,21 +44,237 @@ const LOGO: [u8; 48] = [
     0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
 ];
 
-/// Load a cartridge.
-pub fn load_cartridge<P: AsRef<Utf8Path>>(filepath: P) -> Box<dyn Cartridge> {
+/// Size of the cartridge header, including everything before it (0x0000-0x014F).
+const HEADER_END: usize = 0x0150;
+/// Size of a switchable ROM bank on most mappers.
+const ROM_BANK_SIZE: usize = 0x4000;
+/// Size of a switchable RAM bank on most mappers.
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Errors that can occur while loading a cartridge or boot ROM.
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The file couldn't be read.
+    Io {
+        /// The file that couldn't be read.
+        path: Utf8PathBuf,
+        /// The underlying I/O error.
+        source: std::io::Error,
+    },
+    /// The data is too short to contain a full header.
+    TruncatedHeader {
+        /// The minimum number of bytes needed.
+        expected: usize,
+        /// The number of bytes provided.
+        actual: usize,
+    },
+    /// The sizes in the header don't match the data or aren't supported by the mapper.
+    SizeMismatch(String),
+    /// The cartridge type in the header isn't supported.
+    UnsupportedMapper(String),
+    /// The .zip or .gz file couldn't be extracted or doesn't contain a ROM.
+    Archive(String),
+    /// The IPS, UPS or BPS patch is invalid or doesn't match the ROM.
+    Patch(String),
+}
+impl Display for CartridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "Cartridge/ROM file error: {source} {path}"),
+            Self::TruncatedHeader { expected, actual } => write!(
+                f,
+                "Truncated header: {actual} bytes of data < {expected} bytes needed"
+            ),
+            Self::SizeMismatch(msg) => write!(f, "Size mismatch: {msg}"),
+            Self::UnsupportedMapper(msg) => write!(f, "Unsupported cartridge type: {msg}"),
+            Self::Archive(msg) => write!(f, "Archive error: {msg}"),
+            Self::Patch(msg) => write!(f, "Patch error: {msg}"),
+        }
+    }
+}
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A problem with a ROM dump that was worked around while loading it. Left for the frontend to
+/// report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadWarning {
+    /// The ROM size doesn't match the header. The ROM was padded (with 0xFF) or truncated to the
+    /// size in the header.
+    RomResized {
+        /// Size of the provided ROM data.
+        actual: usize,
+        /// ROM size in the header.
+        expected: usize,
+    },
+    /// The boot ROM is longer than 256 bytes. Only the first 256 bytes are used.
+    BootRomTruncated {
+        /// Size of the provided boot ROM data.
+        actual: usize,
+    },
+}
+impl Display for LoadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RomResized { actual, expected } => write!(
+                f,
+                "ROM size in header does not match provided data ({actual} bytes of ROM data != {expected} bytes in header). {} the ROM to match the header.",
+                if actual < expected { "Padded" } else { "Truncated" }
+            ),
+            Self::BootRomTruncated { actual } => {
+                write!(f, "Boot ROM is {actual} bytes. Truncated to 256 bytes.")
+            }
+        }
+    }
+}
+impl CartridgeError {
+    /// Create a new [CartridgeError::Io] for the given file.
+    pub(crate) fn io<P: AsRef<Utf8Path>>(path: P, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// Load a cartridge from a file. If an IPS, UPS or BPS patch with the same file stem sits next to
+/// the ROM, it is applied first. Also return the problems with the dump that were worked around.
+pub fn load_cartridge<P: AsRef<Utf8Path>>(
+    filepath: P,
+) -> Result<(Box<dyn Cartridge>, Vec<LoadWarning>), CartridgeError> {
+    let mut data = read_rom_file(&filepath)?;
+    if let Some(patch_path) = patch::find_patch(&filepath) {
+        let patch = read_file(patch_path)?;
+        data = apply_patch(&data, &patch)?;
+    }
+    load_cartridge_rom(data)
+}
+
+/// Read the raw ROM data from a file. ROMs in .zip & .gz files are extracted.
+pub fn read_rom_file<P: AsRef<Utf8Path>>(filepath: P) -> Result<Vec<u8>, CartridgeError> {
+    archive::extract_rom(read_file(filepath)?)
+}
+
+fn read_file<P: AsRef<Utf8Path>>(filepath: P) -> Result<Vec<u8>, CartridgeError> {
     let mut file_buf = vec![];
-    if let Err(e) = File::open(filepath.as_ref()).and_then(|mut f| f.read_to_end(&mut file_buf)) {
-        panic!("Cartridge/ROM file error: {e} {}", filepath.as_ref());
+    File::open(filepath.as_ref())
+        .and_then(|mut f| f.read_to_end(&mut file_buf))
+        .map_err(|e| CartridgeError::io(&filepath, e))?;
+    Ok(file_buf)
+}
+
+/// Load a cartridge from the contents of a ROM file. ROMs in .zip & .gz files are extracted
+/// first. Also return the problems with the dump that were worked around.
+pub fn load_cartridge_bytes(
+    data: Vec<u8>,
+) -> Result<(Box<dyn Cartridge>, Vec<LoadWarning>), CartridgeError> {
+    load_cartridge_rom(archive::extract_rom(data)?)
+}
+
+/// Load a cartridge from ROM data that has already been extracted (& patched). The data is never
+/// treated as an archive. Also return the problems with the dump that were worked around.
+pub fn load_cartridge_rom(
+    mut data: Vec<u8>,
+) -> Result<(Box<dyn Cartridge>, Vec<LoadWarning>), CartridgeError> {
+    if data.len() < HEADER_END {
+        return Err(CartridgeError::TruncatedHeader {
+            expected: HEADER_END,
+            actual: data.len(),
+        });
     }
 
-    let cart_features = CartFeatures::from_data(&file_buf);
-    if cart_features.mbc1 {
-        Box::new(mbc1::CartMBC1::new(file_buf))
-    } else if cart_features.rom_only {
-        Box::new(rom_only::CartRomOnly::new(file_buf, cart_features))
+    // MMM01 dumps usually store the menu & its header in the last 32 KiB, so check there too.
+    if mmm01::is_mmm01(&data) {
+        let warnings = mmm01::fit_rom_size(&mut data)?.into_iter().collect();
+        return Ok((Box::new(mmm01::CartMMM01::new(data)?), warnings));
+    }
+
+    let warnings = fit_rom_size(&mut data)?.into_iter().collect();
+    let cart_features = CartFeatures::from_data(&data);
+    let cart: Box<dyn Cartridge> = if cart_features.mbc1 {
+        if mbc1::is_multicart(&data) {
+            Box::new(mbc1::CartMBC1::new_multicart(data)?)
+        } else {
+            Box::new(mbc1::CartMBC1::new(data)?)
+        }
+    } else if cart_features.mbc2 {
+        Box::new(mbc2::CartMBC2::new(data)?)
+    } else if cart_features.mbc3 {
+        Box::new(mbc3::CartMBC3::new(data)?)
+    } else if cart_features.mbc5 {
+        Box::new(mbc5::CartMBC5::new(data)?)
+    } else if cart_features.mbc6 {
+        Box::new(mbc6::CartMBC6::new(data)?)
+    } else if cart_features.mbc7 {
+        Box::new(mbc7::CartMBC7::new(data)?)
+    } else if cart_features.pocket_camera {
+        Box::new(pocket_camera::CartPocketCamera::new(data)?)
+    } else if cart_features.huc1 {
+        Box::new(huc1::CartHuC1::new(data)?)
+    } else if cart_features.huc3 {
+        Box::new(huc3::CartHuC3::new(data)?)
+    } else if cart_features.bandai_tama5 {
+        Box::new(tama5::CartTama5::new(data)?)
+    } else if cart_features.rom_only || cart_features.rom {
+        let header = CartridgeHeader::from_data(&data);
+        Box::new(rom_only::CartRomOnly::new(data, header))
     } else {
-        unimplemented!("Unimplemented cartridge type: {cart_features}");
+        return Err(CartridgeError::UnsupportedMapper(format!(
+            "{:#04X} ({cart_features})",
+            data[0x0147]
+        )));
+    };
+    Ok((cart, warnings))
+}
+
+/// Pad (with 0xFF) or truncate over- & under-sized ROM dumps to the ROM size in the header.
+/// Return a warning if the size was changed.
+fn fit_rom_size(data: &mut Vec<u8>) -> Result<Option<LoadWarning>, CartridgeError> {
+    let header_value = data[0x0148];
+    let expected = rom_size_fn(header_value) as usize;
+    if expected == 0 {
+        return Err(CartridgeError::SizeMismatch(format!(
+            "Unknown ROM size value in header ({header_value:#04X})"
+        )));
     }
+
+    if data.len() == expected {
+        return Ok(None);
+    }
+    let warning = LoadWarning::RomResized {
+        actual: data.len(),
+        expected,
+    };
+    data.resize(expected, 0xFF);
+    Ok(Some(warning))
+}
+
+/// A snapshot of a cartridge's banking state, for debuggers & tests.
+///
+/// Bank numbers are in units of [Cartridge::rom_bank_size] & [Cartridge::ram_bank_size], after
+/// any masking or remapping the mapper does, so they can be passed straight to
+/// [Cartridge::rom_bank] & [Cartridge::ram_bank].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MapperState {
+    /// ROM bank mapped at 0x0000.
+    pub rom_bank_0000: usize,
+    /// ROM bank mapped at 0x4000.
+    pub rom_bank_4000: usize,
+    /// RAM bank mapped at 0xA000. None if there is no RAM or something else, such as a clock
+    /// register, is mapped there.
+    pub ram_bank: Option<usize>,
+    /// Whether RAM is enabled.
+    pub ram_enabled: bool,
+    /// The mapper's banking mode register, if it has one.
+    pub mode: u8,
+    /// The latched clock registers, if the cartridge has a latched clock.
+    pub rtc_latch: Option<[u8; 5]>,
 }
 
 /// A Game Boy cartridge.
@@ -40,8 +282,60 @@ pub trait Cartridge: Debug {
     /// Get the full raw ROM contents of the cartridge.
     fn rom(&self) -> &[u8];
 
+    /// Get the cartridge header, decoded when the cartridge was loaded.
+    fn header(&self) -> &CartridgeHeader;
+
+    /// Get the full raw RAM contents of the cartridge.
+    ///
+    /// Default: no RAM, empty.
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Get a snapshot of the current banking state.
+    ///
+    /// Default: no banking. Bank 0 at 0x0000, bank 1 at 0x4000 & RAM always enabled.
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: 1,
+            ram_bank: (!self.ram().is_empty()).then_some(0),
+            ram_enabled: !self.ram().is_empty(),
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    /// Get the size of the ROM banks used by [Cartridge::mapper_state] & [Cartridge::rom_bank].
+    ///
+    /// Default: 16 KiB.
+    fn rom_bank_size(&self) -> usize {
+        ROM_BANK_SIZE
+    }
+
+    /// Get the size of the RAM banks used by [Cartridge::mapper_state] & [Cartridge::ram_bank].
+    ///
+    /// Default: 8 KiB.
+    fn ram_bank_size(&self) -> usize {
+        RAM_BANK_SIZE
+    }
+
+    /// Get the contents of the given ROM bank, whether or not it is mapped in. None if there is
+    /// no such bank.
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.rom().chunks(self.rom_bank_size()).nth(bank)
+    }
+
+    /// Get the contents of the given RAM bank, whether or not it is mapped in. None if there is
+    /// no such bank.
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.ram().chunks(self.ram_bank_size()).nth(bank)
+    }
+
     /// Get the cartridge features.
-    fn cart_features(&self) -> &CartFeatures;
+    fn cart_features(&self) -> &CartFeatures {
+        &self.header().cart_features
+    }
 
     /// Return true iff the cartridge is empty.
     ///
@@ -50,6 +344,68 @@ pub trait Cartridge: Debug {
         false
     }
 
+    /// Return true iff the cartridge's rumble motor is currently on.
+    ///
+    /// Default: no rumble motor, false.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Set the current tilt of the cartridge in g. Positive x = tilted right, positive y = tilted
+    /// down.
+    ///
+    /// Default: no accelerometer, do nothing.
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// Set the source of images for the cartridge's camera.
+    ///
+    /// Default: no camera, do nothing.
+    fn set_image_source(&mut self, _image_source: Box<dyn ImageSource>) {}
+
+    /// Get the contents of the cartridge's battery-backed memory, in the raw .sav layout used by
+    /// other emulators. Clock data, if any, is appended after the memory.
+    ///
+    /// Default: no battery-backed memory, None.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore the cartridge's battery-backed memory from raw .sav data. Missing data is left
+    /// as-is & extra data is ignored.
+    ///
+    /// Default: no battery-backed memory, do nothing.
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    /// Return true iff the cartridge's saved memory (RAM, flash, EEPROM or clock registers) has
+    /// been written since the last call, then clear the flag.
+    ///
+    /// Default: nothing to save, false.
+    fn take_save_dirty(&mut self) -> bool {
+        false
+    }
+
+    /// Get a file-name-safe identifier for the game, made from its title & global checksum.
+    fn game_id(&self) -> String {
+        let title: String = self
+            .header()
+            .title
+            .trim()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{title}_{:04X}", self.header().global_checksum)
+    }
+
+    /// Get the file name of the cartridge's save file.
+    fn save_file_name(&self) -> String {
+        format!("{}.sav", self.game_id())
+    }
+
+    /// Advance the cartridge hardware by the given number of T-cycles.
+    ///
+    /// Default: nothing to advance, do nothing.
+    fn cycle(&mut self, _t_cycles: u32) {}
+
     /// Read a byte from the cartridge ROM.
     ///
     /// Default: Read directly from address without any banking.
@@ -78,133 +434,45 @@ pub trait Cartridge: Debug {
     #[allow(unused_variables)]
     fn write_ram(&mut self, address: u16, value: u8) {}
 
-    /// Get the logo shown at startup.
-    fn logo(&self) -> &[u8] {
-        &self.rom()[0x0104..=0x0133]
-    }
-
-    /// Verify the accuracy of the logo. Return true iff logo is okay.
-    fn validate_logo(&self) -> bool {
-        for (i, logo_byte) in LOGO.iter().enumerate() {
-            if self.rom()[0x0104_usize + i] != *logo_byte {
-                return false;
-            }
-        }
-        true
-    }
-
-    /// Get the cartridge title.
-    fn title(&self) -> &str {
-        self.title_helper(0x0143)
-    }
-    /// Get the 11-byte cartridge title (for CGB & later cartridges).
-    fn title_cgb(&self) -> &str {
-        self.title_helper(0x013E)
-    }
-    /// Helper for title functions.
-    fn title_helper(&self, end_index: u16) -> &str {
-        match std::str::from_utf8(&self.rom()[0x0134..=(end_index as usize)]) {
-            Ok(val) => val,
-            _ => std::str::from_utf8(&self.rom()[0x0134..=(end_index as usize - 1)])
-                .unwrap_or_default(),
-        }
-    }
-
-    /// Get the manufacturer code.
-    fn manufacturer_code(&self) -> &[u8] {
-        &self.rom()[0x13F..=0x0142]
-    }
-
-    /// Get the CGB flag.
-    fn cgb_flag(&self) -> CgbFlag {
-        match self.rom()[0x0143] {
-            0b1000_0000 => CgbFlag::CgbBkwd,
-            0b1100_0000 => CgbFlag::Cgb,
-            0b1000_1000 | 0b1000_0100 => CgbFlag::Pgb,
-            _ => CgbFlag::Dmg,
-        }
-    }
-
-    /// Return true iff the cartridge supports SGB functionality.
-    fn sgb_flag(&self) -> bool {
-        self.rom()[0x0146] == 0x03
-    }
-
-    /// Get the amount of ROM present on the cartridge (in bytes).
-    fn rom_size(&self) -> u32 {
-        match self.rom()[0x0148] {
-            val if val <= 0x08 => (1 << val) * 32 * BYTES_IN_KIB,
-            _ => 0,
-        }
-    }
-
-    /// Get the amount of RAM present on the cartridge (in bytes).
-    fn ram_size(&self) -> u32 {
-        (match self.rom()[0x0149] {
-            0x02 => 8,
-            0x03 => 32,
-            0x04 => 128,
-            0x05 => 64,
-            _ => 0,
-        }) * BYTES_IN_KIB
-    }
-
-    /// Return true iff cartridge was intended to be sold in Japan. Otherwise, it was overseas
-    /// only.
-    fn jpn(&self) -> bool {
-        self.rom()[0x014A] != 0x00
-    }
-
-    /// Get the version number of the cartridge (usually 0).
-    fn version_number(&self) -> u8 {
-        self.rom()[0x014C]
-    }
-
-    /// Get the header checksum.
-    fn checksum(&self) -> u8 {
-        self.rom()[0x014D]
-    }
-
-    /// Validate checksum. Return None if checksum passes. Otherwise, return the incorrect checksum
-    /// that was produced.
-    fn validate_checksum(&self) -> Option<u8> {
-        let checksum = checksum_fn(self.rom());
-        if self.checksum() == checksum {
-            // Checksum OK!
-            None
-        } else {
-            // Bad checksum!
-            Some(checksum)
-        }
-    }
-
-    /// Get the global checksum.
-    fn global_checksum(&self) -> u16 {
-        ((self.rom()[0x014E] as u16) << 8) | (self.rom()[0x014F] as u16)
-    }
-
     /// Get some header info formatted as a nice String.
     fn header_info(&self) -> String {
+        let header = self.header();
         format!(
             "Cartridge Info
 \tTitle\t\t\t{}
+\tPublisher\t\t{}
+\tDestination\t\t{}
 \tType\t\t\t{}
 \tROM Size\t\t{} KiB ({:#X} bytes)
 \tRAM Size\t\t{} KiB ({:#X} bytes)
 \tChecksum\t\t{}
+\tGlobal Checksum\t\t{}
 \tLogo Check\t\t{}",
-            self.title(),
-            self.cart_features(),
-            self.rom_size() / BYTES_IN_KIB,
-            self.rom_size(),
-            self.ram_size() / BYTES_IN_KIB,
-            self.ram_size(),
-            if let Some(val) = self.validate_checksum() {
-                format!("Failed! ({:#04X} != {:#04X})", self.checksum(), val)
+            header.title,
+            header.publisher.unwrap_or("Unknown"),
+            header.destination,
+            header.cart_features,
+            header.rom_size / BYTES_IN_KIB,
+            header.rom_size,
+            header.ram_size / BYTES_IN_KIB,
+            header.ram_size,
+            if header.header_checksum_valid {
+                String::from("OK!")
             } else {
+                format!(
+                    "Failed! ({:#04X} != {:#04X})",
+                    header.header_checksum, header.computed_header_checksum
+                )
+            },
+            if header.global_checksum_valid {
                 String::from("OK!")
+            } else {
+                format!(
+                    "Failed! ({:#06X} != {:#06X})",
+                    header.global_checksum, header.computed_global_checksum
+                )
             },
-            if self.validate_logo() {
+            if header.logo_valid {
                 String::from("OK!")
             } else {
                 String::from("Invalid!")
@@ -213,48 +481,63 @@ pub trait Cartridge: Debug {
     }
 }
 
-/// The function used to calculate the header checksum.
-pub fn checksum_fn(rom: &[u8]) -> u8 {
-    let mut checksum: u8 = 0;
-    #[allow(clippy::needless_range_loop)]
-    for i in 0x0134..=0x014C {
-        checksum = checksum.wrapping_sub(rom[i]).wrapping_sub(1);
-    }
-    checksum
+/// Copy as much save data as fits into the given memory. Return the rest of the save data.
+fn load_save_section<'a>(memory: &mut [u8], data: &'a [u8]) -> &'a [u8] {
+    let len = memory.len().min(data.len());
+    memory[..len].copy_from_slice(&data[..len]);
+    &data[len..]
+}
+
+/// Convert a time to seconds since the Unix epoch, for clock data in save files.
+fn to_unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
-/// The different states of the CGB header flag.
-pub enum CgbFlag {
-    /// DMG cartridge. Compatible with DMG & CGB.
-    Dmg,
-    /// Cartridge with CGB enhancements that is backwards-compatible with DMG.
-    CgbBkwd,
-    /// Cartridge that works on CGB only.
-    Cgb,
-    /// Unknown PGB mode.
-    Pgb,
+/// Convert seconds since the Unix epoch to a time, for clock data in save files.
+fn from_unix_timestamp(timestamp: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp)
 }
 
 /// All the different hardware features a cartridge can have.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
 pub struct CartFeatures {
+    /// ROM only, no mapper.
     pub rom_only: bool,
+    /// ROM with unbanked RAM.
     pub rom: bool,
+    /// MBC1 mapper.
     pub mbc1: bool,
+    /// MBC2 mapper.
     pub mbc2: bool,
+    /// MBC3 mapper.
     pub mbc3: bool,
+    /// MBC5 mapper.
     pub mbc5: bool,
+    /// MBC6 mapper.
     pub mbc6: bool,
+    /// MBC7 mapper.
     pub mbc7: bool,
+    /// Cartridge RAM.
     pub ram: bool,
+    /// Battery-backed memory.
     pub battery: bool,
+    /// MMM01 mapper.
     pub mmm01: bool,
+    /// Real-time clock.
     pub timer: bool,
+    /// Rumble motor.
     pub rumble: bool,
+    /// Accelerometer.
     pub sensor: bool,
+    /// Pocket Camera mapper.
     pub pocket_camera: bool,
+    /// Bandai TAMA5 mapper.
     pub bandai_tama5: bool,
+    /// HuC3 mapper.
     pub huc3: bool,
+    /// HuC1 mapper.
     pub huc1: bool,
 }
 impl CartFeatures {
@@ -443,3 +726,210 @@ impl Display for CartFeatures {
         write!(f, "{}", str_vec.join("+"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Write the given data to a temporary file & load it as a cartridge.
+    fn load_test_file(
+        name: &str,
+        data: &[u8],
+    ) -> Result<(Box<dyn Cartridge>, Vec<LoadWarning>), CartridgeError> {
+        let path = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("rbca_test_{name}.gb"));
+        std::fs::write(&path, data).unwrap();
+        let result = load_cartridge(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        assert!(matches!(
+            load_cartridge("/this/file/does/not/exist.gb"),
+            Err(CartridgeError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_truncated_header() {
+        let data = RomBuilder::new(0x01).rom_size(0x01).build();
+        assert!(matches!(
+            load_test_file("truncated", &data[..0x0140]),
+            Err(CartridgeError::TruncatedHeader {
+                expected: 0x0150,
+                actual: 0x0140
+            })
+        ));
+    }
+
+    #[test]
+    fn test_load_unsupported_mapper() {
+        let data = RomBuilder::new(0xEF).rom_size(0x01).build();
+        assert!(matches!(
+            load_test_file("unsupported", &data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_bad_rom_size() {
+        let mut data = RomBuilder::new(0x01).rom_size(0x01).build();
+        data[0x0148] = 0x42;
+        assert!(matches!(
+            load_test_file("bad_rom_size", &data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_fit_rom_size() {
+        // Under-sized dumps are padded
+        let data = RomBuilder::new(0x01).rom_size(0x02).build();
+        let (cart, warnings) = load_test_file("undersized", &data[..0x1_2345]).unwrap();
+        assert_eq!(
+            warnings,
+            vec![LoadWarning::RomResized {
+                actual: 0x1_2345,
+                expected: 0x2_0000
+            }]
+        );
+        assert_eq!(cart.rom().len(), 0x2_0000);
+        assert_eq!(cart.rom()[0x1_2344], 0x00);
+        assert_eq!(cart.rom()[0x1_2345], 0xFF);
+
+        // Over-sized dumps are truncated
+        let mut data = RomBuilder::new(0x01).rom_size(0x02).build();
+        data.extend([0x00; 0x100]);
+        let (cart, warnings) = load_test_file("oversized", &data).unwrap();
+        assert_eq!(
+            warnings,
+            vec![LoadWarning::RomResized {
+                actual: 0x2_0100,
+                expected: 0x2_0000
+            }]
+        );
+        assert_eq!(cart.rom().len(), 0x2_0000);
+
+        // Correctly sized dumps are loaded as-is
+        let data = RomBuilder::new(0x01).rom_size(0x02).build();
+        let (_, warnings) = load_test_file("sized", &data).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_load_fit_mmm01_rom_size() {
+        // 128 KiB MMM01 with the menu header in the last 32 KiB, dumped as 96 KiB
+        let mut data = RomBuilder::new(0xEF).rom_size(0x02).build();
+        let menu = RomBuilder::new(0x0D).rom_size(0x02).build();
+        let menu_start = data.len() - 0x8000;
+        data[menu_start..menu_start + 0x0150].copy_from_slice(&menu[..0x0150]);
+        data[menu_start + 0x1000] = 0x42;
+        data.drain(0x1_0000..0x1_8000);
+        let (cart, warnings) = load_test_file("undersized_mmm01", &data).unwrap();
+        assert_eq!(
+            warnings,
+            vec![LoadWarning::RomResized {
+                actual: 0x1_8000,
+                expected: 0x2_0000
+            }]
+        );
+        assert!(cart.cart_features().mmm01);
+        assert_eq!(cart.rom().len(), 0x2_0000);
+        // The menu is still in the last 32 KiB, after the padding.
+        assert_eq!(cart.rom()[0x1_8000 + 0x1000], 0x42);
+        assert_eq!(cart.rom()[0x1_0000..0x1_8000], [0xFF; 0x8000]);
+        assert_eq!(cart.read_rom(0x1000), 0x42);
+
+        // 64 KiB MMM01 with the header at the start, padded to 128 KiB
+        let mut data = RomBuilder::new(0x0B).rom_size(0x01).build();
+        data.resize(0x2_0000, 0xFF);
+        let (cart, _) = load_test_file("oversized_mmm01", &data).unwrap();
+        assert!(cart.cart_features().mmm01);
+        assert_eq!(cart.rom().len(), 0x1_0000);
+    }
+
+    #[test]
+    fn test_load_mmm01_type_in_bank_data() {
+        // MBC5 game with an MMM01 cart type where the menu header would be
+        let mut data = RomBuilder::new(0x19).rom_size(0x02).build();
+        let menu_start = data.len() - 0x8000;
+        data[menu_start + 0x0147] = 0x0C;
+        let (cart, _) = load_test_file("mbc5_not_mmm01", &data).unwrap();
+        assert!(cart.cart_features().mbc5);
+        assert!(!cart.cart_features().mmm01);
+    }
+
+    #[test]
+    fn test_load_patch() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir()).unwrap();
+        let rom_path = dir.join("rbca_test_patch.gb");
+        let patch_path = dir.join("rbca_test_patch.ips");
+        let data = RomBuilder::new(0x01)
+            .rom_size(0x01)
+            .title("Test Cart")
+            .build();
+        std::fs::write(&rom_path, &data).unwrap();
+        // Change the title & fix the header checksum
+        let mut patch = b"PATCH".to_vec();
+        patch.extend([0x00, 0x01, 0x34, 0x00, 0x01, b'X']);
+        patch.extend([
+            0x00,
+            0x01,
+            0x4D,
+            0x00,
+            0x01,
+            data[0x014D].wrapping_sub(b'X' - data[0x0134]),
+        ]);
+        patch.extend(b"EOF");
+        std::fs::write(&patch_path, &patch).unwrap();
+
+        let cart = load_cartridge(&rom_path);
+        std::fs::remove_file(&rom_path).unwrap();
+        std::fs::remove_file(&patch_path).unwrap();
+        let (cart, _) = cart.unwrap();
+        assert_eq!(cart.header().title, "Xest Cart");
+        assert!(cart.header().header_checksum_valid);
+    }
+
+    #[test]
+    fn test_load_patch_archive_magic() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir()).unwrap();
+        let rom_path = dir.join("rbca_test_patch_magic.gb");
+        let patch_path = dir.join("rbca_test_patch_magic.ips");
+        let data = RomBuilder::new(0x01).rom_size(0x01).build();
+        std::fs::write(&rom_path, &data).unwrap();
+        // Make the patched ROM start with the .gz magic bytes
+        let mut patch = b"PATCH".to_vec();
+        patch.extend([0x00, 0x00, 0x00, 0x00, 0x02, 0x1F, 0x8B]);
+        patch.extend(b"EOF");
+        std::fs::write(&patch_path, &patch).unwrap();
+
+        // The patched ROM isn't extracted a second time.
+        let cart = load_cartridge(&rom_path);
+        std::fs::remove_file(&rom_path).unwrap();
+        std::fs::remove_file(&patch_path).unwrap();
+        let (cart, _) = cart.unwrap();
+        assert_eq!(cart.rom()[0x0000..=0x0001], [0x1F, 0x8B]);
+        assert_eq!(cart.rom()[0x0002..], data[0x0002..]);
+    }
+
+    #[test]
+    fn test_load_gzip() {
+        use std::io::Write;
+
+        let data = RomBuilder::new(0x03).rom_size(0x02).ram_size(0x02).build();
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&data).unwrap();
+        let (cart, _) = load_test_file("gzip", &encoder.finish().unwrap()).unwrap();
+        assert_eq!(cart.rom(), &data[..]);
+
+        // Saves are named after the inner ROM, not the archive
+        let (plain_cart, _) = load_test_file("plain", &data).unwrap();
+        assert_eq!(cart.save_file_name(), plain_cart.save_file_name());
+    }
+}
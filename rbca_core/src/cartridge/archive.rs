@@ -0,0 +1,153 @@
+//! Transparent extraction of ROMs stored in .zip & .gz files.
+use std::io::{Cursor, Read};
+
+use flate2::read::MultiGzDecoder;
+use zip::ZipArchive;
+
+use super::{CartridgeError, BYTES_IN_KIB};
+
+/// Magic bytes at the start of a .zip file (local file header or empty archive).
+const ZIP_MAGIC: [[u8; 4]; 2] = [[0x50, 0x4B, 0x03, 0x04], [0x50, 0x4B, 0x05, 0x06]];
+/// Magic bytes at the start of a .gz file.
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+/// File extensions of ROMs inside a .zip file.
+const ROM_EXTENSIONS: [&str; 2] = [".gb", ".gbc"];
+/// Largest ROM size a header can denote (8 MiB). Extracting stops beyond this.
+const MAX_ROM_SIZE: usize = 8 * 1024 * BYTES_IN_KIB as usize;
+
+/// If the given data is a .zip or .gz file, return the ROM inside it. Otherwise, return the data
+/// unchanged.
+///
+/// For .zip files, the first .gb or .gbc entry is used.
+pub fn extract_rom(data: Vec<u8>) -> Result<Vec<u8>, CartridgeError> {
+    if ZIP_MAGIC.iter().any(|magic| data.starts_with(magic)) {
+        extract_zip(&data)
+    } else if data.starts_with(&GZIP_MAGIC) {
+        extract_gzip(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+fn extract_zip(data: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| CartridgeError::Archive(format!("Bad .zip file: {e}")))?;
+
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .map_err(|e| CartridgeError::Archive(format!("Bad .zip entry: {e}")))?;
+        let name = entry.name().to_ascii_lowercase();
+        if !entry.is_file() || !ROM_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) {
+            continue;
+        }
+
+        return read_capped(entry, &format!(".zip entry {name}"));
+    }
+
+    Err(CartridgeError::Archive(String::from(
+        "No .gb or .gbc file in .zip file",
+    )))
+}
+
+fn extract_gzip(data: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    read_capped(MultiGzDecoder::new(data), ".gz file")
+}
+
+/// Read the extracted ROM, without trusting any sizes stored in the archive. Fail if it is larger
+/// than [MAX_ROM_SIZE].
+fn read_capped(reader: impl Read, source: &str) -> Result<Vec<u8>, CartridgeError> {
+    let mut rom = vec![];
+    reader
+        .take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .map_err(|e| CartridgeError::Archive(format!("Bad {source}: {e}")))?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(CartridgeError::Archive(format!(
+            "{source} is larger than the largest ROM size ({MAX_ROM_SIZE} bytes)"
+        )));
+    }
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use pretty_assertions::assert_eq;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    fn zip_data(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, contents) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_plain() {
+        let rom = super::super::RomBuilder::new(0x00).build();
+        assert_eq!(extract_rom(rom.clone()).unwrap(), rom);
+    }
+
+    #[test]
+    fn test_zip() {
+        let rom = super::super::RomBuilder::new(0x01).rom_size(0x02).build();
+        let data = zip_data(&[
+            ("readme.txt", b"Not a ROM"),
+            ("Game.GBC", &rom),
+            ("other.gb", &[0x00; 0x100]),
+        ]);
+        assert_eq!(extract_rom(data).unwrap(), rom);
+
+        let data = zip_data(&[("readme.txt", b"Not a ROM")]);
+        assert!(matches!(extract_rom(data), Err(CartridgeError::Archive(_))));
+        assert!(matches!(
+            extract_rom(zip_data(&[])),
+            Err(CartridgeError::Archive(_))
+        ));
+    }
+
+    fn gzip_data(contents: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(contents).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_gzip() {
+        let rom = super::super::RomBuilder::new(0x01).rom_size(0x02).build();
+        assert_eq!(extract_rom(gzip_data(&rom)).unwrap(), rom);
+
+        assert!(matches!(
+            extract_rom(vec![0x1F, 0x8B, 0x00, 0x00]),
+            Err(CartridgeError::Archive(_))
+        ));
+    }
+
+    #[test]
+    fn test_size_cap() {
+        let rom = vec![0x00; MAX_ROM_SIZE];
+        assert_eq!(extract_rom(gzip_data(&rom)).unwrap().len(), MAX_ROM_SIZE);
+        assert_eq!(
+            extract_rom(zip_data(&[("game.gb", &rom)])).unwrap().len(),
+            MAX_ROM_SIZE
+        );
+
+        let too_big = vec![0x00; MAX_ROM_SIZE + 1];
+        assert!(matches!(
+            extract_rom(gzip_data(&too_big)),
+            Err(CartridgeError::Archive(_))
+        ));
+        assert!(matches!(
+            extract_rom(zip_data(&[("game.gb", &too_big)])),
+            Err(CartridgeError::Archive(_))
+        ));
+    }
+}
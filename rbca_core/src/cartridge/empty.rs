@@ -1,4 +1,4 @@
-use super::{CartFeatures, Cartridge};
+use super::{Cartridge, CartridgeHeader};
 
 /// An empty cartridge. Allows for read & write testing when no cartridge is inserted.
 /// Acts as a ROM-only cartridge (data from 0x0000..=0x7FFF), but it can be read and written to
@@ -6,13 +6,13 @@ use super::{CartFeatures, Cartridge};
 #[derive(Debug)]
 pub struct CartEmpty {
     rom: Vec<u8>,
-    cart_features: CartFeatures,
+    header: CartridgeHeader,
 }
 impl CartEmpty {
     pub fn new() -> Self {
         Self {
             rom: std::iter::repeat(0x00_u8).take(0x8000).collect(),
-            cart_features: CartFeatures::default(),
+            header: CartridgeHeader::default(),
         }
     }
 }
@@ -21,8 +21,8 @@ impl Cartridge for CartEmpty {
         &self.rom
     }
 
-    fn cart_features(&self) -> &CartFeatures {
-        &self.cart_features
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
     }
 
     fn is_empty(&self) -> bool {
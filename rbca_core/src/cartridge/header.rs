@@ -0,0 +1,512 @@
+//! The cartridge header at 0x0100-0x014F.
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use super::{CartFeatures, BYTES_IN_KIB, LOGO};
+
+/// Old licensee code meaning "use the new licensee code instead".
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+/// Everything in the cartridge header, decoded once when the cartridge is loaded.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct CartridgeHeader {
+    /// Game title, without trailing padding.
+    pub title: String,
+    /// 4-character manufacturer code of later cartridges. Empty if unused.
+    pub manufacturer_code: String,
+    /// CGB support.
+    pub cgb_flag: CgbFlag,
+    /// Whether the cartridge supports SGB functions.
+    pub sgb_flag: bool,
+    /// Raw cartridge type byte.
+    pub cart_type: u8,
+    /// Hardware denoted by the cartridge type.
+    pub cart_features: CartFeatures,
+    /// Amount of ROM (in bytes).
+    pub rom_size: u32,
+    /// Amount of cartridge RAM (in bytes).
+    pub ram_size: u32,
+    /// Where the cartridge was meant to be sold.
+    pub destination: Destination,
+    /// Raw old licensee code.
+    pub old_licensee_code: u8,
+    /// Two-character new licensee code. Only used if the old licensee code is 0x33.
+    pub new_licensee_code: Option<String>,
+    /// Name of the publisher, decoded from the licensee codes.
+    pub publisher: Option<&'static str>,
+    /// Version number of the game (usually 0).
+    pub version_number: u8,
+    /// Whether the boot logo matches the Nintendo logo.
+    pub logo_valid: bool,
+    /// Header checksum stored in the header.
+    pub header_checksum: u8,
+    /// Header checksum calculated from the header.
+    pub computed_header_checksum: u8,
+    /// Whether the header checksum matches. Real hardware refuses to boot otherwise.
+    pub header_checksum_valid: bool,
+    /// Global checksum stored in the header.
+    pub global_checksum: u16,
+    /// Global checksum calculated from the whole ROM.
+    pub computed_global_checksum: u16,
+    /// Whether the global checksum matches. Real hardware doesn't check this.
+    pub global_checksum_valid: bool,
+}
+impl CartridgeHeader {
+    /// Decode the header of the given ROM.
+    pub fn from_data(data: &[u8]) -> Self {
+        Self::from_data_at(data, 0)
+    }
+
+    /// Decode the header of the given ROM, located at the given offset. The global checksum is
+    /// still calculated over the whole ROM.
+    pub fn from_data_at(data: &[u8], offset: usize) -> Self {
+        let header = &data[offset..];
+        let old_licensee_code = header[0x014B];
+        let new_licensee_code = (old_licensee_code == USE_NEW_LICENSEE)
+            .then(|| String::from_utf8_lossy(&header[0x0144..=0x0145]).into_owned());
+        let publisher = match &new_licensee_code {
+            Some(code) => new_licensee_name(code),
+            None => old_licensee_name(old_licensee_code),
+        };
+
+        let header_checksum = header[0x014D];
+        let computed_header_checksum = checksum_fn(header);
+        let global_checksum = u16::from_be_bytes([header[0x014E], header[0x014F]]);
+        let computed_global_checksum = global_checksum_fn(data, offset);
+
+        Self {
+            title: title(header),
+            manufacturer_code: std::str::from_utf8(&header[0x013F..=0x0142])
+                .ok()
+                .filter(|code| code.chars().all(|c| c.is_ascii_uppercase()))
+                .unwrap_or_default()
+                .to_owned(),
+            cgb_flag: CgbFlag::from_byte(header[0x0143]),
+            sgb_flag: header[0x0146] == 0x03,
+            cart_type: header[0x0147],
+            cart_features: CartFeatures::from_data(header),
+            rom_size: rom_size_fn(header[0x0148]),
+            ram_size: ram_size_fn(header[0x0149]),
+            destination: if header[0x014A] == 0x00 {
+                Destination::Japan
+            } else {
+                Destination::Overseas
+            },
+            old_licensee_code,
+            new_licensee_code,
+            publisher,
+            version_number: header[0x014C],
+            logo_valid: header[0x0104..=0x0133] == LOGO,
+            header_checksum,
+            computed_header_checksum,
+            header_checksum_valid: header_checksum == computed_header_checksum,
+            global_checksum,
+            computed_global_checksum,
+            global_checksum_valid: global_checksum == computed_global_checksum,
+        }
+    }
+}
+
+/// Get the title stored in the header. CGB cartridges use the last byte of the title as the CGB
+/// flag.
+fn title(header: &[u8]) -> String {
+    let title = match std::str::from_utf8(&header[0x0134..=0x0143]) {
+        Ok(val) => val,
+        _ => std::str::from_utf8(&header[0x0134..=0x0142]).unwrap_or_default(),
+    };
+    title.trim_end_matches('\0').to_owned()
+}
+
+/// The different states of the CGB header flag.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum CgbFlag {
+    /// DMG cartridge. Compatible with DMG & CGB.
+    #[default]
+    Dmg,
+    /// Cartridge with CGB enhancements that is backwards-compatible with DMG.
+    CgbBkwd,
+    /// Cartridge that works on CGB only.
+    Cgb,
+    /// Unknown PGB mode.
+    Pgb,
+}
+impl CgbFlag {
+    fn from_byte(value: u8) -> Self {
+        match value {
+            0b1000_0000 => Self::CgbBkwd,
+            0b1100_0000 => Self::Cgb,
+            0b1000_1000 | 0b1000_0100 => Self::Pgb,
+            _ => Self::Dmg,
+        }
+    }
+    /// Get the header byte for this flag.
+    pub(super) fn to_byte(self) -> u8 {
+        match self {
+            Self::Dmg => 0x00,
+            Self::CgbBkwd => 0b1000_0000,
+            Self::Cgb => 0b1100_0000,
+            Self::Pgb => 0b1000_0100,
+        }
+    }
+}
+
+/// Where the cartridge was meant to be sold.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum Destination {
+    /// Japan (& possibly overseas).
+    #[default]
+    Japan,
+    /// Overseas only.
+    Overseas,
+}
+impl Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Japan => write!(f, "Japan"),
+            Self::Overseas => write!(f, "Overseas"),
+        }
+    }
+}
+
+/// The function used to calculate the header checksum.
+pub fn checksum_fn(rom: &[u8]) -> u8 {
+    rom[0x0134..=0x014C].iter().fold(0_u8, |checksum, byte| {
+        checksum.wrapping_sub(*byte).wrapping_sub(1)
+    })
+}
+
+/// The function used to calculate the global checksum: the sum of every ROM byte except the
+/// global checksum itself.
+pub(super) fn global_checksum_fn(rom: &[u8], header_offset: usize) -> u16 {
+    let sum = rom
+        .iter()
+        .fold(0_u16, |sum, byte| sum.wrapping_add(*byte as u16));
+    sum.wrapping_sub(rom[header_offset + 0x014E] as u16)
+        .wrapping_sub(rom[header_offset + 0x014F] as u16)
+}
+
+/// Get the ROM size (in bytes) denoted by the given header ROM size value.
+pub fn rom_size_fn(value: u8) -> u32 {
+    match value {
+        val if val <= 0x08 => (1 << val) * 32 * BYTES_IN_KIB,
+        // Unofficial sizes listed in some documentation.
+        0x52 => 72 * 16 * BYTES_IN_KIB,
+        0x53 => 80 * 16 * BYTES_IN_KIB,
+        0x54 => 96 * 16 * BYTES_IN_KIB,
+        _ => 0,
+    }
+}
+
+/// Get the RAM size (in bytes) denoted by the given header RAM size value.
+pub fn ram_size_fn(value: u8) -> u32 {
+    (match value {
+        0x02 => 8,
+        0x03 => 32,
+        0x04 => 128,
+        0x05 => 64,
+        _ => 0,
+    }) * BYTES_IN_KIB
+}
+
+/// Get the name of the publisher with the given old licensee code.
+fn old_licensee_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x01 => "Nintendo",
+        0x08 => "Capcom",
+        0x09 => "HOT-B",
+        0x0A => "Jaleco",
+        0x0B => "Coconuts Japan",
+        0x0C => "Elite Systems",
+        0x13 => "EA (Electronic Arts)",
+        0x18 => "Hudson Soft",
+        0x19 => "ITC Entertainment",
+        0x1A => "Yanoman",
+        0x1D => "Japan Clary",
+        0x1F => "Virgin Games Ltd.",
+        0x24 => "PCM Complete",
+        0x25 => "San-X",
+        0x28 => "Kemco",
+        0x29 => "SETA Corporation",
+        0x30 => "Infogrames",
+        0x31 => "Nintendo",
+        0x32 => "Bandai",
+        0x34 => "Konami",
+        0x35 => "HectorSoft",
+        0x38 => "Capcom",
+        0x39 => "Banpresto",
+        0x3C => "Entertainment Interactive",
+        0x3E => "Gremlin",
+        0x41 => "Ubi Soft",
+        0x42 => "Atlus",
+        0x44 => "Malibu Interactive",
+        0x46 => "Angel",
+        0x47 => "Spectrum HoloByte",
+        0x49 => "Irem",
+        0x4A => "Virgin Games Ltd.",
+        0x4D => "Malibu Interactive",
+        0x4F => "U.S. Gold",
+        0x50 => "Absolute",
+        0x51 => "Acclaim Entertainment",
+        0x52 => "Activision",
+        0x53 => "Sammy USA Corporation",
+        0x54 => "GameTek",
+        0x55 => "Park Place",
+        0x56 => "LJN",
+        0x57 => "Matchbox",
+        0x59 => "Milton Bradley Company",
+        0x5A => "Mindscape",
+        0x5B => "Romstar",
+        0x5C => "Naxat Soft",
+        0x5D => "Tradewest",
+        0x60 => "Titus Interactive",
+        0x61 => "Virgin Games Ltd.",
+        0x67 => "Ocean Software",
+        0x69 => "EA (Electronic Arts)",
+        0x6E => "Elite Systems",
+        0x6F => "Electro Brain",
+        0x70 => "Infogrames",
+        0x71 => "Interplay Entertainment",
+        0x72 => "Broderbund",
+        0x73 => "Sculptured Software",
+        0x75 => "The Sales Curve Limited",
+        0x78 => "THQ",
+        0x79 => "Accolade",
+        0x7A => "Triffix Entertainment",
+        0x7C => "MicroProse",
+        0x7F => "Kemco",
+        0x80 => "Misawa Entertainment",
+        0x83 => "LOZC G.",
+        0x86 => "Tokuma Shoten",
+        0x8B => "Bullet-Proof Software",
+        0x8C => "Vic Tokai Corp.",
+        0x8E => "Ape Inc.",
+        0x8F => "I'Max",
+        0x91 => "Chunsoft Co.",
+        0x92 => "Video System",
+        0x93 => "Tsubaraya Productions",
+        0x95 => "Varie",
+        0x96 => "Yonezawa/S'Pal",
+        0x97 => "Kemco",
+        0x99 => "Arc",
+        0x9A => "Nihon Bussan",
+        0x9B => "Tecmo",
+        0x9C => "Imagineer",
+        0x9D => "Banpresto",
+        0x9F => "Nova",
+        0xA1 => "Hori Electric",
+        0xA2 => "Bandai",
+        0xA4 => "Konami",
+        0xA6 => "Kawada",
+        0xA7 => "Takara",
+        0xA9 => "Technos Japan",
+        0xAA => "Broderbund",
+        0xAC => "Toei Animation",
+        0xAD => "Toho",
+        0xAF => "Namco",
+        0xB0 => "Acclaim Entertainment",
+        0xB1 => "ASCII Corporation or Nexsoft",
+        0xB2 => "Bandai",
+        0xB4 => "Square Enix",
+        0xB6 => "HAL Laboratory",
+        0xB7 => "SNK",
+        0xB9 => "Pony Canyon",
+        0xBA => "Culture Brain",
+        0xBB => "Sunsoft",
+        0xBD => "Sony Imagesoft",
+        0xBF => "Sammy Corporation",
+        0xC0 => "Taito",
+        0xC2 => "Kemco",
+        0xC3 => "Square",
+        0xC4 => "Tokuma Shoten",
+        0xC5 => "Data East",
+        0xC6 => "Tonkin House",
+        0xC8 => "Koei",
+        0xC9 => "UFL",
+        0xCA => "Ultra Games",
+        0xCB => "VAP, Inc.",
+        0xCC => "Use Corporation",
+        0xCD => "Meldac",
+        0xCE => "Pony Canyon",
+        0xCF => "Angel",
+        0xD0 => "Taito",
+        0xD1 => "SOFEL",
+        0xD2 => "Quest",
+        0xD3 => "Sigma Enterprises",
+        0xD4 => "ASK Kodansha Co.",
+        0xD6 => "Naxat Soft",
+        0xD7 => "Copya System",
+        0xD9 => "Banpresto",
+        0xDA => "Tomy",
+        0xDB => "LJN",
+        0xDD => "Nippon Computer Systems",
+        0xDE => "Human Ent.",
+        0xDF => "Altron",
+        0xE0 => "Jaleco",
+        0xE1 => "Towa Chiki",
+        0xE2 => "Yutaka",
+        0xE3 => "Varie",
+        0xE5 => "Epoch",
+        0xE7 => "Athena",
+        0xE8 => "Asmik Ace Entertainment",
+        0xE9 => "Natsume",
+        0xEA => "King Records",
+        0xEB => "Atlus",
+        0xEC => "Epic/Sony Records",
+        0xEE => "IGS",
+        0xF0 => "A Wave",
+        0xF3 => "Extreme Entertainment",
+        0xFF => "LJN",
+        _ => return None,
+    })
+}
+
+/// Get the name of the publisher with the given new licensee code.
+fn new_licensee_name(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "01" => "Nintendo Research & Development 1",
+        "08" => "Capcom",
+        "13" => "EA (Electronic Arts)",
+        "18" => "Hudson Soft",
+        "19" => "B-AI",
+        "20" => "KSS",
+        "22" => "Planning Office WADA",
+        "24" => "PCM Complete",
+        "25" => "San-X",
+        "28" => "Kemco",
+        "29" => "SETA Corporation",
+        "30" => "Viacom",
+        "31" => "Nintendo",
+        "32" => "Bandai",
+        "33" => "Ocean Software/Acclaim Entertainment",
+        "34" => "Konami",
+        "35" => "HectorSoft",
+        "37" => "Taito",
+        "38" => "Hudson Soft",
+        "39" => "Banpresto",
+        "41" => "Ubi Soft",
+        "42" => "Atlus",
+        "44" => "Malibu Interactive",
+        "46" => "Angel",
+        "47" => "Bullet-Proof Software",
+        "49" => "Irem",
+        "50" => "Absolute",
+        "51" => "Acclaim Entertainment",
+        "52" => "Activision",
+        "53" => "Sammy USA Corporation",
+        "54" => "Konami",
+        "55" => "Hi Tech Expressions",
+        "56" => "LJN",
+        "57" => "Matchbox",
+        "58" => "Mattel",
+        "59" => "Milton Bradley Company",
+        "60" => "Titus Interactive",
+        "61" => "Virgin Games Ltd.",
+        "64" => "Lucasfilm Games",
+        "67" => "Ocean Software",
+        "69" => "EA (Electronic Arts)",
+        "70" => "Infogrames",
+        "71" => "Interplay Entertainment",
+        "72" => "Broderbund",
+        "73" => "Sculptured Software",
+        "75" => "The Sales Curve Limited",
+        "78" => "THQ",
+        "79" => "Accolade",
+        "80" => "Misawa Entertainment",
+        "83" => "LOZC G.",
+        "86" => "Tokuma Shoten",
+        "87" => "Tsukuda Original",
+        "91" => "Chunsoft Co.",
+        "92" => "Video System",
+        "93" => "Ocean Software/Acclaim Entertainment",
+        "95" => "Varie",
+        "96" => "Yonezawa/S'Pal",
+        "97" => "Kaneko",
+        "99" => "Pack-In-Video",
+        "9H" => "Bottom Up",
+        "A4" => "Konami (Yu-Gi-Oh!)",
+        "BL" => "MTO",
+        "DK" => "Kodansha",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::super::RomBuilder;
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let mut data = RomBuilder::new(0x13)
+            .rom_size(0x01)
+            .ram_size(0x03)
+            .title("Test Cart")
+            .cgb_flag(CgbFlag::CgbBkwd)
+            .destination(Destination::Overseas)
+            .old_licensee_code(0xA4)
+            .version_number(0x02)
+            .build();
+        let header = CartridgeHeader::from_data(&data);
+
+        assert_eq!(header.title, "Test Cart");
+        assert_eq!(header.manufacturer_code, "");
+        assert_eq!(header.cgb_flag, CgbFlag::CgbBkwd);
+        assert!(!header.sgb_flag);
+        assert_eq!(header.cart_type, 0x13);
+        assert!(header.cart_features.mbc3 && header.cart_features.battery);
+        assert_eq!(header.rom_size, 0x10000);
+        assert_eq!(header.ram_size, 0x8000);
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.new_licensee_code, None);
+        assert_eq!(header.publisher, Some("Konami"));
+        assert_eq!(header.version_number, 0x02);
+        assert!(header.logo_valid);
+        assert!(header.header_checksum_valid);
+        assert!(header.global_checksum_valid);
+
+        // Corrupt the header
+        data[0x0104] = 0x00;
+        data[0x0134] = b'B';
+        let header = CartridgeHeader::from_data(&data);
+        assert!(!header.logo_valid);
+        assert!(!header.header_checksum_valid);
+        assert!(!header.global_checksum_valid);
+    }
+
+    #[test]
+    fn test_new_licensee() {
+        let mut data = RomBuilder::default().new_licensee_code(*b"01").build();
+        let header = CartridgeHeader::from_data(&data);
+        assert_eq!(header.new_licensee_code.as_deref(), Some("01"));
+        assert_eq!(header.publisher, Some("Nintendo Research & Development 1"));
+
+        data[0x0144..=0x0145].copy_from_slice(b"ZZ");
+        let header = CartridgeHeader::from_data(&data);
+        assert_eq!(header.publisher, None);
+    }
+
+    #[test]
+    fn test_rom_sizes() {
+        assert_eq!(rom_size_fn(0x00), 0x8000);
+        assert_eq!(rom_size_fn(0x08), 0x80_0000);
+        assert_eq!(rom_size_fn(0x52), 0x12_0000);
+        assert_eq!(rom_size_fn(0x53), 0x14_0000);
+        assert_eq!(rom_size_fn(0x54), 0x18_0000);
+        assert_eq!(rom_size_fn(0x09), 0);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let data = RomBuilder::new(0x01).title("Test Cart").build();
+        let json = serde_json::to_value(CartridgeHeader::from_data(&data)).unwrap();
+        assert_eq!(json["title"], "Test Cart");
+        assert_eq!(json["cgb_flag"], "Dmg");
+        assert_eq!(json["cart_features"]["mbc1"], true);
+        assert_eq!(json["destination"], "Japan");
+        assert_eq!(json["publisher"], serde_json::Value::Null);
+        assert_eq!(json["header_checksum_valid"], true);
+    }
+}
@@ -0,0 +1,261 @@
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB,
+    RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// A HuC1 cartridge. Banks like an MBC1, with an infrared transceiver in place of RAM enable.
+#[derive(Debug)]
+pub struct CartHuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    /// false = RAM mapped to 0xA000-0xBFFF, true = IR register mapped to 0xA000-0xBFFF.
+    ir_select: bool,
+    /// Infrared LED state.
+    ir_led: bool,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+}
+impl CartHuC1 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mut huc1 = Self {
+            rom: data,
+            ram: vec![],
+            header,
+            save_dirty: false,
+            ir_select: false,
+            ir_led: false,
+            rom_bank_number: 0x01,
+            ram_bank_number: 0x00,
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        huc1.ram = vec![0x00_u8; huc1.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Cart features include HuC1
+        if !huc1.header.cart_features.huc1 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New HuC1: According to the header, this cartridge is not a HuC1. Cart type from header: {}", huc1.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if huc1.rom().len() != huc1.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New HuC1: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", huc1.rom().len(), huc1.header.rom_size)));
+        }
+
+        // Max ROM size
+        if huc1.rom().len() > (1024 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New HuC1: ROM size too big ({} MiB > 1 MiB)",
+                huc1.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+        // Max RAM size
+        if huc1.ram.len() > (32 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New HuC1: RAM size too big ({} KiB > 32 KiB)",
+                huc1.ram.len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(huc1)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u8 {
+        match self.rom_bank_number & 0b0011_1111 {
+            // If register is set to 0, ROM bank number = 1.
+            0x00 => 0x01,
+            // Bank numbers higher than the number of banks in the cart are masked.
+            rbn => rbn & (self.num_rom_banks() - 1),
+        }
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                result |= ((self.ram_bank_number & 0b0000_0011) as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    #[cfg(test)]
+    fn new_test(rom_size_value: u8, ram_size_value: u8) -> Self {
+        CartHuC1::new(
+            super::RomBuilder::new(0xFF)
+                .rom_size(rom_size_value)
+                .ram_size(ram_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartHuC1 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // The IR port is mapped instead of RAM when selected.
+            ram_bank: (!self.ir_select && !self.ram.is_empty())
+                .then(|| self.internal_addr(0xA000) / RAM_BANK_SIZE),
+            ram_enabled: !self.ir_select,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_save_section(&mut self.ram, data);
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_select {
+            // Bit 0 = 1 iff light is being received. There's never any light to receive.
+            return 0xC0;
+        }
+        *self
+            .ram
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM/IR select
+            0x0000..=0x1FFF => self.ir_select = (value & 0x0F) == 0x0E,
+            // ROM bank number
+            0x2000..=0x3FFF => self.rom_bank_number = value & 0b0011_1111,
+            // RAM bank number
+            0x4000..=0x5FFF => self.ram_bank_number = value & 0b0000_0011,
+            // Unused
+            0x6000..=0x7FFF => {}
+            _ => panic!("HuC1 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ir_select {
+            self.ir_led = (value & 0b0000_0001) != 0;
+            return;
+        }
+        let addr = self.internal_addr(address);
+        if addr < self.ram.len() {
+            self.ram[addr] = value;
+            self.save_dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0x01).rom_size(0x01).build();
+        assert!(matches!(
+            CartHuC1::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let huc1 = CartHuC1::new_test(0x05, 0x03);
+        assert!(huc1.header().logo_valid);
+        assert_eq!(huc1.num_rom_banks(), 64);
+        assert!(huc1.cart_features().huc1);
+        assert!(huc1.cart_features().battery);
+        assert_eq!(huc1.ram.len(), 0x8000);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut huc1 = CartHuC1::new_test(0x05, 0x00);
+        for bank_num in 0..64 {
+            huc1.rom[(bank_num * 0x4000) + 0x0042] = bank_num as u8;
+        }
+
+        huc1.write_rom(0x2000, 0x00);
+        assert_eq!(huc1.read_rom(0x4042), 0x01);
+        for bank_num in 1..64 {
+            huc1.write_rom(0x3FFF, bank_num);
+            assert_eq!(huc1.read_rom(0x4042), bank_num);
+            assert_eq!(huc1.read_rom(0x0042), 0x00);
+        }
+    }
+
+    #[test]
+    fn test_ram_ir_select() {
+        let mut huc1 = CartHuC1::new_test(0x01, 0x03);
+
+        // RAM is mapped by default
+        huc1.write_rom(0x4000, 0x02);
+        huc1.write_ram(0xA123, 0x45);
+        assert_eq!(huc1.ram[0x4123], 0x45);
+        assert_eq!(huc1.read_ram(0xA123), 0x45);
+
+        // IR register
+        huc1.write_rom(0x0000, 0x0E);
+        assert_eq!(huc1.read_ram(0xA123), 0xC0);
+        huc1.write_ram(0xA123, 0x01);
+        assert!(huc1.ir_led);
+        assert_eq!(huc1.ram[0x4123], 0x45);
+        huc1.write_ram(0xA000, 0x00);
+        assert!(!huc1.ir_led);
+
+        // Back to RAM
+        huc1.write_rom(0x0000, 0x0A);
+        assert_eq!(huc1.read_ram(0xA123), 0x45);
+    }
+}
@@ -0,0 +1,533 @@
+use std::time::{Duration, SystemTime};
+
+use super::{
+    from_unix_timestamp, load_save_section, to_unix_timestamp, Cartridge, CartridgeError,
+    CartridgeHeader, MapperState, BYTES_IN_KIB, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// Number of minutes in a day. The minute counter wraps to 0 & increments the day counter here.
+const MINUTES_PER_DAY: u16 = 1440;
+/// Size of the clock data appended to save files.
+const CLOCK_SAVE_SIZE: usize = 17;
+
+/// A HuC3 cartridge.
+#[derive(Debug)]
+pub struct CartHuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    /// Selects what is mapped to 0xA000-0xBFFF.
+    ///
+    /// 0x00 = RAM (read-only), 0x0A = RAM (read/write), 0x0B = clock command, 0x0C = clock
+    /// command result, 0x0D = clock semaphore, 0x0E = IR register. Anything else = nothing.
+    mode: u8,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    /// Infrared LED state.
+    ir_led: bool,
+    clock: Clock,
+}
+impl CartHuC3 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mut huc3 = Self {
+            rom: data,
+            ram: vec![],
+            header,
+            save_dirty: false,
+            mode: 0x00,
+            rom_bank_number: 0x01,
+            ram_bank_number: 0x00,
+            ir_led: false,
+            clock: Clock::new(),
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        huc3.ram = vec![0x00_u8; huc3.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Cart features include HuC3
+        if !huc3.header.cart_features.huc3 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New HuC3: According to the header, this cartridge is not a HuC3. Cart type from header: {}", huc3.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if huc3.rom().len() != huc3.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New HuC3: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", huc3.rom().len(), huc3.header.rom_size)));
+        }
+
+        // Max ROM size
+        if huc3.rom().len() > (2 * 1024 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New HuC3: ROM size too big ({} MiB > 2 MiB)",
+                huc3.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+        // Max RAM size
+        if huc3.ram.len() > (32 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New HuC3: RAM size too big ({} KiB > 32 KiB)",
+                huc3.ram.len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(huc3)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u8 {
+        match self.rom_bank_number & 0b0111_1111 {
+            // If register is set to 0, ROM bank number = 1.
+            0x00 => 0x01,
+            // Bank numbers higher than the number of banks in the cart are masked.
+            rbn => rbn & (self.num_rom_banks() - 1),
+        }
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                result |= ((self.ram_bank_number & 0b0000_0011) as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    #[cfg(test)]
+    fn new_test(rom_size_value: u8, ram_size_value: u8) -> Self {
+        CartHuC3::new(
+            super::RomBuilder::new(0xFE)
+                .rom_size(rom_size_value)
+                .ram_size(ram_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartHuC3 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // Other modes map the clock or IR port instead of RAM.
+            ram_bank: (matches!(self.mode, 0x00 | 0x0A) && !self.ram.is_empty())
+                .then(|| self.internal_addr(0xA000) / RAM_BANK_SIZE),
+            ram_enabled: self.mode == 0x0A,
+            mode: self.mode,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        let mut data = self.ram.clone();
+        data.extend(self.clock.save_bytes());
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let rest = load_save_section(&mut self.ram, data);
+        self.clock.load_bytes(rest);
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            0x00 | 0x0A => *self
+                .ram
+                .get(self.internal_addr(address))
+                .unwrap_or(&0xFF_u8),
+            0x0C => self.clock.response(),
+            // Bit 0 = 1 iff the clock is ready. Commands finish instantly.
+            0x0D => 0x01,
+            // Bit 0 = 1 iff light is being received. There's never any light to receive.
+            0x0E => 0xC0,
+            _ => 0xFF_u8,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // Mode select
+            0x0000..=0x1FFF => self.mode = value & 0x0F,
+            // ROM bank number
+            0x2000..=0x3FFF => self.rom_bank_number = value & 0b0111_1111,
+            // RAM bank number
+            0x4000..=0x5FFF => self.ram_bank_number = value & 0b0000_0011,
+            // Unused
+            0x6000..=0x7FFF => {}
+            _ => panic!("HuC3 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        match self.mode {
+            0x0A => {
+                let addr = self.internal_addr(address);
+                if addr < self.ram.len() {
+                    self.ram[addr] = value;
+                    self.save_dirty = true;
+                }
+            }
+            0x0B => {
+                self.clock.update();
+                self.clock.command(value);
+                self.save_dirty = true;
+            }
+            0x0E => self.ir_led = (value & 0b0000_0001) != 0,
+            _ => {}
+        }
+    }
+}
+
+/// The HuC3 clock. Counts minutes & days using the host system clock.
+///
+/// The program talks to the clock through a small nibble-addressed memory. The current time lives
+/// at 0x00-0x05 (minute of the day, then day, 3 nibbles each, least significant first) & is only
+/// exchanged with the counters on request.
+#[derive(Debug, Clone)]
+struct Clock {
+    /// Minute of the day, 0-1439.
+    minutes: u16,
+    /// 12-bit day counter.
+    days: u16,
+    /// Seconds counted towards the next minute.
+    seconds: u8,
+    /// Clock memory. Each byte holds one nibble.
+    memory: [u8; 0x100],
+    /// Clock memory address used by the next read/write command.
+    address: u8,
+    /// The last command sent to the clock (bits 4-6).
+    last_command: u8,
+    /// The result of the last read command.
+    result: u8,
+    /// The host time up to which the counters have been advanced.
+    last_update: SystemTime,
+}
+impl Clock {
+    fn new() -> Self {
+        Self {
+            minutes: 0,
+            days: 0,
+            seconds: 0,
+            memory: [0x00; 0x100],
+            address: 0x00,
+            last_command: 0x00,
+            result: 0x00,
+            last_update: SystemTime::now(),
+        }
+    }
+
+    /// Get the clock data appended to save files, all little-endian: the Unix time of the current
+    /// minute as a 64-bit value, the minute & day counters as 16-bit values, then the alarm minute
+    /// & day counters (16-bit) & alarm enable (8-bit), which are unused & always 0.
+    fn save_bytes(&self) -> Vec<u8> {
+        let minute_start = self.last_update - Duration::from_secs(self.seconds as u64);
+        let mut bytes = Vec::with_capacity(CLOCK_SAVE_SIZE);
+        bytes.extend(to_unix_timestamp(minute_start).to_le_bytes());
+        bytes.extend(self.minutes.to_le_bytes());
+        bytes.extend(self.days.to_le_bytes());
+        bytes.extend([0x00; 5]);
+        bytes
+    }
+
+    /// Restore the counters from save file clock data, then catch up to the current time.
+    fn load_bytes(&mut self, data: &[u8]) {
+        if data.len() < CLOCK_SAVE_SIZE {
+            return;
+        }
+        let timestamp = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        self.minutes = u16::from_le_bytes([data[8], data[9]]) % MINUTES_PER_DAY;
+        self.days = u16::from_le_bytes([data[10], data[11]]) & 0x0FFF;
+        self.seconds = 0;
+        self.last_update = from_unix_timestamp(timestamp);
+        self.update();
+    }
+
+    /// Advance the counters to the current host time.
+    fn update(&mut self) {
+        let now = SystemTime::now();
+        // If the host clock went backwards, don't move the clock.
+        let elapsed = now.duration_since(self.last_update).unwrap_or_default();
+        let elapsed_secs = elapsed.as_secs();
+        self.last_update += Duration::from_secs(elapsed_secs);
+        self.advance(elapsed_secs);
+    }
+
+    /// Advance the counters by the given number of seconds.
+    fn advance(&mut self, secs: u64) {
+        let total_secs = (self.seconds as u64) + secs;
+        self.seconds = (total_secs % 60) as u8;
+
+        let total_minutes = (self.minutes as u64) + (total_secs / 60);
+        self.minutes = (total_minutes % (MINUTES_PER_DAY as u64)) as u16;
+        let days = (self.days as u64) + (total_minutes / (MINUTES_PER_DAY as u64));
+        self.days = (days & 0x0FFF) as u16;
+    }
+
+    /// Execute a command written in mode 0x0B. Bits 4-6 = command, bits 0-3 = argument.
+    fn command(&mut self, value: u8) {
+        let command = (value >> 4) & 0b0000_0111;
+        let argument = value & 0b0000_1111;
+        self.last_command = command;
+        match command {
+            // Read the nibble at the current address, then increment the address.
+            0x1 => {
+                self.result = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            // Write the argument to the current address, then increment the address.
+            0x3 => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            // Set the lower nibble of the address.
+            0x4 => self.address = (self.address & 0xF0) | argument,
+            // Set the upper nibble of the address.
+            0x5 => self.address = (self.address & 0x0F) | (argument << 4),
+            // Extended command.
+            0x6 => match argument {
+                // Copy the current time to clock memory.
+                0x0 => self.store_time(),
+                // Set the current time from clock memory.
+                0x1 => self.load_time(),
+                // Tone generator & status queries aren't emulated.
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// The value read in mode 0x0C. Bits 4-6 = last command, bits 0-3 = last read result.
+    fn response(&self) -> u8 {
+        0b1000_0000 | (self.last_command << 4) | (self.result & 0x0F)
+    }
+
+    fn store_time(&mut self) {
+        for i in 0..3 {
+            self.memory[i] = ((self.minutes >> (i * 4)) & 0x0F) as u8;
+            self.memory[i + 3] = ((self.days >> (i * 4)) & 0x0F) as u8;
+        }
+    }
+
+    fn load_time(&mut self) {
+        let mut minutes = 0;
+        let mut days = 0;
+        for i in 0..3 {
+            minutes |= (self.memory[i] as u16) << (i * 4);
+            days |= (self.memory[i + 3] as u16) << (i * 4);
+        }
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = days;
+        self.seconds = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn set_address(huc3: &mut CartHuC3, address: u8) {
+        huc3.write_ram(0xA000, 0x40 | (address & 0x0F));
+        huc3.write_ram(0xA000, 0x50 | (address >> 4));
+    }
+
+    fn read_nibble(huc3: &mut CartHuC3) -> u8 {
+        huc3.write_rom(0x0000, 0x0B);
+        huc3.write_ram(0xA000, 0x10);
+        huc3.write_rom(0x0000, 0x0C);
+        let result = huc3.read_ram(0xA000);
+        huc3.write_rom(0x0000, 0x0B);
+        result & 0x0F
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0xFF).rom_size(0x01).build();
+        assert!(matches!(
+            CartHuC3::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let huc3 = CartHuC3::new_test(0x06, 0x03);
+        assert!(huc3.header().logo_valid);
+        assert_eq!(huc3.num_rom_banks(), 128);
+        assert!(huc3.cart_features().huc3);
+        assert_eq!(huc3.ram.len(), 0x8000);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut huc3 = CartHuC3::new_test(0x06, 0x00);
+        for bank_num in 0..128 {
+            huc3.rom[(bank_num * 0x4000) + 0x3210] = bank_num as u8;
+        }
+
+        huc3.write_rom(0x2000, 0x00);
+        assert_eq!(huc3.read_rom(0x7210), 0x01);
+        for bank_num in 1..128 {
+            huc3.write_rom(0x2000, bank_num);
+            assert_eq!(huc3.read_rom(0x7210), bank_num);
+            assert_eq!(huc3.read_rom(0x3210), 0x00);
+        }
+    }
+
+    #[test]
+    fn test_ram_modes() {
+        let mut huc3 = CartHuC3::new_test(0x01, 0x03);
+        huc3.ram[0x6001] = 0x12;
+
+        // Read-only RAM
+        huc3.write_rom(0x4000, 0x03);
+        assert_eq!(huc3.read_ram(0xA001), 0x12);
+        huc3.write_ram(0xA001, 0x34);
+        assert_eq!(huc3.read_ram(0xA001), 0x12);
+
+        // Read/write RAM
+        huc3.write_rom(0x0000, 0x0A);
+        huc3.write_ram(0xA001, 0x34);
+        assert_eq!(huc3.read_ram(0xA001), 0x34);
+
+        // Semaphore always ready
+        huc3.write_rom(0x0000, 0x0D);
+        assert_eq!(huc3.read_ram(0xA001) & 0x01, 0x01);
+
+        // IR
+        huc3.write_rom(0x0000, 0x0E);
+        assert_eq!(huc3.read_ram(0xA001), 0xC0);
+        huc3.write_ram(0xA001, 0x01);
+        assert!(huc3.ir_led);
+        assert_eq!(huc3.ram[0x6001], 0x34);
+
+        // Unmapped
+        huc3.write_rom(0x0000, 0x05);
+        assert_eq!(huc3.read_ram(0xA001), 0xFF);
+    }
+
+    #[test]
+    fn test_clock_memory() {
+        let mut huc3 = CartHuC3::new_test(0x01, 0x00);
+        huc3.write_rom(0x0000, 0x0B);
+
+        // Write 3 nibbles starting at 0x42
+        set_address(&mut huc3, 0x42);
+        huc3.write_ram(0xA000, 0x37);
+        huc3.write_ram(0xA000, 0x38);
+        huc3.write_ram(0xA000, 0x39);
+        assert_eq!(huc3.clock.address, 0x45);
+
+        set_address(&mut huc3, 0x42);
+        assert_eq!(read_nibble(&mut huc3), 0x07);
+        assert_eq!(read_nibble(&mut huc3), 0x08);
+        assert_eq!(read_nibble(&mut huc3), 0x09);
+
+        // Response includes the last command
+        huc3.write_ram(0xA000, 0x10);
+        huc3.write_rom(0x0000, 0x0C);
+        assert_eq!(huc3.read_ram(0xA000), 0x90);
+    }
+
+    #[test]
+    fn test_clock_time() {
+        let mut huc3 = CartHuC3::new_test(0x01, 0x00);
+        huc3.write_rom(0x0000, 0x0B);
+
+        // Set the time to day 0x123, minute 1439
+        set_address(&mut huc3, 0x00);
+        for nibble in [0x0F, 0x09, 0x05, 0x03, 0x02, 0x01] {
+            huc3.write_ram(0xA000, 0x30 | nibble);
+        }
+        huc3.write_ram(0xA000, 0x61);
+        assert_eq!(huc3.clock.minutes, 1439);
+        assert_eq!(huc3.clock.days, 0x123);
+
+        // One minute later, the day rolls over
+        huc3.clock.advance(59);
+        assert_eq!(huc3.clock.minutes, 1439);
+        huc3.clock.advance(1);
+        assert_eq!(huc3.clock.minutes, 0);
+        assert_eq!(huc3.clock.days, 0x124);
+
+        huc3.clock.advance(61 * 60);
+        huc3.write_ram(0xA000, 0x60);
+        set_address(&mut huc3, 0x00);
+        let nibbles: Vec<u8> = (0..6).map(|_| read_nibble(&mut huc3)).collect();
+        assert_eq!(nibbles, vec![0x0D, 0x03, 0x00, 0x04, 0x02, 0x01]);
+
+        // Day counter is 12 bits
+        huc3.clock.days = 0x0FFF;
+        huc3.clock.advance(MINUTES_PER_DAY as u64 * 60);
+        assert_eq!(huc3.clock.days, 0x000);
+    }
+
+    #[test]
+    fn test_save_data() {
+        let mut huc3 = CartHuC3::new_test(0x01, 0x02);
+        huc3.ram[0x0123] = 0x45;
+        huc3.clock.minutes = 1439;
+        huc3.clock.days = 0x123;
+
+        let data = huc3.save_data().unwrap();
+        assert_eq!(data.len(), 0x2000 + CLOCK_SAVE_SIZE);
+
+        let mut loaded = CartHuC3::new_test(0x01, 0x02);
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.ram[0x0123], 0x45);
+        assert_eq!(loaded.clock.minutes, 1439);
+        assert_eq!(loaded.clock.days, 0x123);
+
+        // Time passes while the emulator is closed
+        let mut data = data;
+        let timestamp = to_unix_timestamp(SystemTime::now()) - 120;
+        data[0x2000..0x2008].copy_from_slice(&timestamp.to_le_bytes());
+        let mut loaded = CartHuC3::new_test(0x01, 0x02);
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.clock.minutes, 1);
+        assert_eq!(loaded.clock.days, 0x124);
+    }
+}
@@ -1,77 +1,114 @@
-use super::{CartFeatures, Cartridge, BYTES_IN_KIB};
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB, LOGO,
+    RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// Size of each game in an MBC1 multicart (MBC1M).
+const MULTICART_SUB_ROM_SIZE: usize = 256 * (BYTES_IN_KIB as usize);
+
+/// Guess whether the given ROM is an MBC1 multicart (MBC1M). Multicarts are 1 MiB compilations
+/// made of 256 KiB sub-ROMs, each of which starts with its own header & Nintendo logo.
+pub fn is_multicart(data: &[u8]) -> bool {
+    if data.len() != 4 * MULTICART_SUB_ROM_SIZE {
+        return false;
+    }
+
+    let num_logos = data
+        .chunks(MULTICART_SUB_ROM_SIZE)
+        .filter(|sub_rom| sub_rom[0x0104..=0x0133] == LOGO)
+        .count();
+    // A regular ROM has a single logo in bank 0.
+    num_logos > 1
+}
 
 /// An MBC1 cartridge.
 #[derive(Debug)]
 pub struct CartMBC1 {
     rom: Vec<u8>,
     ram: Vec<u8>,
-    cart_features: CartFeatures,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
     ram_enable: bool,
     rom_bank_number: u8,
     ram_bank_number: u8,
     /// false = 0 = simple, true = 1 = advanced
     banking_mode_select: bool,
+    /// MBC1M wiring: the ROM bank number uses 4 bits instead of 5, & the secondary bank register
+    /// is shifted into bits 4-5 of the bank number.
+    multicart: bool,
 }
 impl CartMBC1 {
-    pub fn new(data: Vec<u8>) -> Self {
-        let cart_features = CartFeatures::from_data(&data);
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        Self::new_helper(data, false)
+    }
+
+    /// Create a new MBC1 multicart (MBC1M).
+    pub fn new_multicart(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        Self::new_helper(data, true)
+    }
+
+    fn new_helper(data: Vec<u8>, multicart: bool) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
 
         let mut mbc1 = Self {
             rom: data,
             ram: vec![],
-            cart_features,
+            header,
+            save_dirty: false,
             ram_enable: false,
             rom_bank_number: 0x00,
             ram_bank_number: 0x00,
             banking_mode_select: false,
+            multicart,
         };
 
         // Allocate RAM based on RAM size denoted in cartridge header
         mbc1.ram = std::iter::repeat(0x00_u8)
-            .take(mbc1.ram_size() as usize)
+            .take(mbc1.header.ram_size as usize)
             .collect();
 
         // Validate proper parameters
         // Cart features include MBC1
-        if !mbc1.cart_features.mbc1 {
-            panic!("New MBC1: According to the header, this cartridge is not an MBC1. Cart type from header: {}", mbc1.cart_features);
+        if !mbc1.header.cart_features.mbc1 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New MBC1: According to the header, this cartridge is not an MBC1. Cart type from header: {}", mbc1.header.cart_features)));
         }
 
         // Allocated ROM size matches ROM size in header
-        if mbc1.rom().len() != mbc1.rom_size() as usize {
-            panic!("New MBC1: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc1.rom().len(), mbc1.rom_size());
+        if mbc1.rom().len() != mbc1.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MBC1: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc1.rom().len(), mbc1.header.rom_size)));
         }
 
         // Max ROM size
         if mbc1.rom().len() > (2048 * BYTES_IN_KIB) as usize {
-            panic!(
+            return Err(CartridgeError::SizeMismatch(format!(
                 "New MBC1: ROM size too big ({} MiB > 2 MiB)",
                 mbc1.rom().len() / 1024 / (BYTES_IN_KIB as usize)
-            );
+            )));
         }
         // Max RAM size
         if mbc1.ram.len() > (32 * BYTES_IN_KIB) as usize {
-            panic!(
+            return Err(CartridgeError::SizeMismatch(format!(
                 "New MBC1: RAM size too big ({} KiB > 32 KiB)",
                 mbc1.ram.len() / (BYTES_IN_KIB as usize)
-            );
+            )));
         }
         // Max ROM size + max RAM size
         if (mbc1.rom().len() > (512 * BYTES_IN_KIB) as usize)
             && (mbc1.ram.len() > (8 * BYTES_IN_KIB) as usize)
         {
-            panic!("New MBC1: MBC1 with >512 KiB of ROM can only have up to 8 KiB of RAM ({} KiB > 8 KiB)", mbc1.ram.len() / (BYTES_IN_KIB as usize));
+            return Err(CartridgeError::SizeMismatch(format!("New MBC1: MBC1 with >512 KiB of ROM can only have up to 8 KiB of RAM ({} KiB > 8 KiB)", mbc1.ram.len() / (BYTES_IN_KIB as usize))));
         }
 
-        mbc1
+        Ok(mbc1)
     }
 
     fn num_rom_banks(&self) -> u8 {
-        (self.rom_size() / 0x4000).try_into().unwrap()
+        (self.header.rom_size / 0x4000).try_into().unwrap()
     }
 
     fn num_ram_banks(&self) -> u8 {
-        (self.ram_size() / 0x2000).try_into().unwrap()
+        (self.header.ram_size / 0x2000).try_into().unwrap()
     }
 
     fn get_rom_bank_number(&self) -> u8 {
@@ -98,17 +135,24 @@ impl CartMBC1 {
     }
 
     fn internal_addr(&self, address: u16) -> usize {
+        // On multicarts, the secondary bank register is wired one bit lower.
+        let (upper_bank_shift, rom_bank_mask) = if self.multicart {
+            (18, 0b0000_1111)
+        } else {
+            (19, 0b0001_1111)
+        };
+
         let mut result: u32 = 0x0000_0000;
         match address {
             0x0000..=0x3FFF => {
                 if self.banking_mode_select {
-                    result |= (self.get_ram_bank_number() as u32) << 19;
+                    result |= (self.get_ram_bank_number() as u32) << upper_bank_shift;
                 }
                 result |= (address as u32) & 0b0011_1111_1111_1111;
             }
             0x4000..=0x7FFF => {
-                result |= (self.get_ram_bank_number() as u32) << 19;
-                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (self.get_ram_bank_number() as u32) << upper_bank_shift;
+                result |= ((self.get_rom_bank_number() & rom_bank_mask) as u32) << 14;
                 result |= (address as u32) & 0b0011_1111_1111_1111;
             }
             0xA000..=0xBFFF => {
@@ -132,16 +176,14 @@ impl CartMBC1 {
         num_rom_banks: usize,
         num_ram_banks: usize,
     ) -> Self {
-        use super::{checksum_fn, BYTES_IN_KIB, LOGO};
-
-        let (rom_bytes, rom_size_value) = match num_rom_banks {
-            2 => (32 * BYTES_IN_KIB, 0x00_u8),
-            4 => (64 * BYTES_IN_KIB, 0x01_u8),
-            8 => (128 * BYTES_IN_KIB, 0x02_u8),
-            16 => (256 * BYTES_IN_KIB, 0x03_u8),
-            32 => (512 * BYTES_IN_KIB, 0x04_u8),
-            64 => (1024 * BYTES_IN_KIB, 0x05_u8),
-            128 => (2048 * BYTES_IN_KIB, 0x06_u8),
+        let rom_size_value = match num_rom_banks {
+            2 => 0x00_u8,
+            4 => 0x01_u8,
+            8 => 0x02_u8,
+            16 => 0x03_u8,
+            32 => 0x04_u8,
+            64 => 0x05_u8,
+            128 => 0x06_u8,
             _ => panic!("Illegal value for num_rom_banks ({num_rom_banks})"),
         };
 
@@ -154,26 +196,20 @@ impl CartMBC1 {
             ),
         };
 
-        let mut data: Vec<u8> = std::iter::repeat(0x00_u8)
-            .take(rom_bytes as usize)
-            .collect();
-
-        data[0x0101..=0x0103].copy_from_slice(&[0xC3, 0x50, 0x01]);
-        data[0x0104..=0x0133].copy_from_slice(&LOGO);
-        data[0x0134..=0x013E].copy_from_slice(&[
-            0x54, 0x65, 0x73, 0x74, 0x20, 0x43, 0x61, 0x72, 0x74, 0x00, 0x00,
-        ]);
-        data[0x147] = match (has_ram, has_battery) {
+        let cart_type = match (has_ram, has_battery) {
             (true, true) => 0x03,
             (true, false) => 0x02,
             (false, false) => 0x01,
             _ => panic!("Any MBC1 with a battery must have RAM."),
         };
-        data[0x148] = rom_size_value;
-        data[0x149] = ram_size_value;
-        data[0x14D] = checksum_fn(&data);
 
-        CartMBC1::new(data)
+        let data = super::RomBuilder::new(cart_type)
+            .rom_size(rom_size_value)
+            .ram_size(ram_size_value)
+            .title("Test Cart")
+            .build();
+
+        CartMBC1::new(data).unwrap()
     }
 }
 impl Cartridge for CartMBC1 {
@@ -181,8 +217,41 @@ impl Cartridge for CartMBC1 {
         &self.rom
     }
 
-    fn cart_features(&self) -> &CartFeatures {
-        &self.cart_features
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: self.internal_addr(0x0000) / ROM_BANK_SIZE,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            ram_bank: (!self.ram.is_empty()).then(|| self.internal_addr(0xA000) / RAM_BANK_SIZE),
+            ram_enabled: self.ram_enable,
+            mode: self.banking_mode_select as u8,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.header.cart_features.battery {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.header.cart_features.battery {
+            load_save_section(&mut self.ram, data);
+        }
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
     }
 
     fn read_rom(&self, address: u16) -> u8 {
@@ -227,6 +296,7 @@ impl Cartridge for CartMBC1 {
         let addr = self.internal_addr(address);
         if addr < self.ram.len() {
             self.ram[addr] = value;
+            self.save_dirty = true;
         }
     }
 }
@@ -238,29 +308,32 @@ mod tests {
     use super::*;
 
     #[test]
-    #[should_panic]
     fn test_bad_rom_size_header() {
         let mut data: Vec<u8> = std::iter::repeat(0x00_u8)
             .take((64 * BYTES_IN_KIB) as usize)
             .collect();
         data[0x0147] = 0x01;
         data[0x0148] = 0x02;
-        let _ = CartMBC1::new(data);
+        assert!(matches!(
+            CartMBC1::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
     }
 
     #[test]
-    #[should_panic]
     fn test_rom_too_large() {
         let mut data: Vec<u8> = std::iter::repeat(0x00_u8)
             .take((4 * 1024 * BYTES_IN_KIB) as usize)
             .collect();
         data[0x0147] = 0x01;
         data[0x0148] = 0x07;
-        let _ = CartMBC1::new(data);
+        assert!(matches!(
+            CartMBC1::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
     }
 
     #[test]
-    #[should_panic]
     fn test_ram_too_large() {
         let mut data: Vec<u8> = std::iter::repeat(0x00_u8)
             .take((64 * BYTES_IN_KIB) as usize)
@@ -268,11 +341,13 @@ mod tests {
         data[0x0147] = 0x03;
         data[0x0148] = 0x01;
         data[0x0149] = 0x05;
-        let _ = CartMBC1::new(data);
+        assert!(matches!(
+            CartMBC1::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
     }
 
     #[test]
-    #[should_panic]
     fn test_rom_ram_too_large() {
         let mut data: Vec<u8> = std::iter::repeat(0x00_u8)
             .take((1024 * BYTES_IN_KIB) as usize)
@@ -280,11 +355,13 @@ mod tests {
         data[0x0147] = 0x03;
         data[0x0148] = 0x05;
         data[0x0149] = 0x03;
-        let _ = CartMBC1::new(data);
+        assert!(matches!(
+            CartMBC1::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
     }
 
     #[test]
-    #[should_panic]
     fn test_bad_header() {
         let mut data: Vec<u8> = std::iter::repeat(0x00_u8)
             .take((1024 * BYTES_IN_KIB) as usize)
@@ -292,15 +369,18 @@ mod tests {
         data[0x0147] = 0x00;
         data[0x0148] = 0x00;
         data[0x0149] = 0x00;
-        let _ = CartMBC1::new(data);
+        assert!(matches!(
+            CartMBC1::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
     }
 
     #[test]
     fn test_new() {
         let mbc1 = CartMBC1::new_test(false, false, 4, 0);
 
-        assert_eq!(mbc1.logo(), super::super::LOGO);
-        assert!(mbc1.validate_logo());
+        assert_eq!(mbc1.rom()[0x0104..=0x0133], super::super::LOGO);
+        assert!(mbc1.header().logo_valid);
 
         assert_eq!(mbc1.num_rom_banks(), 4);
         assert_eq!(mbc1.num_ram_banks(), 0);
@@ -309,12 +389,12 @@ mod tests {
         assert!(!mbc1.cart_features().ram);
         assert!(!mbc1.cart_features().battery);
 
-        assert_eq!(mbc1.title(), "Test Cart\0\0\0\0\0\0\0");
+        assert_eq!(mbc1.header().title, "Test Cart");
 
-        assert_eq!(mbc1.rom_size(), 65536);
-        assert_eq!(mbc1.ram_size(), 0);
+        assert_eq!(mbc1.header.rom_size, 65536);
+        assert_eq!(mbc1.header.ram_size, 0);
 
-        assert!(mbc1.validate_checksum().is_none());
+        assert!(mbc1.header().header_checksum_valid);
     }
 
     #[test]
@@ -481,12 +561,99 @@ mod tests {
             } else {
                 assert_eq!(
                     mbc1.read_ram(0xA000 + bank_num),
-                    bank_num.try_into().unwrap()
+                    u8::try_from(bank_num).unwrap()
                 );
             }
         }
     }
 
+    #[test]
+    fn test_mapper_state() {
+        let mut mbc1 = CartMBC1::new_test(true, true, 64, 1);
+        for bank_num in 0..64 {
+            mbc1.rom[bank_num * 0x4000] = bank_num as u8;
+        }
+        assert_eq!(
+            mbc1.mapper_state(),
+            MapperState {
+                rom_bank_0000: 0,
+                rom_bank_4000: 1,
+                ram_bank: Some(0),
+                ram_enabled: false,
+                mode: 0,
+                rtc_latch: None,
+            }
+        );
+
+        mbc1.write_rom(0x0000, 0x0A);
+        mbc1.write_rom(0x2000, 0x05);
+        mbc1.write_rom(0x4000, 0x01);
+        mbc1.write_rom(0x6000, 0x01);
+        let state = mbc1.mapper_state();
+        assert_eq!(state.rom_bank_0000, 0x20);
+        assert_eq!(state.rom_bank_4000, 0x25);
+        assert!(state.ram_enabled);
+        assert_eq!(state.mode, 1);
+        assert_eq!(mbc1.rom_bank(state.rom_bank_4000).unwrap()[0], 0x25);
+
+        // Reading banks has no side effects
+        assert_eq!(mbc1.rom_bank(3).unwrap()[0], 0x03);
+        assert_eq!(mbc1.rom_bank(3).unwrap().len(), 0x4000);
+        assert!(mbc1.rom_bank(64).is_none());
+        mbc1.write_rom(0x6000, 0x00);
+        mbc1.write_ram(0xA000, 0x42);
+        assert_eq!(mbc1.ram_bank(0).unwrap()[0], 0x42);
+        assert!(mbc1.ram_bank(1).is_none());
+        assert_eq!(mbc1.read_rom(0x4000), 0x25);
+    }
+
+    #[test]
+    fn test_multicart_detection() {
+        let builder = super::super::RomBuilder::new(0x01).rom_size(0x05);
+        assert!(!is_multicart(&builder.build()));
+
+        let data = (1..4)
+            .fold(builder, |builder, sub_rom| {
+                builder.data((sub_rom * MULTICART_SUB_ROM_SIZE) + 0x0104, &LOGO)
+            })
+            .build();
+        assert!(is_multicart(&data));
+
+        // Only 1 MiB ROMs can be multicarts
+        let data = super::super::RomBuilder::new(0x01).rom_size(0x04).build();
+        assert!(!is_multicart(&data));
+    }
+
+    #[test]
+    fn test_multicart_rom_bank_switching() {
+        let data = (0..64)
+            .fold(
+                super::super::RomBuilder::new(0x01).rom_size(0x05),
+                |builder, bank_num| builder.code(bank_num, 0x4ABC, &[bank_num as u8]),
+            )
+            .build();
+        let mut mbc1 = CartMBC1::new_multicart(data).unwrap();
+
+        for sub_rom in 0..4_u8 {
+            mbc1.write_rom(0x4000, sub_rom);
+            for bank_num in 0..16_u8 {
+                mbc1.write_rom(0x2000, bank_num);
+                let expected = (sub_rom << 4) | if bank_num == 0 { 1 } else { bank_num };
+                assert_eq!(mbc1.read_rom(0x4ABC), expected);
+            }
+
+            // Bit 4 of the ROM bank register is ignored, but still prevents the 0->1 translation
+            mbc1.write_rom(0x2000, 0x10);
+            assert_eq!(mbc1.read_rom(0x4ABC), sub_rom << 4);
+
+            // Mode 1 maps the first bank of the sub-ROM to 0x0000-0x3FFF
+            mbc1.write_rom(0x6000, 0x01);
+            assert_eq!(mbc1.read_rom(0x0ABC), sub_rom << 4);
+            mbc1.write_rom(0x6000, 0x00);
+            assert_eq!(mbc1.read_rom(0x0ABC), 0x00);
+        }
+    }
+
     #[test]
     fn test_useless_ram_bank_number() {
         let mut mbc1 = CartMBC1::new_test(true, true, 32, 1);
@@ -0,0 +1,358 @@
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB,
+    ROM_BANK_SIZE,
+};
+
+/// Number of half-bytes of RAM built into the MBC2 chip.
+const MBC2_RAM_SIZE: usize = 512;
+
+/// An MBC2 cartridge.
+#[derive(Debug)]
+pub struct CartMBC2 {
+    rom: Vec<u8>,
+    /// Built-in 512x4-bit RAM. Only the lower nibble of each byte is used.
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    ram_enable: bool,
+    rom_bank_number: u8,
+}
+impl CartMBC2 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mbc2 = Self {
+            rom: data,
+            ram: vec![0x00_u8; MBC2_RAM_SIZE],
+            header,
+            save_dirty: false,
+            ram_enable: false,
+            rom_bank_number: 0x01,
+        };
+
+        // Validate proper parameters
+        // Cart features include MBC2
+        if !mbc2.header.cart_features.mbc2 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New MBC2: According to the header, this cartridge is not an MBC2. Cart type from header: {}", mbc2.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if mbc2.rom().len() != mbc2.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MBC2: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc2.rom().len(), mbc2.header.rom_size)));
+        }
+
+        // Max ROM size
+        if mbc2.rom().len() > (256 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC2: ROM size too big ({} KiB > 256 KiB)",
+                mbc2.rom().len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(mbc2)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u8 {
+        match self.rom_bank_number & 0b0000_1111 {
+            // If register is set to 0, ROM bank number = 1.
+            0x00 => 0x01,
+            // Bank numbers higher than the number of banks in the cart wrap around.
+            rbn => rbn % self.num_rom_banks(),
+        }
+    }
+
+    fn set_rom_bank_number(&mut self, value: u8) {
+        self.rom_bank_number = value & 0b0000_1111;
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            // Only the bottom 9 bits of the address are used, so the RAM repeats.
+            0xA000..=0xBFFF => {
+                result |= (address as u32) & 0b0000_0001_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    #[cfg(test)]
+    fn new_test(has_battery: bool, rom_size_value: u8) -> Self {
+        CartMBC2::new(
+            super::RomBuilder::new(if has_battery { 0x06 } else { 0x05 })
+                .rom_size(rom_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartMBC2 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            ram_bank: Some(0),
+            ram_enabled: self.ram_enable,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.header.cart_features.battery {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.header.cart_features.battery {
+            load_save_section(&mut self.ram, data);
+            // Only the lower nibble of each byte is stored.
+            self.ram.iter_mut().for_each(|b| *b &= 0x0F);
+        }
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF_u8;
+        }
+        // Upper nibble is undefined; it reads as all 1s.
+        0b1111_0000 | self.ram[self.internal_addr(address)]
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // Bit 8 of the address selects between RAM enable & ROM bank number.
+            0x0000..=0x3FFF => {
+                if (address & 0x0100) == 0 {
+                    self.ram_enable = (value & 0x0F) == 0x0A;
+                } else {
+                    self.set_rom_bank_number(value);
+                }
+            }
+            // Unused
+            0x4000..=0x7FFF => {}
+            _ => panic!("MBC2 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        let addr = self.internal_addr(address);
+        self.ram[addr] = value & 0b0000_1111;
+        self.save_dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_bad_rom_size_header() {
+        let mut data = vec![0x00_u8; (64 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x05;
+        data[0x0148] = 0x02;
+        assert!(matches!(
+            CartMBC2::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let mut data = vec![0x00_u8; (512 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x05;
+        data[0x0148] = 0x04;
+        assert!(matches!(
+            CartMBC2::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
+
+        // 72 banks
+        let data = super::super::RomBuilder::new(0x05).rom_size(0x52).build();
+        assert!(matches!(
+            CartMBC2::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let mut data = vec![0x00_u8; (32 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x01;
+        assert!(matches!(
+            CartMBC2::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let mbc2 = CartMBC2::new_test(true, 0x03);
+
+        assert_eq!(mbc2.rom()[0x0104..=0x0133], super::super::LOGO);
+        assert!(mbc2.header().logo_valid);
+
+        assert_eq!(mbc2.num_rom_banks(), 16);
+
+        assert!(mbc2.cart_features().mbc2);
+        assert!(mbc2.cart_features().battery);
+
+        // RAM is built into the MBC, so the header reports none.
+        assert_eq!(mbc2.header.ram_size, 0);
+        assert_eq!(mbc2.ram.len(), 512);
+
+        assert!(mbc2.header().header_checksum_valid);
+    }
+
+    #[test]
+    fn test_bank_numbers() {
+        let mut mbc2 = CartMBC2::new_test(false, 0x03);
+
+        mbc2.set_rom_bank_number(0b0000_0000);
+        assert_eq!(mbc2.get_rom_bank_number(), 1);
+
+        mbc2.set_rom_bank_number(0b1111_0000);
+        assert_eq!(mbc2.get_rom_bank_number(), 1);
+
+        mbc2.set_rom_bank_number(0b1111_1111);
+        assert_eq!(mbc2.get_rom_bank_number(), 15);
+
+        mbc2.set_rom_bank_number(0b0000_0101);
+        assert_eq!(mbc2.get_rom_bank_number(), 5);
+
+        // Masked to the number of banks in the cart
+        let mut mbc2 = CartMBC2::new_test(false, 0x01);
+        mbc2.set_rom_bank_number(0b0000_0110);
+        assert_eq!(mbc2.get_rom_bank_number(), 2);
+    }
+
+    #[test]
+    fn test_register_select() {
+        let mut mbc2 = CartMBC2::new_test(false, 0x03);
+
+        // Bit 8 clear: RAM enable
+        mbc2.write_rom(0x0000, 0x0A);
+        assert!(mbc2.ram_enable);
+        assert_eq!(mbc2.get_rom_bank_number(), 1);
+        mbc2.write_rom(0x3EFF, 0x00);
+        assert!(!mbc2.ram_enable);
+        mbc2.write_rom(0x20FF, 0x1A);
+        assert!(mbc2.ram_enable);
+
+        // Bit 8 set: ROM bank number
+        mbc2.write_rom(0x0100, 0x03);
+        assert_eq!(mbc2.get_rom_bank_number(), 3);
+        assert!(mbc2.ram_enable);
+        mbc2.write_rom(0x3FFF, 0x0A);
+        assert_eq!(mbc2.get_rom_bank_number(), 10);
+        assert!(mbc2.ram_enable);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut mbc2 = CartMBC2::new_test(false, 0x03);
+        for bank_num in 0..16 {
+            mbc2.rom[(bank_num * 0x4000) + 0x2FAB] = bank_num as u8;
+        }
+
+        // Setting bank 0 should access bank 1
+        mbc2.write_rom(0x2100, 0x00);
+        assert_eq!(mbc2.read_rom(0x6FAB), 0x01);
+
+        for bank_num in 1..16 {
+            mbc2.write_rom(0x2100, bank_num);
+            assert_eq!(mbc2.read_rom(0x6FAB), bank_num);
+            assert_eq!(mbc2.read_rom(0x2FAB), 0x00);
+        }
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut mbc2 = CartMBC2::new_test(true, 0x01);
+        mbc2.ram[0x0000] = 0x09;
+        mbc2.ram[0x0123] = 0x0B;
+        mbc2.ram[0x01FF] = 0x0D;
+
+        assert!(!mbc2.ram_enable);
+        assert_eq!(mbc2.read_ram(0xA000), 0xFF);
+
+        mbc2.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc2.read_ram(0xA000), 0xF9);
+        assert_eq!(mbc2.read_ram(0xA123), 0xFB);
+        assert_eq!(mbc2.read_ram(0xA1FF), 0xFD);
+
+        // Only the lower nibble is stored
+        mbc2.write_ram(0xA042, 0xAB);
+        assert_eq!(mbc2.ram[0x0042], 0x0B);
+        assert_eq!(mbc2.read_ram(0xA042), 0xFB);
+
+        // Disabled RAM can't be written to
+        mbc2.write_rom(0x0000, 0x00);
+        mbc2.write_ram(0xA042, 0x01);
+        mbc2.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc2.read_ram(0xA042), 0xFB);
+    }
+
+    #[test]
+    fn test_ram_echo() {
+        let mut mbc2 = CartMBC2::new_test(false, 0x01);
+        mbc2.write_rom(0x0000, 0x0A);
+
+        mbc2.write_ram(0xA1AB, 0x07);
+        // RAM repeats every 512 bytes across 0xA000-0xBFFF
+        for echo in 0..16 {
+            assert_eq!(mbc2.read_ram(0xA1AB + (echo * 0x0200)), 0xF7);
+        }
+
+        mbc2.write_ram(0xBFFF, 0x03);
+        assert_eq!(mbc2.ram[0x01FF], 0x03);
+        assert_eq!(mbc2.read_ram(0xA1FF), 0xF3);
+    }
+}
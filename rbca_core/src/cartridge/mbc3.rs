@@ -0,0 +1,717 @@
+use std::time::{Duration, SystemTime};
+
+use super::{
+    from_unix_timestamp, load_save_section, to_unix_timestamp, Cartridge, CartridgeError,
+    CartridgeHeader, MapperState, BYTES_IN_KIB, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// Size of the clock data appended to save files.
+const RTC_SAVE_SIZE: usize = 48;
+
+/// An MBC3 cartridge.
+#[derive(Debug)]
+pub struct CartMBC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    /// Enables both the RAM and the RTC registers.
+    ram_timer_enable: bool,
+    rom_bank_number: u8,
+    /// 0x00-0x03 = RAM bank, 0x08-0x0C = RTC register.
+    ram_bank_number: u8,
+    /// The last value written to the latch register.
+    latch_register: u8,
+    rtc: Rtc,
+}
+impl CartMBC3 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mut mbc3 = Self {
+            rom: data,
+            ram: vec![],
+            header,
+            save_dirty: false,
+            ram_timer_enable: false,
+            rom_bank_number: 0x01,
+            ram_bank_number: 0x00,
+            latch_register: 0xFF,
+            rtc: Rtc::new(),
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        mbc3.ram = vec![0x00_u8; mbc3.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Cart features include MBC3
+        if !mbc3.header.cart_features.mbc3 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New MBC3: According to the header, this cartridge is not an MBC3. Cart type from header: {}", mbc3.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if mbc3.rom().len() != mbc3.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MBC3: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc3.rom().len(), mbc3.header.rom_size)));
+        }
+
+        // Max ROM size
+        if mbc3.rom().len() > (2048 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC3: ROM size too big ({} MiB > 2 MiB)",
+                mbc3.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+        // Max RAM size
+        if mbc3.ram.len() > (32 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC3: RAM size too big ({} KiB > 32 KiB)",
+                mbc3.ram.len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(mbc3)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u8 {
+        match self.rom_bank_number & 0b0111_1111 {
+            // Unlike MBC1, only a value of exactly 0 is translated to bank 1.
+            0x00 => 0x01,
+            // Bank numbers higher than the number of banks in the cart wrap around. Not every
+            // bank count is a power of 2.
+            rbn => rbn % self.num_rom_banks(),
+        }
+    }
+
+    fn set_rom_bank_number(&mut self, value: u8) {
+        self.rom_bank_number = value & 0b0111_1111;
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                result |= ((self.ram_bank_number & 0b0000_0011) as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    #[cfg(test)]
+    fn new_test(cart_type: u8, rom_size_value: u8, ram_size_value: u8) -> Self {
+        CartMBC3::new(
+            super::RomBuilder::new(cart_type)
+                .rom_size(rom_size_value)
+                .ram_size(ram_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartMBC3 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // 0x08-0x0C map a clock register instead.
+            ram_bank: (self.ram_bank_number <= 0x03 && !self.ram.is_empty())
+                .then(|| self.internal_addr(0xA000) / RAM_BANK_SIZE),
+            ram_enabled: self.ram_timer_enable,
+            mode: 0,
+            rtc_latch: self.header.cart_features.timer.then_some(self.rtc.latched),
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.header.cart_features.battery {
+            return None;
+        }
+        let mut data = self.ram.clone();
+        if self.header.cart_features.timer {
+            data.extend(self.rtc.save_bytes());
+        }
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if !self.header.cart_features.battery {
+            return;
+        }
+        let rest = load_save_section(&mut self.ram, data);
+        if self.header.cart_features.timer {
+            self.rtc.load_bytes(rest);
+        }
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_timer_enable {
+            return 0xFF_u8;
+        }
+        match self.ram_bank_number {
+            0x00..=0x03 => *self
+                .ram
+                .get(self.internal_addr(address))
+                .unwrap_or(&0xFF_u8),
+            0x08..=0x0C if self.header.cart_features.timer => {
+                self.rtc.read_latched(self.ram_bank_number)
+            }
+            _ => 0xFF_u8,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM & timer enable
+            0x0000..=0x1FFF => self.ram_timer_enable = (value & 0x0F) == 0x0A,
+            // ROM bank number
+            0x2000..=0x3FFF => self.set_rom_bank_number(value),
+            // RAM bank number / RTC register select
+            0x4000..=0x5FFF => self.ram_bank_number = value & 0b0000_1111,
+            // Latch clock data: writing 0x00 then 0x01 latches the current time.
+            0x6000..=0x7FFF => {
+                if self.latch_register == 0x00 && value == 0x01 {
+                    self.rtc.latch();
+                }
+                self.latch_register = value;
+            }
+            _ => panic!("MBC3 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_timer_enable {
+            return;
+        }
+        match self.ram_bank_number {
+            0x00..=0x03 => {
+                let addr = self.internal_addr(address);
+                if addr < self.ram.len() {
+                    self.ram[addr] = value;
+                    self.save_dirty = true;
+                }
+            }
+            0x08..=0x0C if self.header.cart_features.timer => {
+                self.rtc.write(self.ram_bank_number, value);
+                self.save_dirty = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The MBC3 real-time clock. Keeps time using the host system clock.
+#[derive(Debug, Clone)]
+struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 9-bit day counter.
+    days: u16,
+    /// Stops the clock when set.
+    halt: bool,
+    /// Set when the day counter overflows. Stays set until cleared by the program.
+    day_carry: bool,
+    /// The values of the clock registers at the time of the last latch (0x08-0x0C).
+    latched: [u8; 5],
+    /// The host time up to which the clock registers have been advanced.
+    last_update: SystemTime,
+}
+impl Rtc {
+    fn new() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            day_carry: false,
+            latched: [0x00; 5],
+            last_update: SystemTime::now(),
+        }
+    }
+
+    /// Advance the clock registers to the current host time. Time spent halted is discarded.
+    fn update(&mut self) {
+        let now = SystemTime::now();
+        // If the host clock went backwards, don't move the RTC.
+        let elapsed = now.duration_since(self.last_update).unwrap_or_default();
+        let elapsed_secs = elapsed.as_secs();
+        self.last_update += Duration::from_secs(elapsed_secs);
+        self.advance(elapsed_secs);
+    }
+
+    /// Advance the running clock by the given number of seconds.
+    fn advance(&mut self, mut secs: u64) {
+        if self.halt {
+            return;
+        }
+
+        // Out-of-range register values count up to their bit limit & wrap without carrying, so
+        // step one second at a time until every register is back in range.
+        while secs > 0 && !self.is_in_range() {
+            self.tick();
+            secs -= 1;
+        }
+        if secs == 0 {
+            return;
+        }
+
+        let total = secs
+            + (self.seconds as u64)
+            + (self.minutes as u64) * 60
+            + (self.hours as u64) * 3600
+            + (self.days as u64) * 86400;
+        self.seconds = (total % 60) as u8;
+        self.minutes = ((total / 60) % 60) as u8;
+        self.hours = ((total / 3600) % 24) as u8;
+        let days = total / 86400;
+        if days > 0x1FF {
+            self.day_carry = true;
+        }
+        self.days = (days & 0x1FF) as u16;
+    }
+
+    fn is_in_range(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /// Advance the clock by exactly one second, emulating the hardware counter widths.
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0b0011_1111;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0b0011_1111;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0b0001_1111;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.days += 1;
+        if self.days > 0x1FF {
+            self.days = 0;
+            self.day_carry = true;
+        }
+    }
+
+    /// Copy the current clock registers into the latched registers.
+    fn latch(&mut self) {
+        self.update();
+        self.latched = [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.read_live(0x0B),
+            self.read_live(0x0C),
+        ];
+    }
+
+    /// Read a latched clock register.
+    fn read_latched(&self, register: u8) -> u8 {
+        match register {
+            0x08..=0x0C => self.latched[(register - 0x08) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    /// Read a live clock register.
+    fn read_live(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => (self.days & 0x00FF) as u8,
+            0x0C => {
+                ((self.days >> 8) as u8 & 0b0000_0001)
+                    | if self.halt { 0b0100_0000 } else { 0 }
+                    | if self.day_carry { 0b1000_0000 } else { 0 }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    /// Write to a live clock register.
+    fn write(&mut self, register: u8, value: u8) {
+        self.update();
+        match register {
+            0x08 => {
+                self.seconds = value & 0b0011_1111;
+                // Writing to the seconds register resets the sub-second counter.
+                self.last_update = SystemTime::now();
+            }
+            0x09 => self.minutes = value & 0b0011_1111,
+            0x0A => self.hours = value & 0b0001_1111,
+            0x0B => self.days = (self.days & 0x0100) | (value as u16),
+            0x0C => {
+                self.days = (self.days & 0x00FF) | (((value & 0b0000_0001) as u16) << 8);
+                self.halt = (value & 0b0100_0000) != 0;
+                self.day_carry = (value & 0b1000_0000) != 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Get the clock data appended to save files: the live & latched registers as 32-bit values,
+    /// then the Unix time they were current at as a 64-bit value, all little-endian. This is the
+    /// 48-byte layout used by BGB & VBA-M.
+    fn save_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RTC_SAVE_SIZE);
+        for register in 0x08..=0x0C {
+            bytes.extend((self.read_live(register) as u32).to_le_bytes());
+        }
+        for value in self.latched {
+            bytes.extend((value as u32).to_le_bytes());
+        }
+        bytes.extend(to_unix_timestamp(self.last_update).to_le_bytes());
+        bytes
+    }
+
+    /// Restore the clock from save file clock data, then catch up to the current time. The older
+    /// 44-byte layout with a 32-bit timestamp is also accepted.
+    fn load_bytes(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE - 4 {
+            return;
+        }
+        let value = |i: usize| data[i * 4];
+        self.seconds = value(0) & 0b0011_1111;
+        self.minutes = value(1) & 0b0011_1111;
+        self.hours = value(2) & 0b0001_1111;
+        self.days = (value(3) as u16) | (((value(4) & 0b0000_0001) as u16) << 8);
+        self.halt = (value(4) & 0b0100_0000) != 0;
+        self.day_carry = (value(4) & 0b1000_0000) != 0;
+        for (i, latched) in self.latched.iter_mut().enumerate() {
+            *latched = value(5 + i);
+        }
+
+        let timestamp = if data.len() >= RTC_SAVE_SIZE {
+            u64::from_le_bytes(data[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(data[40..44].try_into().unwrap()) as u64
+        };
+        self.last_update = from_unix_timestamp(timestamp);
+        self.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_bad_rom_size_header() {
+        let mut data = vec![0x00_u8; (64 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x11;
+        data[0x0148] = 0x02;
+        assert!(matches!(
+            CartMBC3::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let mut data = vec![0x00_u8; (32 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x01;
+        assert!(matches!(
+            CartMBC3::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let mbc3 = CartMBC3::new_test(0x10, 0x06, 0x03);
+
+        assert!(mbc3.header().logo_valid);
+        assert_eq!(mbc3.num_rom_banks(), 128);
+        assert!(mbc3.cart_features().mbc3);
+        assert!(mbc3.cart_features().timer);
+        assert!(mbc3.cart_features().ram);
+        assert!(mbc3.cart_features().battery);
+        assert_eq!(mbc3.ram.len(), 0x8000);
+        assert!(mbc3.header().header_checksum_valid);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut mbc3 = CartMBC3::new_test(0x11, 0x06, 0x00);
+        for bank_num in 0..128 {
+            mbc3.rom[(bank_num * 0x4000) + 0x0123] = bank_num as u8;
+        }
+
+        // Bank 0 is always at 0x0000-0x3FFF
+        assert_eq!(mbc3.read_rom(0x0123), 0x00);
+
+        // Bank 0 maps to bank 1
+        mbc3.write_rom(0x2000, 0x00);
+        assert_eq!(mbc3.read_rom(0x4123), 0x01);
+
+        // Unlike MBC1, banks 0x20, 0x40 & 0x60 are directly accessible
+        for bank_num in 1..128 {
+            mbc3.write_rom(0x3FFF, bank_num);
+            assert_eq!(mbc3.read_rom(0x4123), bank_num);
+            assert_eq!(mbc3.read_rom(0x0123), 0x00);
+        }
+
+        // Only 7 bits are used
+        mbc3.write_rom(0x2000, 0b1000_0011);
+        assert_eq!(mbc3.read_rom(0x4123), 0x03);
+    }
+
+    #[test]
+    fn test_rom_bank_wrapping() {
+        // 72 banks
+        let mut mbc3 = CartMBC3::new_test(0x11, 0x52, 0x00);
+        for bank_num in 0..72 {
+            mbc3.rom[(bank_num * 0x4000) + 0x0123] = bank_num as u8;
+        }
+
+        for bank_num in 1..72 {
+            mbc3.write_rom(0x2000, bank_num);
+            assert_eq!(mbc3.read_rom(0x4123), bank_num);
+        }
+        mbc3.write_rom(0x2000, 100);
+        assert_eq!(mbc3.read_rom(0x4123), 28);
+    }
+
+    #[test]
+    fn test_ram_bank_switching() {
+        let mut mbc3 = CartMBC3::new_test(0x13, 0x02, 0x03);
+
+        // RAM is disabled by default
+        mbc3.write_ram(0xA000, 0x12);
+        assert_eq!(mbc3.read_ram(0xA000), 0xFF);
+
+        mbc3.write_rom(0x0000, 0x0A);
+        for bank_num in 0..4 {
+            mbc3.write_rom(0x4000, bank_num);
+            mbc3.write_ram(0xA000 + bank_num as u16, 0x10 + bank_num);
+        }
+        for bank_num in 0..4 {
+            mbc3.write_rom(0x5FFF, bank_num);
+            assert_eq!(mbc3.read_ram(0xA000 + bank_num as u16), 0x10 + bank_num);
+        }
+        assert_eq!(mbc3.ram[0x6003], 0x13);
+
+        mbc3.write_rom(0x1FFF, 0x00);
+        assert_eq!(mbc3.read_ram(0xA003), 0xFF);
+    }
+
+    #[test]
+    fn test_rtc_latch() {
+        let mut mbc3 = CartMBC3::new_test(0x10, 0x01, 0x02);
+        mbc3.write_rom(0x0000, 0x0A);
+
+        // Halt the clock so that it can be set deterministically
+        mbc3.write_rom(0x4000, 0x0C);
+        mbc3.write_ram(0xA000, 0b0100_0000);
+        mbc3.write_rom(0x4000, 0x08);
+        mbc3.write_ram(0xA000, 59);
+        mbc3.write_rom(0x4000, 0x09);
+        mbc3.write_ram(0xA000, 59);
+        mbc3.write_rom(0x4000, 0x0A);
+        mbc3.write_ram(0xA000, 23);
+        mbc3.write_rom(0x4000, 0x0B);
+        mbc3.write_ram(0xA000, 0xFF);
+
+        // Registers read the latched values, not the live ones
+        mbc3.write_rom(0x4000, 0x08);
+        assert_eq!(mbc3.read_ram(0xA000) & 0b0011_1111, 0);
+
+        // Latch sequence requires 0x00 then 0x01
+        mbc3.write_rom(0x6000, 0x01);
+        assert_eq!(mbc3.read_ram(0xA000) & 0b0011_1111, 0);
+        mbc3.write_rom(0x6000, 0x00);
+        mbc3.write_rom(0x6000, 0x01);
+        assert_eq!(mbc3.read_ram(0xA000) & 0b0011_1111, 59);
+
+        mbc3.write_rom(0x4000, 0x0A);
+        assert_eq!(mbc3.read_ram(0xA000) & 0b0001_1111, 23);
+        mbc3.write_rom(0x4000, 0x0B);
+        assert_eq!(mbc3.read_ram(0xA000), 0xFF);
+        mbc3.write_rom(0x4000, 0x0C);
+        assert_eq!(mbc3.read_ram(0xA000) & 0b1100_0001, 0b0100_0000);
+
+        // Halted clock doesn't advance
+        mbc3.rtc.advance(100);
+        assert_eq!(mbc3.rtc.seconds, 59);
+    }
+
+    #[test]
+    fn test_mapper_state() {
+        let mut mbc3 = CartMBC3::new_test(0x10, 0x02, 0x03);
+        mbc3.write_rom(0x0000, 0x0A);
+        mbc3.write_rom(0x2000, 0x06);
+        mbc3.write_rom(0x4000, 0x02);
+        let state = mbc3.mapper_state();
+        assert_eq!(state.rom_bank_0000, 0);
+        assert_eq!(state.rom_bank_4000, 6);
+        assert_eq!(state.ram_bank, Some(2));
+        assert!(state.ram_enabled);
+        assert_eq!(state.rtc_latch, Some([0x00; 5]));
+
+        // Halt the clock & latch it
+        mbc3.write_rom(0x4000, 0x0C);
+        mbc3.write_ram(0xA000, 0b0100_0000);
+        mbc3.write_rom(0x4000, 0x08);
+        mbc3.write_ram(0xA000, 30);
+        mbc3.write_rom(0x6000, 0x00);
+        mbc3.write_rom(0x6000, 0x01);
+        let state = mbc3.mapper_state();
+        assert_eq!(state.ram_bank, None);
+        assert_eq!(state.rtc_latch, Some([30, 0, 0, 0, 0b0100_0000]));
+
+        // No clock
+        let mbc3 = CartMBC3::new_test(0x13, 0x02, 0x03);
+        assert_eq!(mbc3.mapper_state().rtc_latch, None);
+    }
+
+    #[test]
+    fn test_save_data() {
+        // No battery
+        let mbc3 = CartMBC3::new_test(0x12, 0x01, 0x02);
+        assert_eq!(mbc3.save_data(), None);
+
+        // Battery, no timer
+        let mut mbc3 = CartMBC3::new_test(0x13, 0x01, 0x02);
+        mbc3.ram[0x1234] = 0x56;
+        let data = mbc3.save_data().unwrap();
+        assert_eq!(data.len(), 0x2000);
+        let mut loaded = CartMBC3::new_test(0x13, 0x01, 0x02);
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.ram[0x1234], 0x56);
+    }
+
+    #[test]
+    fn test_save_data_rtc() {
+        let mut mbc3 = CartMBC3::new_test(0x10, 0x01, 0x02);
+        mbc3.ram[0x0042] = 0x24;
+        mbc3.rtc.halt = true;
+        mbc3.rtc.seconds = 12;
+        mbc3.rtc.minutes = 34;
+        mbc3.rtc.hours = 5;
+        mbc3.rtc.days = 0x1AB;
+        mbc3.rtc.latched = [1, 2, 3, 4, 5];
+
+        let data = mbc3.save_data().unwrap();
+        assert_eq!(data.len(), 0x2000 + RTC_SAVE_SIZE);
+        assert_eq!(data[0x2000], 12);
+        assert_eq!(data[0x2000 + 12], 0xAB);
+        assert_eq!(data[0x2000 + 16], 0b0100_0001);
+
+        let mut loaded = CartMBC3::new_test(0x10, 0x01, 0x02);
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.ram[0x0042], 0x24);
+        assert!(loaded.rtc.halt);
+        assert_eq!(loaded.rtc.seconds, 12);
+        assert_eq!(loaded.rtc.minutes, 34);
+        assert_eq!(loaded.rtc.hours, 5);
+        assert_eq!(loaded.rtc.days, 0x1AB);
+        assert_eq!(loaded.rtc.latched, [1, 2, 3, 4, 5]);
+
+        // Time passes while the emulator is closed
+        let mut data = mbc3.save_data().unwrap();
+        data[0x2000 + 16] = 0x00;
+        let timestamp = to_unix_timestamp(SystemTime::now()) - 90;
+        data[0x2000 + 40..].copy_from_slice(&timestamp.to_le_bytes());
+        let mut loaded = CartMBC3::new_test(0x10, 0x01, 0x02);
+        loaded.load_save_data(&data);
+        assert!(loaded.rtc.minutes == 35 || loaded.rtc.minutes == 36);
+    }
+
+    #[test]
+    fn test_rtc_day_carry() {
+        let mut rtc = Rtc::new();
+        rtc.seconds = 59;
+        rtc.minutes = 59;
+        rtc.hours = 23;
+        rtc.days = 0x1FF;
+        rtc.advance(1);
+        assert_eq!(rtc.seconds, 0);
+        assert_eq!(rtc.minutes, 0);
+        assert_eq!(rtc.hours, 0);
+        assert_eq!(rtc.days, 0);
+        assert!(rtc.day_carry);
+        assert_eq!(rtc.read_live(0x0C), 0b1000_0000);
+
+        // Day carry stays set until cleared
+        rtc.advance(86400 + 3661);
+        assert!(rtc.day_carry);
+        assert_eq!(rtc.days, 1);
+        assert_eq!(rtc.hours, 1);
+        assert_eq!(rtc.minutes, 1);
+        assert_eq!(rtc.seconds, 1);
+
+        rtc.write(0x0C, 0b0000_0001);
+        assert!(!rtc.day_carry);
+        assert_eq!(rtc.days, 0x101);
+    }
+
+    #[test]
+    fn test_rtc_out_of_range() {
+        let mut rtc = Rtc::new();
+        // Invalid seconds count up to 63, then wrap to 0 without incrementing minutes
+        rtc.seconds = 62;
+        rtc.advance(2);
+        assert_eq!(rtc.seconds, 0);
+        assert_eq!(rtc.minutes, 0);
+
+        rtc.hours = 30;
+        rtc.minutes = 59;
+        rtc.seconds = 59;
+        rtc.advance(3600 * 2);
+        assert_eq!(rtc.hours, 0);
+        assert_eq!(rtc.days, 0);
+        rtc.advance(3600);
+        assert_eq!(rtc.hours, 1);
+    }
+}
@@ -0,0 +1,366 @@
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB,
+    RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// An MBC5 cartridge.
+#[derive(Debug)]
+pub struct CartMBC5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    ram_enable: bool,
+    /// 9-bit ROM bank number.
+    rom_bank_number: u16,
+    ram_bank_number: u8,
+    /// Rumble motor state. Only used by rumble cartridges.
+    rumble: bool,
+}
+impl CartMBC5 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mut mbc5 = Self {
+            rom: data,
+            ram: vec![],
+            header,
+            save_dirty: false,
+            ram_enable: false,
+            rom_bank_number: 0x0001,
+            ram_bank_number: 0x00,
+            rumble: false,
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        mbc5.ram = vec![0x00_u8; mbc5.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Cart features include MBC5
+        if !mbc5.header.cart_features.mbc5 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New MBC5: According to the header, this cartridge is not an MBC5. Cart type from header: {}", mbc5.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if mbc5.rom().len() != mbc5.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MBC5: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc5.rom().len(), mbc5.header.rom_size)));
+        }
+
+        // Max ROM size
+        if mbc5.rom().len() > (8 * 1024 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC5: ROM size too big ({} MiB > 8 MiB)",
+                mbc5.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+        // Max RAM size
+        if mbc5.ram.len() > (128 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC5: RAM size too big ({} KiB > 128 KiB)",
+                mbc5.ram.len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(mbc5)
+    }
+
+    fn num_rom_banks(&self) -> u16 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u16 {
+        // Bank 0 really is bank 0. Bank numbers higher than the number of banks in the cart wrap
+        // around. Not every bank count is a power of 2.
+        self.rom_bank_number % self.num_rom_banks()
+    }
+
+    fn get_ram_bank_number(&self) -> u8 {
+        if self.header.cart_features.rumble {
+            // Bit 3 controls the rumble motor instead of selecting a RAM bank.
+            self.ram_bank_number & 0b0000_0111
+        } else {
+            self.ram_bank_number & 0b0000_1111
+        }
+    }
+
+    fn set_ram_bank_number(&mut self, value: u8) {
+        self.ram_bank_number = value & 0b0000_1111;
+        if self.header.cart_features.rumble {
+            self.rumble = (value & 0b0000_1000) != 0;
+        }
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                result |= (self.get_ram_bank_number() as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    #[cfg(test)]
+    fn new_test(cart_type: u8, rom_size_value: u8, ram_size_value: u8) -> Self {
+        CartMBC5::new(
+            super::RomBuilder::new(cart_type)
+                .rom_size(rom_size_value)
+                .ram_size(ram_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartMBC5 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // RAM smaller than the selected bank is mirrored.
+            ram_bank: (!self.ram.is_empty())
+                .then(|| (self.internal_addr(0xA000) % self.ram.len()) / RAM_BANK_SIZE),
+            ram_enabled: self.ram_enable,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.header.cart_features.battery {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.header.cart_features.battery {
+            load_save_section(&mut self.ram, data);
+        }
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enable || self.ram.is_empty() {
+            return 0xFF_u8;
+        }
+        // RAM smaller than the selected bank is mirrored.
+        let addr = self.internal_addr(address) % self.ram.len();
+        self.ram[addr]
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enable
+            0x0000..=0x1FFF => self.ram_enable = (value & 0x0F) == 0x0A,
+            // Lower 8 bits of ROM bank number
+            0x2000..=0x2FFF => {
+                self.rom_bank_number = (self.rom_bank_number & 0x0100) | (value as u16);
+            }
+            // Bit 8 of ROM bank number
+            0x3000..=0x3FFF => {
+                self.rom_bank_number =
+                    (self.rom_bank_number & 0x00FF) | (((value & 0b0000_0001) as u16) << 8);
+            }
+            // RAM bank number
+            0x4000..=0x5FFF => self.set_ram_bank_number(value),
+            // Unused
+            0x6000..=0x7FFF => {}
+            _ => panic!("MBC5 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enable || self.ram.is_empty() {
+            return;
+        }
+        let addr = self.internal_addr(address) % self.ram.len();
+        self.ram[addr] = value;
+        self.save_dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_bad_rom_size_header() {
+        let mut data = vec![0x00_u8; (64 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x19;
+        data[0x0148] = 0x02;
+        assert!(matches!(
+            CartMBC5::new(data),
+            Err(CartridgeError::SizeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let mut data = vec![0x00_u8; (32 * BYTES_IN_KIB) as usize];
+        data[0x0147] = 0x13;
+        assert!(matches!(
+            CartMBC5::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let mbc5 = CartMBC5::new_test(0x1B, 0x08, 0x04);
+
+        assert!(mbc5.header().logo_valid);
+        assert_eq!(mbc5.num_rom_banks(), 512);
+        assert!(mbc5.cart_features().mbc5);
+        assert!(mbc5.cart_features().ram);
+        assert!(mbc5.cart_features().battery);
+        assert!(!mbc5.cart_features().rumble);
+        assert_eq!(mbc5.ram.len(), 0x20000);
+        assert!(mbc5.header().header_checksum_valid);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut mbc5 = CartMBC5::new_test(0x19, 0x08, 0x00);
+        for bank_num in 0..512 {
+            mbc5.rom[(bank_num * 0x4000) + 0x0123] = bank_num as u8;
+            mbc5.rom[(bank_num * 0x4000) + 0x0124] = (bank_num >> 8) as u8;
+        }
+
+        // Bank 1 is mapped by default
+        assert_eq!(mbc5.read_rom(0x4123), 0x01);
+
+        // Bank 0 really is bank 0
+        mbc5.write_rom(0x2000, 0x00);
+        assert_eq!(mbc5.read_rom(0x4123), 0x00);
+        assert_eq!(mbc5.read_rom(0x4124), 0x00);
+
+        for bank_num in 0..512_u16 {
+            mbc5.write_rom(0x2FFF, bank_num as u8);
+            mbc5.write_rom(0x3000, (bank_num >> 8) as u8);
+            assert_eq!(mbc5.read_rom(0x4123), bank_num as u8);
+            assert_eq!(mbc5.read_rom(0x4124), (bank_num >> 8) as u8);
+            assert_eq!(mbc5.read_rom(0x0123), 0x00);
+        }
+
+        // Only the lowest bit of the upper register is used
+        mbc5.write_rom(0x2000, 0x05);
+        mbc5.write_rom(0x3000, 0b1111_1110);
+        assert_eq!(mbc5.read_rom(0x4123), 0x05);
+        assert_eq!(mbc5.read_rom(0x4124), 0x00);
+    }
+
+    #[test]
+    fn test_rom_bank_masking() {
+        let mut mbc5 = CartMBC5::new_test(0x19, 0x02, 0x00);
+        mbc5.rom[0x4000 * 3] = 0xAB;
+
+        // 8 banks, so bank 0x10B maps to bank 3
+        mbc5.write_rom(0x2000, 0x0B);
+        mbc5.write_rom(0x3000, 0x01);
+        assert_eq!(mbc5.read_rom(0x4000), 0xAB);
+    }
+
+    #[test]
+    fn test_rom_bank_wrapping() {
+        // 72 banks
+        let mut mbc5 = CartMBC5::new_test(0x19, 0x52, 0x00);
+        for bank_num in 0..72 {
+            mbc5.rom[(bank_num * 0x4000) + 0x0123] = bank_num as u8;
+        }
+
+        for bank_num in 0..72 {
+            mbc5.write_rom(0x2000, bank_num);
+            assert_eq!(mbc5.read_rom(0x4123), bank_num);
+        }
+        // 0x10B = 267 = 3 * 72 + 51
+        mbc5.write_rom(0x2000, 0x0B);
+        mbc5.write_rom(0x3000, 0x01);
+        assert_eq!(mbc5.read_rom(0x4123), 51);
+    }
+
+    #[test]
+    fn test_ram_bank_switching() {
+        let mut mbc5 = CartMBC5::new_test(0x1A, 0x01, 0x04);
+
+        // RAM is disabled by default
+        mbc5.write_ram(0xA000, 0x12);
+        assert_eq!(mbc5.read_ram(0xA000), 0xFF);
+
+        mbc5.write_rom(0x0000, 0x0A);
+        for bank_num in 0..16 {
+            mbc5.write_rom(0x4000, bank_num);
+            mbc5.write_ram(0xBF00 + bank_num as u16, 0x20 + bank_num);
+        }
+        for bank_num in 0..16 {
+            mbc5.write_rom(0x5FFF, bank_num);
+            assert_eq!(mbc5.read_ram(0xBF00 + bank_num as u16), 0x20 + bank_num);
+            assert!(!mbc5.rumble());
+        }
+        assert_eq!(mbc5.ram[(0x2000 * 15) + 0x1F0F], 0x2F);
+
+        mbc5.write_rom(0x1FFF, 0x00);
+        assert_eq!(mbc5.read_ram(0xBF00), 0xFF);
+    }
+
+    #[test]
+    fn test_rumble() {
+        let mut mbc5 = CartMBC5::new_test(0x1E, 0x01, 0x03);
+        assert!(mbc5.cart_features().rumble);
+        assert!(!mbc5.rumble());
+
+        mbc5.write_rom(0x0000, 0x0A);
+        mbc5.write_rom(0x4000, 0x02);
+        mbc5.write_ram(0xA000, 0x22);
+
+        // Bit 3 turns the motor on without changing the RAM bank
+        mbc5.write_rom(0x4000, 0b0000_1010);
+        assert!(mbc5.rumble());
+        assert_eq!(mbc5.read_ram(0xA000), 0x22);
+
+        mbc5.write_rom(0x4000, 0b0000_0010);
+        assert!(!mbc5.rumble());
+        assert_eq!(mbc5.read_ram(0xA000), 0x22);
+    }
+}
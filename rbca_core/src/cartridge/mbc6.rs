@@ -0,0 +1,535 @@
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB,
+};
+
+/// Size of the on-cart flash memory.
+const FLASH_SIZE: usize = 1024 * BYTES_IN_KIB as usize;
+/// Size of a flash sector erased by the sector erase command.
+const FLASH_SECTOR_SIZE: usize = 128 * BYTES_IN_KIB as usize;
+/// Flash ID mode: manufacturer ID.
+const FLASH_MANUFACTURER_ID: u8 = 0xC2;
+/// Flash ID mode: device ID.
+const FLASH_DEVICE_ID: u8 = 0x81;
+/// Size of a switchable ROM bank. Each half of 0x4000-0x7FFF is banked separately.
+const MBC6_ROM_BANK_SIZE: usize = 0x2000;
+/// Size of a switchable RAM bank. Each half of 0xA000-0xBFFF is banked separately.
+const MBC6_RAM_BANK_SIZE: usize = 0x1000;
+
+/// The state of the flash command state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlashState {
+    /// Reading. Waiting for the first unlock byte.
+    Read,
+    /// Received 0xAA at 0x5555.
+    Unlock1,
+    /// Received 0x55 at 0x2AAA. Waiting for a command.
+    Unlock2,
+    /// Received an erase command. Waiting for the first unlock byte.
+    Erase,
+    /// Received an erase command & 0xAA at 0x5555.
+    EraseUnlock1,
+    /// Received an erase command & 0x55 at 0x2AAA. Waiting for an erase type.
+    EraseUnlock2,
+    /// Waiting for a byte to program.
+    Program,
+}
+
+/// An MBC6 cartridge.
+#[derive(Debug)]
+pub struct CartMBC6 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    flash: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    ram_enable: bool,
+    /// 4 KiB RAM bank numbers for 0xA000-0xAFFF & 0xB000-0xBFFF.
+    ram_bank_numbers: [u8; 2],
+    /// 8 KiB ROM/flash bank numbers for 0x4000-0x5FFF & 0x6000-0x7FFF.
+    rom_bank_numbers: [u8; 2],
+    /// true = flash mapped to the corresponding ROM window.
+    flash_select: [bool; 2],
+    flash_enable: bool,
+    flash_write_enable: bool,
+    flash_state: FlashState,
+    /// Flash ID mode. Flash reads return the chip IDs instead of data.
+    flash_id_mode: bool,
+}
+impl CartMBC6 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mut mbc6 = Self {
+            rom: data,
+            ram: vec![],
+            flash: vec![0xFF_u8; FLASH_SIZE],
+            header,
+            save_dirty: false,
+            ram_enable: false,
+            ram_bank_numbers: [0x00; 2],
+            rom_bank_numbers: [0x00; 2],
+            flash_select: [false; 2],
+            flash_enable: false,
+            flash_write_enable: false,
+            flash_state: FlashState::Read,
+            flash_id_mode: false,
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        mbc6.ram = vec![0x00_u8; mbc6.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Cart features include MBC6
+        if !mbc6.header.cart_features.mbc6 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New MBC6: According to the header, this cartridge is not an MBC6. Cart type from header: {}", mbc6.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if mbc6.rom().len() != mbc6.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MBC6: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc6.rom().len(), mbc6.header.rom_size)));
+        }
+
+        // Max ROM size
+        if mbc6.rom().len() > (1024 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC6: ROM size too big ({} MiB > 1 MiB)",
+                mbc6.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+        // Max RAM size
+        if mbc6.ram.len() > (32 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC6: RAM size too big ({} KiB > 32 KiB)",
+                mbc6.ram.len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(mbc6)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x2000).try_into().unwrap()
+    }
+
+    /// Get the ROM/flash window (0 = 0x4000-0x5FFF, 1 = 0x6000-0x7FFF) of an address.
+    fn rom_window(address: u16) -> usize {
+        ((address >> 13) & 0b1) as usize
+    }
+
+    /// Get the RAM window (0 = 0xA000-0xAFFF, 1 = 0xB000-0xBFFF) of an address.
+    fn ram_window(address: u16) -> usize {
+        ((address >> 12) & 0b1) as usize
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                let window = Self::rom_window(address);
+                let bank = if self.flash_select[window] {
+                    self.rom_bank_numbers[window] & 0b0111_1111
+                } else {
+                    // Bank numbers higher than the number of banks in the cart are masked.
+                    self.rom_bank_numbers[window] & (self.num_rom_banks() - 1)
+                };
+                result |= (bank as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                let bank = self.ram_bank_numbers[Self::ram_window(address)] & 0b0000_0111;
+                result |= (bank as u32) << 12;
+                result |= (address as u32) & 0b0000_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    /// Handle a write to the flash chip.
+    fn write_flash(&mut self, addr: usize, value: u8) {
+        // Unlock & command addresses only use the low 15 bits.
+        let cmd_addr = addr & 0x7FFF;
+
+        // Writing 0xF0 anywhere resets the chip to read mode.
+        if value == 0xF0 && self.flash_state != FlashState::Program {
+            self.flash_state = FlashState::Read;
+            self.flash_id_mode = false;
+            return;
+        }
+
+        self.flash_state = match (self.flash_state, cmd_addr, value) {
+            (FlashState::Read, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0x80) => FlashState::Erase,
+            (FlashState::Unlock2, 0x5555, 0x90) => {
+                self.flash_id_mode = true;
+                FlashState::Read
+            }
+            (FlashState::Unlock2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Erase, 0x5555, 0xAA) => FlashState::EraseUnlock1,
+            (FlashState::EraseUnlock1, 0x2AAA, 0x55) => FlashState::EraseUnlock2,
+            // Chip erase
+            (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                if self.flash_write_enable {
+                    self.flash.fill(0xFF);
+                    self.save_dirty = true;
+                }
+                FlashState::Read
+            }
+            // Sector erase
+            (FlashState::EraseUnlock2, _, 0x30) => {
+                if self.flash_write_enable {
+                    let start = addr - (addr % FLASH_SECTOR_SIZE);
+                    self.flash[start..start + FLASH_SECTOR_SIZE].fill(0xFF);
+                    self.save_dirty = true;
+                }
+                FlashState::Read
+            }
+            // Programming can only clear bits.
+            (FlashState::Program, _, _) => {
+                if self.flash_write_enable {
+                    self.flash[addr] &= value;
+                    self.save_dirty = true;
+                }
+                FlashState::Read
+            }
+            // Anything unexpected aborts the command.
+            _ => FlashState::Read,
+        };
+    }
+
+    #[cfg(test)]
+    fn new_test() -> Self {
+        CartMBC6::new(
+            super::RomBuilder::new(0x20)
+                .rom_size(0x05)
+                .ram_size(0x03)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartMBC6 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Only the first ROM & RAM windows (0x4000-0x5FFF & 0xA000-0xAFFF) are reported. When flash
+    /// is mapped into the ROM window, the bank is a flash bank.
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / MBC6_ROM_BANK_SIZE,
+            ram_bank: (!self.ram.is_empty())
+                .then(|| self.internal_addr(0xA000) / MBC6_RAM_BANK_SIZE),
+            ram_enabled: self.ram_enable,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn rom_bank_size(&self) -> usize {
+        MBC6_ROM_BANK_SIZE
+    }
+
+    fn ram_bank_size(&self) -> usize {
+        MBC6_RAM_BANK_SIZE
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        // RAM followed by the entire flash
+        let mut data = self.ram.clone();
+        data.extend_from_slice(&self.flash);
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let rest = load_save_section(&mut self.ram, data);
+        load_save_section(&mut self.flash, rest);
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let addr = self.internal_addr(address);
+        if (0x4000..=0x7FFF).contains(&address) && self.flash_select[Self::rom_window(address)] {
+            if !self.flash_enable {
+                return 0xFF_u8;
+            }
+            if self.flash_id_mode {
+                return match addr & 0b1 {
+                    0 => FLASH_MANUFACTURER_ID,
+                    _ => FLASH_DEVICE_ID,
+                };
+            }
+            return self.flash[addr];
+        }
+        *self.rom.get(addr).unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF_u8;
+        }
+        *self
+            .ram
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enable
+            0x0000..=0x03FF => self.ram_enable = (value & 0x0F) == 0x0A,
+            // RAM bank A number
+            0x0400..=0x07FF => self.ram_bank_numbers[0] = value & 0b0000_0111,
+            // RAM bank B number
+            0x0800..=0x0BFF => self.ram_bank_numbers[1] = value & 0b0000_0111,
+            // Flash enable
+            0x0C00..=0x0FFF => self.flash_enable = (value & 0b0000_0001) != 0,
+            // Flash write enable
+            0x1000 => self.flash_write_enable = (value & 0b0000_0001) != 0,
+            // Unused
+            0x1001..=0x1FFF => {}
+            // ROM/flash bank A number
+            0x2000..=0x27FF => self.rom_bank_numbers[0] = value & 0b0111_1111,
+            // ROM/flash bank A select
+            0x2800..=0x2FFF => self.flash_select[0] = value == 0x08,
+            // ROM/flash bank B number
+            0x3000..=0x37FF => self.rom_bank_numbers[1] = value & 0b0111_1111,
+            // ROM/flash bank B select
+            0x3800..=0x3FFF => self.flash_select[1] = value == 0x08,
+            // Flash commands & programming
+            0x4000..=0x7FFF => {
+                if self.flash_enable && self.flash_select[Self::rom_window(address)] {
+                    let addr = self.internal_addr(address);
+                    self.write_flash(addr, value);
+                }
+            }
+            _ => panic!("MBC6 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        let addr = self.internal_addr(address);
+        if addr < self.ram.len() {
+            self.ram[addr] = value;
+            self.save_dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Map flash bank 2 to window A & flash bank 1 to window B, for the unlock addresses.
+    fn map_command_banks(mbc6: &mut CartMBC6) {
+        mbc6.write_rom(0x0C00, 0x01);
+        mbc6.write_rom(0x2000, 0x02);
+        mbc6.write_rom(0x2800, 0x08);
+        mbc6.write_rom(0x3000, 0x01);
+        mbc6.write_rom(0x3800, 0x08);
+    }
+
+    fn send_command(mbc6: &mut CartMBC6, command: u8) {
+        mbc6.write_rom(0x5555, 0xAA);
+        mbc6.write_rom(0x6AAA, 0x55);
+        mbc6.write_rom(0x5555, command);
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0x19)
+            .rom_size(0x05)
+            .ram_size(0x03)
+            .build();
+        assert!(matches!(
+            CartMBC6::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let mbc6 = CartMBC6::new_test();
+        assert!(mbc6.header().logo_valid);
+        assert_eq!(mbc6.num_rom_banks(), 128);
+        assert!(mbc6.cart_features().mbc6);
+        assert_eq!(mbc6.ram.len(), 0x8000);
+        assert_eq!(mbc6.flash.len(), 0x100000);
+    }
+
+    #[test]
+    fn test_rom_windows() {
+        let mut mbc6 = CartMBC6::new_test();
+        for bank_num in 0..128 {
+            mbc6.rom[(bank_num * 0x2000) + 0x0042] = bank_num as u8;
+        }
+
+        for bank_num in 0..128 {
+            mbc6.write_rom(0x2000, bank_num);
+            mbc6.write_rom(0x3000, 127 - bank_num);
+            assert_eq!(mbc6.read_rom(0x4042), bank_num);
+            assert_eq!(mbc6.read_rom(0x6042), 127 - bank_num);
+            assert_eq!(mbc6.read_rom(0x0042), 0x00);
+            assert_eq!(mbc6.read_rom(0x2042), 0x01);
+        }
+    }
+
+    #[test]
+    fn test_ram_windows() {
+        let mut mbc6 = CartMBC6::new_test();
+        mbc6.write_ram(0xA000, 0x12);
+        assert_eq!(mbc6.read_ram(0xA000), 0xFF);
+
+        mbc6.write_rom(0x0000, 0x0A);
+        for bank_num in 0..8 {
+            mbc6.write_rom(0x0400, bank_num);
+            mbc6.write_ram(0xA123, 0x30 + bank_num);
+        }
+        for bank_num in 0..8 {
+            mbc6.write_rom(0x0800, bank_num);
+            assert_eq!(mbc6.read_ram(0xB123), 0x30 + bank_num);
+        }
+        assert_eq!(mbc6.ram[0x7123], 0x37);
+    }
+
+    #[test]
+    fn test_mapper_state() {
+        let mut mbc6 = CartMBC6::new_test();
+        mbc6.write_rom(0x0000, 0x0A);
+        mbc6.write_rom(0x0400, 0x05);
+        mbc6.write_rom(0x2000, 0x42);
+        mbc6.ram[0x5000] = 0x12;
+        let state = mbc6.mapper_state();
+        assert_eq!(state.rom_bank_4000, 0x42);
+        assert_eq!(state.ram_bank, Some(5));
+        assert!(state.ram_enabled);
+        assert_eq!(mbc6.rom_bank(0x42).unwrap().len(), 0x2000);
+        assert_eq!(mbc6.ram_bank(5).unwrap()[0], 0x12);
+        assert!(mbc6.ram_bank(8).is_none());
+    }
+
+    #[test]
+    fn test_flash_select() {
+        let mut mbc6 = CartMBC6::new_test();
+        mbc6.rom[0x2000 * 5] = 0x55;
+        mbc6.flash[0x2000 * 5] = 0x66;
+        mbc6.write_rom(0x2000, 0x05);
+        assert_eq!(mbc6.read_rom(0x4000), 0x55);
+
+        // Flash disabled
+        mbc6.write_rom(0x2800, 0x08);
+        assert_eq!(mbc6.read_rom(0x4000), 0xFF);
+
+        mbc6.write_rom(0x0C00, 0x01);
+        assert_eq!(mbc6.read_rom(0x4000), 0x66);
+
+        mbc6.write_rom(0x2800, 0x00);
+        assert_eq!(mbc6.read_rom(0x4000), 0x55);
+    }
+
+    #[test]
+    fn test_flash_id_mode() {
+        let mut mbc6 = CartMBC6::new_test();
+        map_command_banks(&mut mbc6);
+        send_command(&mut mbc6, 0x90);
+        assert_eq!(mbc6.read_rom(0x4000), FLASH_MANUFACTURER_ID);
+        assert_eq!(mbc6.read_rom(0x6001), FLASH_DEVICE_ID);
+
+        mbc6.write_rom(0x4000, 0xF0);
+        assert_eq!(mbc6.read_rom(0x4000), 0xFF);
+    }
+
+    #[test]
+    fn test_flash_program() {
+        let mut mbc6 = CartMBC6::new_test();
+        map_command_banks(&mut mbc6);
+
+        // Write protected
+        send_command(&mut mbc6, 0xA0);
+        mbc6.write_rom(0x4123, 0x12);
+        assert_eq!(mbc6.read_rom(0x4123), 0xFF);
+        assert!(!mbc6.take_save_dirty());
+
+        mbc6.write_rom(0x1000, 0x01);
+        send_command(&mut mbc6, 0xA0);
+        mbc6.write_rom(0x4123, 0x3C);
+        assert_eq!(mbc6.read_rom(0x4123), 0x3C);
+        assert_eq!(mbc6.flash[0x4123], 0x3C);
+        assert!(mbc6.take_save_dirty());
+        assert!(!mbc6.take_save_dirty());
+
+        // Programming can only clear bits
+        send_command(&mut mbc6, 0xA0);
+        mbc6.write_rom(0x4123, 0xF0);
+        assert_eq!(mbc6.read_rom(0x4123), 0x30);
+
+        // Writes without a command do nothing
+        mbc6.write_rom(0x4124, 0x00);
+        assert_eq!(mbc6.read_rom(0x4124), 0xFF);
+    }
+
+    #[test]
+    fn test_flash_erase() {
+        let mut mbc6 = CartMBC6::new_test();
+        map_command_banks(&mut mbc6);
+        mbc6.write_rom(0x1000, 0x01);
+        mbc6.flash[0x00000] = 0x00;
+        mbc6.flash[0x1FFFF] = 0x00;
+        mbc6.flash[0x20000] = 0x00;
+
+        // Sector erase
+        send_command(&mut mbc6, 0x80);
+        mbc6.write_rom(0x5555, 0xAA);
+        mbc6.write_rom(0x6AAA, 0x55);
+        mbc6.write_rom(0x4000, 0x30);
+        assert_eq!(mbc6.flash[0x00000], 0xFF);
+        assert_eq!(mbc6.flash[0x1FFFF], 0xFF);
+        assert_eq!(mbc6.flash[0x20000], 0x00);
+
+        // Chip erase
+        send_command(&mut mbc6, 0x80);
+        send_command(&mut mbc6, 0x10);
+        assert_eq!(mbc6.flash[0x20000], 0xFF);
+    }
+
+    #[test]
+    fn test_save_data() {
+        let mut mbc6 = CartMBC6::new_test();
+        mbc6.ram[0x1234] = 0x56;
+        mbc6.flash[0xABCDE] = 0x78;
+
+        let data = mbc6.save_data().unwrap();
+        assert_eq!(data.len(), mbc6.ram.len() + FLASH_SIZE);
+
+        let mut loaded = CartMBC6::new_test();
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.ram[0x1234], 0x56);
+        assert_eq!(loaded.flash[0xABCDE], 0x78);
+        assert_eq!(loaded.flash[0xABCDF], 0xFF);
+    }
+}
@@ -0,0 +1,590 @@
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB,
+    ROM_BANK_SIZE,
+};
+
+/// Number of 16-bit words stored in the 93LC56 EEPROM.
+const EEPROM_WORDS: usize = 128;
+/// Accelerometer reading when level.
+const ACCEL_CENTRE: f32 = 0x81D0 as f32;
+/// Accelerometer reading change per 1g of tilt.
+const ACCEL_GRAVITY: f32 = 0x70 as f32;
+/// Latched accelerometer reading after an erase.
+const ACCEL_ERASED: u16 = 0x8000;
+
+/// An MBC7 cartridge, with a two-axis accelerometer & a 93LC56 serial EEPROM.
+#[derive(Debug)]
+pub struct CartMBC7 {
+    rom: Vec<u8>,
+    header: CartridgeHeader,
+    /// Both RAM enable registers must be set to access 0xA000-0xAFFF.
+    ram_enable_1: bool,
+    ram_enable_2: bool,
+    rom_bank_number: u8,
+    /// Current tilt in g. Positive x = tilted right, positive y = tilted down.
+    tilt: (f32, f32),
+    /// Latched accelerometer X & Y readings.
+    accel_latched: (u16, u16),
+    eeprom: Eeprom,
+}
+impl CartMBC7 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mbc7 = Self {
+            rom: data,
+            header,
+            ram_enable_1: false,
+            ram_enable_2: false,
+            rom_bank_number: 0x01,
+            tilt: (0.0, 0.0),
+            accel_latched: (ACCEL_ERASED, ACCEL_ERASED),
+            eeprom: Eeprom::new(),
+        };
+
+        // Validate proper parameters
+        // Cart features include MBC7
+        if !mbc7.header.cart_features.mbc7 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New MBC7: According to the header, this cartridge is not an MBC7. Cart type from header: {}", mbc7.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if mbc7.rom().len() != mbc7.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MBC7: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mbc7.rom().len(), mbc7.header.rom_size)));
+        }
+
+        // Max ROM size
+        if mbc7.rom().len() > (2 * 1024 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New MBC7: ROM size too big ({} MiB > 2 MiB)",
+                mbc7.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(mbc7)
+    }
+
+    fn num_rom_banks(&self) -> u16 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u16 {
+        // Bank numbers higher than the number of banks in the cart are masked.
+        (self.rom_bank_number as u16) & (self.num_rom_banks() - 1)
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enable_1 && self.ram_enable_2
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    /// Convert a tilt in g to an accelerometer reading.
+    fn accel_value(tilt: f32) -> u16 {
+        (ACCEL_CENTRE + (tilt * ACCEL_GRAVITY)).clamp(0.0, u16::MAX as f32) as u16
+    }
+
+    #[cfg(test)]
+    fn new_test(rom_size_value: u8) -> Self {
+        CartMBC7::new(
+            super::RomBuilder::new(0x22)
+                .rom_size(rom_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartMBC7 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // The accelerometer & EEPROM are mapped instead of RAM.
+            ram_bank: None,
+            ram_enabled: self.ram_enable_1 && self.ram_enable_2,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.eeprom.data.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_save_section(&mut self.eeprom.data, data);
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.eeprom.dirty)
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled() || address >= 0xB000 {
+            return 0xFF_u8;
+        }
+        // Address bits 4-7 select the register.
+        match (address >> 4) & 0x0F {
+            0x2 => self.accel_latched.0 as u8,
+            0x3 => (self.accel_latched.0 >> 8) as u8,
+            0x4 => self.accel_latched.1 as u8,
+            0x5 => (self.accel_latched.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF_u8,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enable 1
+            0x0000..=0x1FFF => self.ram_enable_1 = value == 0x0A,
+            // ROM bank number
+            0x2000..=0x3FFF => self.rom_bank_number = value,
+            // RAM enable 2
+            0x4000..=0x5FFF => self.ram_enable_2 = value == 0x40,
+            // Unused
+            0x6000..=0x7FFF => {}
+            _ => panic!("MBC7 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled() || address >= 0xB000 {
+            return;
+        }
+        match (address >> 4) & 0x0F {
+            // Erase the latched accelerometer data.
+            0x0 if value == 0x55 => self.accel_latched = (ACCEL_ERASED, ACCEL_ERASED),
+            // Latch the accelerometer. Only works after an erase.
+            0x1 if value == 0xAA && self.accel_latched == (ACCEL_ERASED, ACCEL_ERASED) => {
+                self.accel_latched = (
+                    Self::accel_value(self.tilt.0),
+                    Self::accel_value(self.tilt.1),
+                );
+            }
+            0x8 => self.eeprom.write(value),
+            _ => {}
+        }
+    }
+}
+
+/// The stage of the current EEPROM transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EepromState {
+    /// Shifting in the start bit, opcode, & address.
+    Command { bits: u8, value: u16 },
+    /// Shifting out a word.
+    Read { bits: u8, value: u16 },
+    /// Shifting in a word to write. No address = write all.
+    WriteData {
+        address: Option<u8>,
+        bits: u8,
+        value: u16,
+    },
+    /// Transaction done. Waiting for chip select to go low.
+    Done,
+}
+
+/// A 93LC56 serial EEPROM in 16-bit mode, accessed by bit-banging the MBC7 EEPROM register.
+#[derive(Debug, Clone)]
+struct Eeprom {
+    /// Little-endian 16-bit words.
+    data: Vec<u8>,
+    state: EepromState,
+    write_enable: bool,
+    /// Chip select.
+    cs: bool,
+    /// Clock.
+    clk: bool,
+    /// Data in.
+    di: bool,
+    /// Data out.
+    dout: bool,
+    /// Set when a word is written.
+    dirty: bool,
+}
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            data: vec![0xFF_u8; EEPROM_WORDS * 2],
+            state: EepromState::Command { bits: 0, value: 0 },
+            write_enable: false,
+            cs: false,
+            clk: false,
+            di: false,
+            dout: true,
+            dirty: false,
+        }
+    }
+
+    /// The value of the EEPROM register. Bit 7 = CS, bit 6 = CLK, bit 1 = DI, bit 0 = DO.
+    fn read(&self) -> u8 {
+        ((self.cs as u8) << 7)
+            | ((self.clk as u8) << 6)
+            | ((self.di as u8) << 1)
+            | (self.dout as u8)
+    }
+
+    fn write(&mut self, value: u8) {
+        let cs = (value & 0b1000_0000) != 0;
+        let clk = (value & 0b0100_0000) != 0;
+        self.di = (value & 0b0000_0010) != 0;
+
+        if !cs {
+            // Deselecting the chip ends the transaction.
+            self.state = EepromState::Command { bits: 0, value: 0 };
+        } else if self.cs && clk && !self.clk {
+            self.clock_rising_edge();
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock_rising_edge(&mut self) {
+        let di = self.di as u16;
+        self.state = match self.state {
+            // Leading 0s before the start bit are ignored.
+            EepromState::Command { bits: 0, .. } if di == 0 => self.state,
+            EepromState::Command { bits, value } => {
+                let value = (value << 1) | di;
+                // Start bit + 2-bit opcode + 8-bit address
+                if bits + 1 == 11 {
+                    self.execute(value)
+                } else {
+                    EepromState::Command {
+                        bits: bits + 1,
+                        value,
+                    }
+                }
+            }
+            EepromState::Read { bits, value } => {
+                self.dout = (value & 0x8000) != 0;
+                if bits + 1 == 16 {
+                    EepromState::Done
+                } else {
+                    EepromState::Read {
+                        bits: bits + 1,
+                        value: value << 1,
+                    }
+                }
+            }
+            EepromState::WriteData {
+                address,
+                bits,
+                value,
+            } => {
+                let value = (value << 1) | di;
+                if bits + 1 == 16 {
+                    if self.write_enable {
+                        match address {
+                            Some(address) => self.set_word(address, value),
+                            None => (0..EEPROM_WORDS as u8).for_each(|a| self.set_word(a, value)),
+                        }
+                    }
+                    // Ready
+                    self.dout = true;
+                    EepromState::Done
+                } else {
+                    EepromState::WriteData {
+                        address,
+                        bits: bits + 1,
+                        value,
+                    }
+                }
+            }
+            EepromState::Done => EepromState::Done,
+        };
+    }
+
+    /// Execute an 11-bit command. Return the next state.
+    fn execute(&mut self, command: u16) -> EepromState {
+        let opcode = (command >> 8) & 0b11;
+        // The top address bit is unused in 16-bit mode.
+        let address = (command & 0x7F) as u8;
+        match opcode {
+            // READ: a dummy 0 bit, then the word.
+            0b10 => {
+                self.dout = false;
+                EepromState::Read {
+                    bits: 0,
+                    value: self.word(address),
+                }
+            }
+            // WRITE
+            0b01 => EepromState::WriteData {
+                address: Some(address),
+                bits: 0,
+                value: 0,
+            },
+            // ERASE
+            0b11 => {
+                if self.write_enable {
+                    self.set_word(address, 0xFFFF);
+                }
+                self.dout = true;
+                EepromState::Done
+            }
+            _ => match (command >> 6) & 0b11 {
+                // EWDS
+                0b00 => {
+                    self.write_enable = false;
+                    EepromState::Done
+                }
+                // WRAL
+                0b01 => EepromState::WriteData {
+                    address: None,
+                    bits: 0,
+                    value: 0,
+                },
+                // ERAL
+                0b10 => {
+                    if self.write_enable {
+                        self.data.fill(0xFF);
+                    }
+                    self.dout = true;
+                    EepromState::Done
+                }
+                // EWEN
+                _ => {
+                    self.write_enable = true;
+                    EepromState::Done
+                }
+            },
+        }
+    }
+
+    fn word(&self, address: u8) -> u16 {
+        let i = (address as usize) * 2;
+        u16::from_le_bytes([self.data[i], self.data[i + 1]])
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        let i = (address as usize) * 2;
+        self.data[i..i + 2].copy_from_slice(&value.to_le_bytes());
+        self.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Clock a single bit into the EEPROM.
+    fn send_bit(mbc7: &mut CartMBC7, bit: bool) {
+        let di = (bit as u8) << 1;
+        mbc7.write_ram(0xA080, 0x80 | di);
+        mbc7.write_ram(0xA080, 0xC0 | di);
+    }
+
+    /// Select the EEPROM & send a start bit, opcode, & address.
+    fn send_command(mbc7: &mut CartMBC7, opcode: u8, address: u8) {
+        mbc7.write_ram(0xA080, 0x00);
+        mbc7.write_ram(0xA080, 0x80);
+        send_bit(mbc7, true);
+        for i in (0..2).rev() {
+            send_bit(mbc7, (opcode >> i) & 1 != 0);
+        }
+        for i in (0..8).rev() {
+            send_bit(mbc7, (address >> i) & 1 != 0);
+        }
+    }
+
+    fn send_word(mbc7: &mut CartMBC7, value: u16) {
+        for i in (0..16).rev() {
+            send_bit(mbc7, (value >> i) & 1 != 0);
+        }
+    }
+
+    fn read_word(mbc7: &mut CartMBC7, address: u8) -> u16 {
+        send_command(mbc7, 0b10, address);
+        // Dummy bit
+        assert_eq!(mbc7.read_ram(0xA080) & 0x01, 0x00);
+        let mut result = 0;
+        for _ in 0..16 {
+            send_bit(mbc7, false);
+            result = (result << 1) | ((mbc7.read_ram(0xA080) & 0x01) as u16);
+        }
+        mbc7.write_ram(0xA080, 0x00);
+        result
+    }
+
+    fn enabled_mbc7() -> CartMBC7 {
+        let mut mbc7 = CartMBC7::new_test(0x05);
+        mbc7.write_rom(0x0000, 0x0A);
+        mbc7.write_rom(0x4000, 0x40);
+        mbc7
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0x19).rom_size(0x01).build();
+        assert!(matches!(
+            CartMBC7::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let mbc7 = CartMBC7::new_test(0x05);
+        assert!(mbc7.header().logo_valid);
+        assert_eq!(mbc7.num_rom_banks(), 64);
+        assert!(mbc7.cart_features().mbc7);
+        assert!(mbc7.cart_features().sensor);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut mbc7 = CartMBC7::new_test(0x05);
+        for bank_num in 0..64 {
+            mbc7.rom[(bank_num * 0x4000) + 0x0777] = bank_num as u8;
+        }
+        for bank_num in 0..64 {
+            mbc7.write_rom(0x2000, bank_num);
+            assert_eq!(mbc7.read_rom(0x4777), bank_num);
+            assert_eq!(mbc7.read_rom(0x0777), 0x00);
+        }
+    }
+
+    #[test]
+    fn test_ram_enable() {
+        let mut mbc7 = CartMBC7::new_test(0x01);
+        assert_eq!(mbc7.read_ram(0xA060), 0xFF);
+        mbc7.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc7.read_ram(0xA060), 0xFF);
+        mbc7.write_rom(0x4000, 0x40);
+        assert_eq!(mbc7.read_ram(0xA060), 0x00);
+        assert_eq!(mbc7.read_ram(0xB060), 0xFF);
+        mbc7.write_rom(0x0000, 0x00);
+        assert_eq!(mbc7.read_ram(0xA060), 0xFF);
+    }
+
+    #[test]
+    fn test_accelerometer() {
+        let mut mbc7 = enabled_mbc7();
+        assert_eq!(mbc7.read_ram(0xA020), 0x00);
+        assert_eq!(mbc7.read_ram(0xA030), 0x80);
+
+        // Level
+        mbc7.write_ram(0xA000, 0x55);
+        mbc7.write_ram(0xA010, 0xAA);
+        assert_eq!(mbc7.read_ram(0xA020), 0xD0);
+        assert_eq!(mbc7.read_ram(0xA030), 0x81);
+        assert_eq!(mbc7.read_ram(0xA040), 0xD0);
+        assert_eq!(mbc7.read_ram(0xA050), 0x81);
+
+        // Latching again without erasing does nothing
+        mbc7.set_tilt(1.0, -1.0);
+        mbc7.write_ram(0xA010, 0xAA);
+        assert_eq!(mbc7.accel_latched, (0x81D0, 0x81D0));
+
+        mbc7.write_ram(0xA000, 0x55);
+        assert_eq!(mbc7.accel_latched, (0x8000, 0x8000));
+        mbc7.write_ram(0xA010, 0xAA);
+        assert_eq!(mbc7.accel_latched, (0x81D0 + 0x70, 0x81D0 - 0x70));
+        // Mirrored across 0xA000-0xAFFF
+        assert_eq!(mbc7.read_ram(0xA725), 0x40);
+        assert_eq!(mbc7.read_ram(0xAF5F), 0x81);
+    }
+
+    #[test]
+    fn test_eeprom_write_protect() {
+        let mut mbc7 = enabled_mbc7();
+        send_command(&mut mbc7, 0b01, 0x05);
+        send_word(&mut mbc7, 0x1234);
+        assert_eq!(read_word(&mut mbc7, 0x05), 0xFFFF);
+        assert!(!mbc7.take_save_dirty());
+    }
+
+    #[test]
+    fn test_eeprom_read_write_erase() {
+        let mut mbc7 = enabled_mbc7();
+
+        // EWEN
+        send_command(&mut mbc7, 0b00, 0b1100_0000);
+        send_command(&mut mbc7, 0b01, 0x05);
+        send_word(&mut mbc7, 0x1234);
+        // Ready
+        assert_eq!(mbc7.read_ram(0xA080) & 0x01, 0x01);
+        assert!(mbc7.take_save_dirty());
+        send_command(&mut mbc7, 0b01, 0x7F);
+        send_word(&mut mbc7, 0xBEEF);
+        assert_eq!(read_word(&mut mbc7, 0x05), 0x1234);
+        assert_eq!(read_word(&mut mbc7, 0x7F), 0xBEEF);
+        // Top address bit is ignored
+        assert_eq!(read_word(&mut mbc7, 0x85), 0x1234);
+
+        // ERASE
+        send_command(&mut mbc7, 0b11, 0x05);
+        assert_eq!(read_word(&mut mbc7, 0x05), 0xFFFF);
+        assert_eq!(read_word(&mut mbc7, 0x7F), 0xBEEF);
+
+        // WRAL
+        send_command(&mut mbc7, 0b00, 0b0100_0000);
+        send_word(&mut mbc7, 0xA5A5);
+        assert!((0..128).all(|a| read_word(&mut mbc7, a) == 0xA5A5));
+
+        // ERAL
+        send_command(&mut mbc7, 0b00, 0b1000_0000);
+        assert!((0..128).all(|a| read_word(&mut mbc7, a) == 0xFFFF));
+
+        // EWDS
+        send_command(&mut mbc7, 0b00, 0b0000_0000);
+        send_command(&mut mbc7, 0b01, 0x05);
+        send_word(&mut mbc7, 0x1234);
+        assert_eq!(read_word(&mut mbc7, 0x05), 0xFFFF);
+    }
+
+    #[test]
+    fn test_save_data() {
+        let mut mbc7 = enabled_mbc7();
+        send_command(&mut mbc7, 0b00, 0b1100_0000);
+        send_command(&mut mbc7, 0b01, 0x05);
+        send_word(&mut mbc7, 0x1234);
+
+        let data = mbc7.save_data().unwrap();
+        assert_eq!(data.len(), 256);
+
+        let mut loaded = enabled_mbc7();
+        loaded.load_save_data(&data);
+        assert_eq!(read_word(&mut loaded, 0x05), 0x1234);
+    }
+}
@@ -0,0 +1,460 @@
+use super::{
+    header::checksum_fn, load_save_section, CartFeatures, Cartridge, CartridgeError,
+    CartridgeHeader, LoadWarning, MapperState, BYTES_IN_KIB, LOGO, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// Size of the menu stored at the end of an MMM01 ROM.
+const MENU_SIZE: usize = 32 * (BYTES_IN_KIB as usize);
+
+/// Return true iff the given ROM has an MMM01 header, either at the start of the ROM or at the
+/// start of the menu in the last 32 KiB.
+pub fn is_mmm01(data: &[u8]) -> bool {
+    header_offset(data).is_some()
+}
+
+/// Pad (with 0xFF) or truncate over- & under-sized MMM01 dumps to the ROM size in the MMM01
+/// header. If the menu is in the last 32 KiB, it is kept there & the games before it are padded
+/// or truncated instead. Return a warning if the size was changed.
+pub fn fit_rom_size(data: &mut Vec<u8>) -> Result<Option<LoadWarning>, CartridgeError> {
+    match header_offset(data) {
+        Some(0) | None => super::fit_rom_size(data),
+        Some(_) => {
+            // Move the menu to the front so its header is used, then move it back.
+            data.rotate_right(MENU_SIZE);
+            let warning = super::fit_rom_size(data)?;
+            data.rotate_left(MENU_SIZE);
+            Ok(warning)
+        }
+    }
+}
+
+/// Find the offset of the MMM01 header.
+///
+/// The last 32 KiB of any other ROM is ordinary bank data, so the menu header is only used if the
+/// header at the start doesn't name a known mapper & the menu header has a valid logo & header
+/// checksum.
+fn header_offset(data: &[u8]) -> Option<usize> {
+    let is_mmm01_type = |offset: usize| {
+        data.get(offset + 0x0147)
+            .is_some_and(|cart_type| (0x0B..=0x0D).contains(cart_type))
+    };
+
+    if is_mmm01_type(0) {
+        return Some(0);
+    }
+    let menu_start = data.len().checked_sub(MENU_SIZE)?;
+    if CartFeatures::from_data(data) != CartFeatures::default() || !is_mmm01_type(menu_start) {
+        return None;
+    }
+    let menu = &data[menu_start..];
+    (menu[0x0104..=0x0133] == LOGO && menu[0x014D] == checksum_fn(menu)).then_some(menu_start)
+}
+
+/// An MMM01 multicart cartridge.
+///
+/// Starts "unmapped", with the menu in the last 32 KiB of ROM mapped to 0x0000-0x7FFF. Once the
+/// menu has selected a game & written the map enable bit, the outer bank bits & masks are locked
+/// in & the cart behaves like an MBC1 limited to the selected game.
+#[derive(Debug)]
+pub struct CartMMM01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    /// false = unmapped (menu), true = mapped (game). Can only be set once.
+    mapped: bool,
+    ram_enable: bool,
+    /// ROM bank number bits 0-4.
+    rom_bank_low: u8,
+    /// ROM bank number bits 5-6. Only writable while unmapped.
+    rom_bank_mid: u8,
+    /// ROM bank number bits 7-8. Only writable while unmapped.
+    rom_bank_high: u8,
+    /// Set bits lock the corresponding ROM bank number bits 1-4 once mapped.
+    rom_bank_mask: u8,
+    /// RAM bank number bits 0-1.
+    ram_bank_low: u8,
+    /// RAM bank number bits 2-3. Only writable while unmapped.
+    ram_bank_high: u8,
+    /// Set bits lock the corresponding RAM bank number bits 0-1 once mapped.
+    ram_bank_mask: u8,
+    /// false = 0 = simple, true = 1 = advanced
+    banking_mode_select: bool,
+    /// Prevents the game from changing the banking mode once mapped.
+    mode_write_disable: bool,
+}
+impl CartMMM01 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let Some(header_offset) = header_offset(&data) else {
+            return Err(CartridgeError::UnsupportedMapper(String::from(
+                "New MMM01: According to the header, this cartridge is not an MMM01.",
+            )));
+        };
+        let header = CartridgeHeader::from_data_at(&data, header_offset);
+
+        let mut mmm01 = Self {
+            rom: data,
+            ram: vec![],
+            header,
+            save_dirty: false,
+            mapped: false,
+            ram_enable: false,
+            rom_bank_low: 0x00,
+            rom_bank_mid: 0x00,
+            rom_bank_high: 0x00,
+            rom_bank_mask: 0x00,
+            ram_bank_low: 0x00,
+            ram_bank_high: 0x00,
+            ram_bank_mask: 0x00,
+            banking_mode_select: false,
+            mode_write_disable: false,
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        mmm01.ram = vec![0x00_u8; mmm01.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Allocated ROM size matches ROM size in header
+        if mmm01.rom().len() != mmm01.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New MMM01: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", mmm01.rom().len(), mmm01.header.rom_size)));
+        }
+
+        Ok(mmm01)
+    }
+
+    fn num_rom_banks(&self) -> u16 {
+        (self.rom.len() / 0x4000).try_into().unwrap()
+    }
+
+    fn num_ram_banks(&self) -> u8 {
+        (self.ram.len() / 0x2000).try_into().unwrap()
+    }
+
+    /// The ROM bank number bits 0-4 that the game can still change.
+    fn rom_bank_inner_mask(&self) -> u8 {
+        if self.mapped {
+            !(self.rom_bank_mask << 1) & 0b0001_1111
+        } else {
+            0b0001_1111
+        }
+    }
+
+    /// The RAM bank number bits 0-1 that the game can still change.
+    fn ram_bank_inner_mask(&self) -> u8 {
+        if self.mapped {
+            !self.ram_bank_mask & 0b0000_0011
+        } else {
+            0b0000_0011
+        }
+    }
+
+    /// Get the ROM bank mapped to 0x0000-0x3FFF (false) or 0x4000-0x7FFF (true).
+    fn get_rom_bank_number(&self, upper_area: bool) -> u16 {
+        let inner_mask = self.rom_bank_inner_mask();
+        let mut low = self.rom_bank_low;
+        if upper_area {
+            // Like MBC1, bank 0 of the selected game maps to bank 1.
+            if (low & inner_mask) == 0 {
+                low |= 0b0000_0001;
+            }
+        } else {
+            // The first bank of the selected game.
+            low &= !inner_mask;
+        }
+
+        let mut bank =
+            (low as u16) | ((self.rom_bank_mid as u16) << 5) | ((self.rom_bank_high as u16) << 7);
+        if !self.mapped {
+            // While unmapped, bits 1-8 are forced high so the menu in the last banks is visible.
+            bank |= 0b1_1111_1110;
+        }
+        bank & (self.num_rom_banks() - 1)
+    }
+
+    fn get_ram_bank_number(&self) -> u8 {
+        let low = if self.banking_mode_select {
+            self.ram_bank_low
+        } else {
+            // In mode 0, only the locked bits of the RAM bank number are used.
+            self.ram_bank_low & !self.ram_bank_inner_mask()
+        };
+        (low | (self.ram_bank_high << 2)) & self.num_ram_banks().saturating_sub(1)
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= (self.get_rom_bank_number(false) as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number(true) as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                result |= (self.get_ram_bank_number() as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+}
+impl Cartridge for CartMMM01 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: self.internal_addr(0x0000) / ROM_BANK_SIZE,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            ram_bank: (!self.ram.is_empty()).then(|| self.internal_addr(0xA000) / RAM_BANK_SIZE),
+            ram_enabled: self.ram_enable,
+            mode: self.banking_mode_select as u8,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.header.cart_features.battery {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.header.cart_features.battery {
+            load_save_section(&mut self.ram, data);
+        }
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF_u8;
+        }
+        *self
+            .ram
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enable. While unmapped: RAM bank mask & map enable.
+            0x0000..=0x1FFF => {
+                self.ram_enable = (value & 0x0F) == 0x0A;
+                if !self.mapped {
+                    self.ram_bank_mask = (value >> 4) & 0b0000_0011;
+                    // Lock in the selected game.
+                    self.mapped = (value & 0b0100_0000) != 0;
+                }
+            }
+            // ROM bank number. While unmapped: ROM bank mid bits.
+            0x2000..=0x3FFF => {
+                let inner_mask = self.rom_bank_inner_mask();
+                self.rom_bank_low = (self.rom_bank_low & !inner_mask) | (value & inner_mask);
+                if !self.mapped {
+                    self.rom_bank_mid = (value >> 5) & 0b0000_0011;
+                }
+            }
+            // RAM bank number. While unmapped: RAM bank high bits, ROM bank high bits & mode
+            // write disable.
+            0x4000..=0x5FFF => {
+                let inner_mask = self.ram_bank_inner_mask();
+                self.ram_bank_low = (self.ram_bank_low & !inner_mask) | (value & inner_mask);
+                if !self.mapped {
+                    self.ram_bank_high = (value >> 2) & 0b0000_0011;
+                    self.rom_bank_high = (value >> 4) & 0b0000_0011;
+                    self.mode_write_disable = (value & 0b0100_0000) != 0;
+                }
+            }
+            // Banking mode select. While unmapped: ROM bank mask.
+            // Multiplex mode (bit 6) is not emulated.
+            0x6000..=0x7FFF => {
+                if !(self.mapped && self.mode_write_disable) {
+                    self.banking_mode_select = (value & 0b0000_0001) == 0x01;
+                }
+                if !self.mapped {
+                    self.rom_bank_mask = (value >> 2) & 0b0000_1111;
+                }
+            }
+            _ => panic!("MMM01 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        let addr = self.internal_addr(address);
+        if addr < self.ram.len() {
+            self.ram[addr] = value;
+            self.save_dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn new_test(ram_size_value: u8) -> CartMMM01 {
+        // 512 KiB: 32 banks. Tag each bank with its number.
+        let mut data = super::super::RomBuilder::new(0x0D)
+            .rom_size(0x04)
+            .ram_size(ram_size_value)
+            .build();
+        for bank_num in 0..32 {
+            data[(bank_num * 0x4000) + 0x1000] = bank_num as u8;
+        }
+        CartMMM01::new(data).unwrap()
+    }
+
+    /// Lock in the game at banks 8-15 (128 KiB).
+    fn map_game(mmm01: &mut CartMMM01) {
+        // Outer bank bit 3 set.
+        mmm01.write_rom(0x2000, 0b0000_1000);
+        // Lock bank bits 3-4.
+        mmm01.write_rom(0x6000, 0b0011_0000);
+        // Map enable.
+        mmm01.write_rom(0x0000, 0b0100_0000);
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0x01).rom_size(0x04).build();
+        assert!(matches!(
+            CartMMM01::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_detection() {
+        let data = super::super::RomBuilder::new(0x0B).rom_size(0x04).build();
+        assert!(is_mmm01(&data));
+
+        // Menu header in the last 32 KiB, after a game without a known mapper in its header
+        let mut data = super::super::RomBuilder::new(0xEF).rom_size(0x04).build();
+        assert!(!is_mmm01(&data));
+        let menu = super::super::RomBuilder::new(0x0C)
+            .rom_size(0x04)
+            .ram_size(0x02)
+            .build();
+        let menu_start = data.len() - MENU_SIZE;
+        data[menu_start..menu_start + 0x0150].copy_from_slice(&menu[..0x0150]);
+        assert!(is_mmm01(&data));
+
+        // The menu header needs a valid logo & header checksum.
+        let mut bad_checksum = data.clone();
+        bad_checksum[menu_start + 0x014D] ^= 0xFF;
+        assert!(!is_mmm01(&bad_checksum));
+        let mut bad_logo = data.clone();
+        bad_logo[menu_start + 0x0104] ^= 0xFF;
+        assert!(!is_mmm01(&bad_logo));
+
+        // A known mapper in the header at the start wins.
+        let mut known_mapper = data.clone();
+        known_mapper[0x0147] = 0x19;
+        assert!(!is_mmm01(&known_mapper));
+
+        let mmm01 = CartMMM01::new(data).unwrap();
+        assert!(mmm01.cart_features().mmm01);
+        assert!(mmm01.cart_features().ram);
+        assert_eq!(mmm01.header.ram_size, 0x2000);
+        assert_eq!(mmm01.ram.len(), 0x2000);
+    }
+
+    #[test]
+    fn test_unmapped() {
+        let mut mmm01 = new_test(0x00);
+
+        // Menu in the last 2 banks
+        assert_eq!(mmm01.read_rom(0x1000), 30);
+        assert_eq!(mmm01.read_rom(0x5000), 31);
+
+        // Switching banks while unmapped doesn't move away from the menu
+        mmm01.write_rom(0x2000, 0b0000_0101);
+        assert_eq!(mmm01.read_rom(0x1000), 30);
+        assert_eq!(mmm01.read_rom(0x5000), 31);
+    }
+
+    #[test]
+    fn test_mapping() {
+        let mut mmm01 = new_test(0x00);
+        map_game(&mut mmm01);
+        assert!(mmm01.mapped);
+
+        // First bank of the game at 0x0000-0x3FFF, 0->1 translation at 0x4000-0x7FFF
+        assert_eq!(mmm01.read_rom(0x1000), 8);
+        assert_eq!(mmm01.read_rom(0x5000), 9);
+
+        // Inner bank bits are still writable
+        mmm01.write_rom(0x2000, 0b0000_0011);
+        assert_eq!(mmm01.read_rom(0x5000), 11);
+        assert_eq!(mmm01.read_rom(0x1000), 8);
+
+        // Locked bits can't be changed
+        mmm01.write_rom(0x3FFF, 0b0111_1111);
+        assert_eq!(mmm01.read_rom(0x5000), 15);
+        mmm01.write_rom(0x3FFF, 0b0001_0000);
+        assert_eq!(mmm01.read_rom(0x5000), 9);
+
+        // The mapping can't be undone
+        mmm01.write_rom(0x6000, 0b0000_0000);
+        mmm01.write_rom(0x0000, 0b0000_0000);
+        assert!(mmm01.mapped);
+        mmm01.write_rom(0x2000, 0b0000_0010);
+        assert_eq!(mmm01.read_rom(0x5000), 10);
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut mmm01 = new_test(0x03);
+        mmm01.ram[0x2000 * 2] = 0xAB;
+
+        // Lock RAM bank bit 1 & select RAM bank 2 for the game
+        mmm01.write_rom(0x4000, 0b0000_0010);
+        mmm01.write_rom(0x6000, 0b0011_0000);
+        mmm01.write_rom(0x0000, 0b0110_1010);
+        assert!(mmm01.mapped);
+        assert!(mmm01.ram_enable);
+        assert_eq!(mmm01.read_ram(0xA000), 0xAB);
+
+        // The game can only switch between RAM banks 2 & 3
+        mmm01.write_rom(0x6000, 0x01);
+        mmm01.write_rom(0x4000, 0b0000_0001);
+        mmm01.write_ram(0xA000, 0xCD);
+        assert_eq!(mmm01.ram[0x2000 * 3], 0xCD);
+        mmm01.write_rom(0x4000, 0b0000_0000);
+        assert_eq!(mmm01.read_ram(0xA000), 0xAB);
+    }
+}
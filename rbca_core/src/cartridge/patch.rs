@@ -0,0 +1,423 @@
+//! IPS, UPS & BPS ROM patches.
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::CartridgeError;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// Size of the source, target & patch CRC32s at the end of UPS & BPS patches.
+const FOOTER_SIZE: usize = 12;
+/// File extensions of patches, in order of preference.
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+/// Find a patch next to the given ROM with the same file stem, if there is one.
+pub fn find_patch<P: AsRef<Utf8Path>>(rom_path: P) -> Option<Utf8PathBuf> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|ext| rom_path.as_ref().with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+/// Apply an IPS, UPS or BPS patch to the given ROM. The patch format is detected from its magic
+/// bytes.
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(patch_error("Unknown patch format"))
+    }
+}
+
+fn patch_error(msg: &str) -> CartridgeError {
+    CartridgeError::Patch(String::from(msg))
+}
+
+/// Reads the fields of a patch in order.
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], CartridgeError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| patch_error("Patch ends unexpectedly"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, CartridgeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Read a big-endian number of the given number of bytes.
+    fn be(&mut self, len: usize) -> Result<usize, CartridgeError> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |acc, &byte| (acc << 8) | byte as usize))
+    }
+
+    /// Read a variable-length number, as used by UPS & BPS.
+    fn varint(&mut self) -> Result<usize, CartridgeError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            value += (byte & 0x7F) as usize * shift;
+            if (byte & 0x80) != 0 {
+                return Ok(value);
+            }
+            shift <<= 7;
+            value += shift;
+            if shift > (1 << 56) {
+                return Err(patch_error("Number in patch is too large"));
+            }
+        }
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let mut target = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        let offset_bytes = reader.bytes(3)?;
+        if offset_bytes == IPS_EOF {
+            break;
+        }
+        let offset = offset_bytes
+            .iter()
+            .fold(0, |acc, &byte| (acc << 8) | byte as usize);
+
+        let size = reader.be(2)?;
+        let (size, data) = if size == 0 {
+            // RLE record
+            let size = reader.be(2)?;
+            (size, vec![reader.byte()?; size])
+        } else {
+            (size, reader.bytes(size)?.to_vec())
+        };
+
+        if target.len() < offset + size {
+            target.resize(offset + size, 0x00);
+        }
+        target[offset..offset + size].copy_from_slice(&data);
+    }
+
+    // Optional truncation extension
+    if let Ok(truncate_size) = reader.be(3) {
+        target.truncate(truncate_size);
+    }
+
+    Ok(target)
+}
+
+/// Split off & validate the CRC32 footer of a UPS or BPS patch. Return the expected source &
+/// target CRC32s.
+fn check_footer(patch: &[u8], format: &str) -> Result<(u32, u32), CartridgeError> {
+    if patch.len() < FOOTER_SIZE {
+        return Err(patch_error("Patch ends unexpectedly"));
+    }
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let crc = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+
+    let actual_patch_crc = crc32fast::hash(&patch[..patch.len() - 4]);
+    if actual_patch_crc != crc(8) {
+        return Err(CartridgeError::Patch(format!(
+            "{format} patch CRC32 mismatch ({actual_patch_crc:#010X} != {:#010X}). The patch is corrupt.",
+            crc(8)
+        )));
+    }
+    Ok((crc(0), crc(4)))
+}
+
+/// Check the CRC32 of the ROM the patch is being applied to.
+fn check_source_crc(rom: &[u8], expected: u32, format: &str) -> Result<(), CartridgeError> {
+    let actual = crc32fast::hash(rom);
+    if actual != expected {
+        return Err(CartridgeError::Patch(format!(
+            "{format} source CRC32 mismatch ({actual:#010X} != {expected:#010X}). The patch is for a different ROM."
+        )));
+    }
+    Ok(())
+}
+
+/// Check the CRC32 of the patched ROM.
+fn check_target_crc(target: &[u8], expected: u32, format: &str) -> Result<(), CartridgeError> {
+    let actual = crc32fast::hash(target);
+    if actual != expected {
+        return Err(CartridgeError::Patch(format!(
+            "{format} target CRC32 mismatch ({actual:#010X} != {expected:#010X}). The patch was applied incorrectly."
+        )));
+    }
+    Ok(())
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let (source_crc, target_crc) = check_footer(patch, "UPS")?;
+    check_source_crc(rom, source_crc, "UPS")?;
+
+    let patch = &patch[..patch.len() - FOOTER_SIZE];
+    let mut reader = PatchReader::new(patch, UPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    if source_size != rom.len() {
+        return Err(CartridgeError::Patch(format!(
+            "UPS source size mismatch ({} bytes != {source_size} bytes). The patch is for a different ROM.",
+            rom.len()
+        )));
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0x00);
+    let mut offset: usize = 0;
+    while reader.pos < patch.len() {
+        offset += reader.varint()?;
+        loop {
+            let xor = reader.byte()?;
+            if xor == 0x00 {
+                offset += 1;
+                break;
+            }
+            if let Some(byte) = target.get_mut(offset) {
+                *byte ^= xor;
+            }
+            offset += 1;
+        }
+    }
+
+    check_target_crc(&target, target_crc, "UPS")?;
+    Ok(target)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let (source_crc, target_crc) = check_footer(patch, "BPS")?;
+    check_source_crc(rom, source_crc, "BPS")?;
+
+    let patch = &patch[..patch.len() - FOOTER_SIZE];
+    let mut reader = PatchReader::new(patch, BPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(CartridgeError::Patch(format!(
+            "BPS source size mismatch ({} bytes != {source_size} bytes). The patch is for a different ROM.",
+            rom.len()
+        )));
+    }
+
+    let mut target: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    let out_of_bounds = || patch_error("BPS patch reads outside of the ROM");
+    while reader.pos < patch.len() {
+        let action = reader.varint()?;
+        let len = (action >> 2) + 1;
+        match action & 0b11 {
+            // Source read
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + len).ok_or_else(out_of_bounds)?);
+            }
+            // Target read
+            1 => target.extend_from_slice(reader.bytes(len)?),
+            // Source copy
+            2 => {
+                source_offset += relative_offset(reader.varint()?);
+                let start = usize::try_from(source_offset).map_err(|_| out_of_bounds())?;
+                target.extend_from_slice(rom.get(start..start + len).ok_or_else(out_of_bounds)?);
+                source_offset += len as isize;
+            }
+            // Target copy. Copied byte by byte, since the source & destination can overlap.
+            _ => {
+                target_offset += relative_offset(reader.varint()?);
+                for _ in 0..len {
+                    let index = usize::try_from(target_offset).map_err(|_| out_of_bounds())?;
+                    let byte = *target.get(index).ok_or_else(out_of_bounds)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+        if target.len() > target_size {
+            return Err(patch_error("BPS patch writes past the end of the target"));
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(CartridgeError::Patch(format!(
+            "BPS target size mismatch ({} bytes != {target_size} bytes). The patch is corrupt.",
+            target.len()
+        )));
+    }
+    check_target_crc(&target, target_crc, "BPS")?;
+    Ok(target)
+}
+
+/// Decode a BPS relative offset. Bit 0 = sign, the rest = magnitude.
+fn relative_offset(value: usize) -> isize {
+    let magnitude = (value >> 1) as isize;
+    if (value & 1) != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn encode_varint(mut value: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | byte);
+                return bytes;
+            }
+            bytes.push(byte);
+            value -= 1;
+        }
+    }
+
+    /// Append the source, target & patch CRC32s to a UPS or BPS patch.
+    fn add_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32fast::hash(source).to_le_bytes());
+        patch.extend(crc32fast::hash(target).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    fn test_source() -> Vec<u8> {
+        (0..0x40).collect()
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 0x7F, 0x80, 0x1234, 0x10_0000, 0xFFFF_FFFF] {
+            let bytes = encode_varint(value);
+            assert_eq!(PatchReader::new(&bytes, 0).varint().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_ips() {
+        let mut patch = IPS_MAGIC.to_vec();
+        // 2 bytes at 0x0010
+        patch.extend([0x00, 0x00, 0x10, 0x00, 0x02, 0xAA, 0xBB]);
+        // RLE: 3 bytes of 0xCC at 0x003F, extending the ROM
+        patch.extend([0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x03, 0xCC]);
+        patch.extend(IPS_EOF);
+
+        let target = apply_patch(&test_source(), &patch).unwrap();
+        assert_eq!(target.len(), 0x42);
+        assert_eq!(target[0x0F..=0x12], [0x0F, 0xAA, 0xBB, 0x12]);
+        assert_eq!(target[0x3E..], [0x3E, 0xCC, 0xCC, 0xCC]);
+
+        // Truncation
+        patch.extend([0x00, 0x00, 0x20]);
+        assert_eq!(apply_patch(&test_source(), &patch).unwrap().len(), 0x20);
+
+        // Missing EOF
+        let patch = [IPS_MAGIC, &[0x00, 0x00, 0x10, 0x00, 0x02, 0xAA, 0xBB]].concat();
+        assert!(matches!(
+            apply_patch(&test_source(), &patch),
+            Err(CartridgeError::Patch(_))
+        ));
+    }
+
+    #[test]
+    fn test_ups() {
+        let source = test_source();
+        let mut target = source.clone();
+        target[0x02] = 0xFF;
+        target[0x03] = 0xEE;
+        target[0x30] = 0x00;
+        target.push(0x99);
+
+        let mut patch = UPS_MAGIC.to_vec();
+        patch.extend(encode_varint(source.len()));
+        patch.extend(encode_varint(target.len()));
+        patch.extend(encode_varint(0x02));
+        patch.extend([0x02 ^ 0xFF, 0x03 ^ 0xEE, 0x00]);
+        patch.extend(encode_varint(0x30 - 0x05));
+        patch.extend([0x30, 0x00]);
+        patch.extend(encode_varint(0x40 - 0x32));
+        patch.extend([0x99, 0x00]);
+        let patch = add_footer(patch, &source, &target);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+
+        // Wrong ROM
+        let Err(CartridgeError::Patch(msg)) = apply_patch(&target, &patch) else {
+            panic!("Patch applied to the wrong ROM");
+        };
+        assert!(msg.contains("source CRC32 mismatch"));
+
+        // Corrupt patch
+        let mut corrupt = patch.clone();
+        corrupt[8] ^= 0x01;
+        let Err(CartridgeError::Patch(msg)) = apply_patch(&source, &corrupt) else {
+            panic!("Corrupt patch applied");
+        };
+        assert!(msg.contains("patch CRC32 mismatch"));
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = test_source();
+        let mut target = source[..0x10].to_vec();
+        target.extend(&source[0x20..0x28]);
+        target.extend([0xAB, 0xCD, 0xAB, 0xCD, 0xAB, 0xCD, 0xAB]);
+
+        let mut patch = BPS_MAGIC.to_vec();
+        patch.extend(encode_varint(source.len()));
+        patch.extend(encode_varint(target.len()));
+        patch.extend(encode_varint(3));
+        patch.extend(b"abc");
+        // Source read 0x10 bytes
+        patch.extend(encode_varint((0x10 - 1) << 2));
+        // Source copy 8 bytes from 0x20
+        patch.extend(encode_varint(((8 - 1) << 2) | 2));
+        patch.extend(encode_varint(0x20 << 1));
+        // Target read 2 bytes
+        patch.extend(encode_varint(((2 - 1) << 2) | 1));
+        patch.extend([0xAB, 0xCD]);
+        // Target copy 5 bytes from 0x18, overlapping the bytes being written
+        patch.extend(encode_varint(((5 - 1) << 2) | 3));
+        patch.extend(encode_varint(0x18 << 1));
+        let patch = add_footer(patch, &source, &target);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+
+        let Err(CartridgeError::Patch(msg)) = apply_patch(&target, &patch) else {
+            panic!("Patch applied to the wrong ROM");
+        };
+        assert!(msg.contains("source CRC32 mismatch"));
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(matches!(
+            apply_patch(&test_source(), b"NOT A PATCH"),
+            Err(CartridgeError::Patch(_))
+        ));
+    }
+}
@@ -0,0 +1,619 @@
+use std::fmt::Debug;
+
+use super::{
+    load_save_section, Cartridge, CartridgeError, CartridgeHeader, MapperState, BYTES_IN_KIB,
+    RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+/// Width of a captured camera image in pixels.
+pub const CAMERA_WIDTH: usize = 128;
+/// Height of a captured camera image in pixels.
+pub const CAMERA_HEIGHT: usize = 112;
+
+/// RAM bank number which maps the camera registers instead of RAM.
+const REGISTER_BANK: u8 = 0x10;
+/// Number of camera registers.
+const NUM_REGISTERS: usize = 0x36;
+/// Start of the dithering matrix in the camera registers.
+const DITHER_MATRIX_START: usize = 0x06;
+/// Offset of the captured image in RAM bank 0.
+const IMAGE_OFFSET: usize = 0x0100;
+/// Edge enhancement ratios selected by register 4 bits 4-6.
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/// A source of images for the Pocket Camera sensor.
+pub trait ImageSource: Debug {
+    /// Return a [CAMERA_WIDTH] x [CAMERA_HEIGHT] greyscale image, row by row. 0x00 = black,
+    /// 0xFF = white.
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+/// An [ImageSource] which always returns the same image.
+#[derive(Debug, Clone)]
+pub struct StillImage {
+    pixels: Vec<u8>,
+}
+impl StillImage {
+    /// Create a new [StillImage] from greyscale pixel data, row by row. The image is stretched to
+    /// fit the camera sensor.
+    ///
+    /// Panics if the pixel data doesn't match the given dimensions.
+    pub fn new(width: usize, height: usize, pixels: &[u8]) -> Self {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            panic!(
+                "Still image: {} bytes of pixel data don't match a {width}x{height} image",
+                pixels.len()
+            );
+        }
+
+        // Nearest-neighbour scale to the sensor size.
+        let mut scaled = vec![0x00_u8; CAMERA_WIDTH * CAMERA_HEIGHT];
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let src_x = x * width / CAMERA_WIDTH;
+                let src_y = y * height / CAMERA_HEIGHT;
+                scaled[y * CAMERA_WIDTH + x] = pixels[src_y * width + src_x];
+            }
+        }
+        Self { pixels: scaled }
+    }
+
+    /// Create a new [StillImage] of a single shade.
+    pub fn blank(shade: u8) -> Self {
+        Self {
+            pixels: vec![shade; CAMERA_WIDTH * CAMERA_HEIGHT],
+        }
+    }
+}
+impl ImageSource for StillImage {
+    fn capture(&mut self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+/// A Pocket Camera cartridge.
+#[derive(Debug)]
+pub struct CartPocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    /// Only enables RAM writes. RAM can always be read.
+    ram_write_enable: bool,
+    rom_bank_number: u8,
+    /// 0x00-0x0F = RAM bank, 0x10 = camera registers.
+    ram_bank_number: u8,
+    registers: [u8; NUM_REGISTERS],
+    /// T-cycles until the current capture finishes. 0 = not capturing.
+    capture_remaining_cycles: u32,
+    image_source: Box<dyn ImageSource>,
+}
+impl CartPocketCamera {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let mut camera = Self {
+            rom: data,
+            ram: vec![],
+            header,
+            save_dirty: false,
+            ram_write_enable: false,
+            rom_bank_number: 0x01,
+            ram_bank_number: 0x00,
+            registers: [0x00; NUM_REGISTERS],
+            capture_remaining_cycles: 0,
+            image_source: Box::new(StillImage::blank(0x80)),
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        camera.ram = vec![0x00_u8; camera.header.ram_size as usize];
+
+        // Validate proper parameters
+        // Cart features include Pocket Camera
+        if !camera.header.cart_features.pocket_camera {
+            return Err(CartridgeError::UnsupportedMapper(format!("New Pocket Camera: According to the header, this cartridge is not a Pocket Camera. Cart type from header: {}", camera.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if camera.rom().len() != camera.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New Pocket Camera: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", camera.rom().len(), camera.header.rom_size)));
+        }
+
+        // Max ROM size
+        if camera.rom().len() > (1024 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New Pocket Camera: ROM size too big ({} MiB > 1 MiB)",
+                camera.rom().len() / 1024 / (BYTES_IN_KIB as usize)
+            )));
+        }
+        // Max RAM size
+        if camera.ram.len() > (128 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New Pocket Camera: RAM size too big ({} KiB > 128 KiB)",
+                camera.ram.len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(camera)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u8 {
+        // Bank 0 really is bank 0. Bank numbers higher than the number of banks in the cart are
+        // masked.
+        self.rom_bank_number & (self.num_rom_banks() - 1)
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            0xA000..=0xBFFF => {
+                result |= ((self.ram_bank_number & 0b0000_1111) as u32) << 13;
+                result |= (address as u32) & 0b0001_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.capture_remaining_cycles > 0
+    }
+
+    /// Exposure time, in units of 16 µs.
+    fn exposure(&self) -> u16 {
+        ((self.registers[0x02] as u16) << 8) | (self.registers[0x03] as u16)
+    }
+
+    /// Start a capture. Its length depends on the exposure time.
+    fn start_capture(&mut self) {
+        // N flag set = no extra exposure cycles.
+        let n_cycles = if (self.registers[0x01] & 0b1000_0000) != 0 {
+            0
+        } else {
+            2048
+        };
+        self.capture_remaining_cycles = 129_784 + n_cycles + (self.exposure() as u32) * 64;
+    }
+
+    /// Take a picture with the sensor & write the processed image to RAM bank 0.
+    fn finish_capture(&mut self) {
+        self.registers[0x00] &= 0b1111_1110;
+
+        let raw = self.image_source.capture();
+        let sensor = self.sensor_output(&raw);
+        let processed = self.edge_enhance(&sensor);
+
+        // Dither to 2bpp & store as 16x14 tiles.
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let colour = self.dither(x, y, processed[y * CAMERA_WIDTH + x]);
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + (x / 8);
+                let addr = IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                if addr + 1 >= self.ram.len() {
+                    continue;
+                }
+                self.ram[addr] = (self.ram[addr] & !(1 << bit)) | ((colour & 0b01) << bit);
+                self.ram[addr + 1] =
+                    (self.ram[addr + 1] & !(1 << bit)) | (((colour & 0b10) >> 1) << bit);
+            }
+        }
+        self.save_dirty = true;
+    }
+
+    /// Apply the gain, exposure, & inversion settings to the raw image.
+    fn sensor_output(&self, raw: &[u8]) -> Vec<f32> {
+        // Approximation of the sensor's gain curve.
+        let gain = 1.0 + ((self.registers[0x01] & 0b0001_1111) as f32) / 16.0;
+        let exposure = (self.exposure() as f32) / 4096.0;
+        let invert = (self.registers[0x04] & 0b0000_1000) != 0;
+
+        (0..CAMERA_WIDTH * CAMERA_HEIGHT)
+            .map(|i| {
+                let value = (*raw.get(i).unwrap_or(&0x00) as f32) * gain * exposure;
+                if invert {
+                    255.0 - value
+                } else {
+                    value
+                }
+            })
+            .collect()
+    }
+
+    /// Apply the edge enhancement mode. Register 1 bits 5-6 select no enhancement, horizontal,
+    /// vertical, or 2-D.
+    fn edge_enhance(&self, image: &[f32]) -> Vec<f32> {
+        let mode = (self.registers[0x01] >> 5) & 0b11;
+        if mode == 0 {
+            return image.to_vec();
+        }
+        let ratio = EDGE_RATIOS[((self.registers[0x04] >> 4) & 0b111) as usize];
+        let pixel = |x: isize, y: isize| -> f32 {
+            let x = x.clamp(0, CAMERA_WIDTH as isize - 1) as usize;
+            let y = y.clamp(0, CAMERA_HEIGHT as isize - 1) as usize;
+            image[y * CAMERA_WIDTH + x]
+        };
+
+        let mut result = Vec::with_capacity(image.len());
+        for y in 0..CAMERA_HEIGHT as isize {
+            for x in 0..CAMERA_WIDTH as isize {
+                let centre = pixel(x, y);
+                let horizontal = 2.0 * centre - pixel(x - 1, y) - pixel(x + 1, y);
+                let vertical = 2.0 * centre - pixel(x, y - 1) - pixel(x, y + 1);
+                let edge = match mode {
+                    0b01 => horizontal,
+                    0b10 => vertical,
+                    _ => horizontal + vertical,
+                };
+                result.push(centre + ratio * edge);
+            }
+        }
+        result
+    }
+
+    /// Convert a processed pixel to a 2-bit colour using the dithering matrix.
+    fn dither(&self, x: usize, y: usize, value: f32) -> u8 {
+        let base = DITHER_MATRIX_START + ((y % 4) * 4 + (x % 4)) * 3;
+        let thresholds = &self.registers[base..base + 3];
+        if value < thresholds[0] as f32 {
+            3
+        } else if value < thresholds[1] as f32 {
+            2
+        } else if value < thresholds[2] as f32 {
+            1
+        } else {
+            0
+        }
+    }
+
+    #[cfg(test)]
+    fn new_test() -> Self {
+        CartPocketCamera::new(
+            super::RomBuilder::new(0xFC)
+                .rom_size(0x05)
+                .ram_size(0x04)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartPocketCamera {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // Bank 0x10 maps the camera registers.
+            ram_bank: (self.ram_bank_number & 0x10 == 0)
+                .then(|| self.internal_addr(0xA000) / RAM_BANK_SIZE),
+            ram_enabled: self.ram_write_enable,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_save_section(&mut self.ram, data);
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn set_image_source(&mut self, image_source: Box<dyn ImageSource>) {
+        self.image_source = image_source;
+    }
+
+    fn cycle(&mut self, t_cycles: u32) {
+        if !self.is_capturing() {
+            return;
+        }
+        self.capture_remaining_cycles = self.capture_remaining_cycles.saturating_sub(t_cycles);
+        if !self.is_capturing() {
+            self.finish_capture();
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if (self.ram_bank_number & REGISTER_BANK) != 0 {
+            // Only register 0 can be read. The registers repeat every 0x80 bytes.
+            return if (address & 0x007F) == 0x0000 {
+                self.registers[0x00]
+            } else {
+                0x00
+            };
+        }
+        // RAM can't be read while the camera is capturing.
+        if self.is_capturing() {
+            return 0x00;
+        }
+        *self
+            .ram
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM write enable
+            0x0000..=0x1FFF => self.ram_write_enable = (value & 0x0F) == 0x0A,
+            // ROM bank number
+            0x2000..=0x3FFF => self.rom_bank_number = value & 0b0011_1111,
+            // RAM bank number / camera register select
+            0x4000..=0x5FFF => self.ram_bank_number = value & 0b0001_1111,
+            // Unused
+            0x6000..=0x7FFF => {}
+            _ => panic!("Pocket Camera Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if (self.ram_bank_number & REGISTER_BANK) != 0 {
+            let register = (address & 0x007F) as usize;
+            match register {
+                0x00 => {
+                    // Only bits 0-2 are used. A capture can't be cancelled once started.
+                    let was_capturing = self.is_capturing();
+                    self.registers[0x00] = (value & 0b0000_0111) | (was_capturing as u8);
+                    if !was_capturing && (value & 0b0000_0001) != 0 {
+                        self.start_capture();
+                    }
+                }
+                0x01..=0x35 => self.registers[register] = value,
+                _ => {}
+            }
+            return;
+        }
+        if !self.ram_write_enable || self.is_capturing() {
+            return;
+        }
+        let addr = self.internal_addr(address);
+        if addr < self.ram.len() {
+            self.ram[addr] = value;
+            self.save_dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Set up the registers for a capture with no edge enhancement & an evenly-spaced dithering
+    /// matrix.
+    fn setup_capture(camera: &mut CartPocketCamera, edge_mode: u8) {
+        camera.write_rom(0x4000, 0x10);
+        camera.write_ram(0xA001, 0b1000_0000 | (edge_mode << 5));
+        // Exposure 0x1000 = no scaling
+        camera.write_ram(0xA002, 0x10);
+        camera.write_ram(0xA003, 0x00);
+        camera.write_ram(0xA004, 0x20);
+        for i in 0..16 {
+            camera.write_ram(0xA006 + i * 3, 0x40);
+            camera.write_ram(0xA007 + i * 3, 0x80);
+            camera.write_ram(0xA008 + i * 3, 0xC0);
+        }
+    }
+
+    fn capture(camera: &mut CartPocketCamera) {
+        camera.write_ram(0xA000, 0x01);
+        while camera.read_ram(0xA000) & 0x01 != 0 {
+            camera.cycle(4096);
+        }
+        camera.write_rom(0x4000, 0x00);
+    }
+
+    /// Get the colour of a pixel from the captured image in RAM.
+    fn pixel(camera: &CartPocketCamera, x: usize, y: usize) -> u8 {
+        let tile = (y / 8) * 16 + (x / 8);
+        let addr = IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+        let bit = 7 - (x % 8);
+        ((camera.ram[addr] >> bit) & 1) | (((camera.ram[addr + 1] >> bit) & 1) << 1)
+    }
+
+    #[derive(Debug)]
+    struct Gradient;
+    impl ImageSource for Gradient {
+        fn capture(&mut self) -> Vec<u8> {
+            (0..CAMERA_WIDTH * CAMERA_HEIGHT)
+                .map(|i| ((i % CAMERA_WIDTH) * 2) as u8)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0x13)
+            .rom_size(0x05)
+            .ram_size(0x04)
+            .build();
+        assert!(matches!(
+            CartPocketCamera::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let camera = CartPocketCamera::new_test();
+        assert!(camera.header().logo_valid);
+        assert_eq!(camera.num_rom_banks(), 64);
+        assert!(camera.cart_features().pocket_camera);
+        assert_eq!(camera.ram.len(), 0x20000);
+    }
+
+    #[test]
+    fn test_still_image_scaling() {
+        let mut still = StillImage::new(2, 2, &[0x00, 0x55, 0xAA, 0xFF]);
+        let pixels = still.capture();
+        assert_eq!(pixels.len(), CAMERA_WIDTH * CAMERA_HEIGHT);
+        assert_eq!(pixels[0], 0x00);
+        assert_eq!(pixels[CAMERA_WIDTH - 1], 0x55);
+        assert_eq!(pixels[(CAMERA_HEIGHT - 1) * CAMERA_WIDTH], 0xAA);
+        assert_eq!(pixels[CAMERA_WIDTH * CAMERA_HEIGHT - 1], 0xFF);
+    }
+
+    #[test]
+    fn test_ram_banks() {
+        let mut camera = CartPocketCamera::new_test();
+
+        // RAM is readable but not writable without enabling it
+        camera.ram[0x2000 * 15 + 0x0123] = 0x45;
+        camera.write_rom(0x4000, 0x0F);
+        assert_eq!(camera.read_ram(0xA123), 0x45);
+        camera.write_ram(0xA123, 0x67);
+        assert_eq!(camera.read_ram(0xA123), 0x45);
+
+        camera.write_rom(0x0000, 0x0A);
+        for bank_num in 0..16 {
+            camera.write_rom(0x4000, bank_num);
+            camera.write_ram(0xB000, bank_num);
+        }
+        for bank_num in 0..16 {
+            camera.write_rom(0x4000, bank_num);
+            assert_eq!(camera.read_ram(0xB000), bank_num);
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut camera = CartPocketCamera::new_test();
+        camera.write_rom(0x4000, 0x10);
+
+        // Registers can be written without enabling RAM, but only register 0 can be read
+        camera.write_ram(0xA001, 0xAB);
+        assert_eq!(camera.registers[0x01], 0xAB);
+        assert_eq!(camera.read_ram(0xA001), 0x00);
+        camera.write_ram(0xA0B5, 0xCD);
+        assert_eq!(camera.registers[0x35], 0xCD);
+        camera.write_ram(0xA000, 0b1111_0110);
+        assert_eq!(camera.read_ram(0xA000), 0b0000_0110);
+        assert_eq!(camera.read_ram(0xA080), 0b0000_0110);
+    }
+
+    #[test]
+    fn test_capture_timing() {
+        let mut camera = CartPocketCamera::new_test();
+        setup_capture(&mut camera, 0b00);
+        camera.write_ram(0xA000, 0x01);
+        assert_eq!(camera.capture_remaining_cycles, 129_784 + 0x1000 * 64);
+        assert_eq!(camera.read_ram(0xA000), 0x01);
+
+        // Can't cancel a capture
+        camera.write_ram(0xA000, 0x00);
+        assert_eq!(camera.read_ram(0xA000), 0x01);
+
+        // RAM is inaccessible during capture
+        camera.write_rom(0x4000, 0x00);
+        camera.ram[0x0000] = 0x12;
+        assert_eq!(camera.read_ram(0xA000), 0x00);
+
+        camera.cycle(129_784 + 0x1000 * 64 - 1);
+        assert_eq!(camera.read_ram(0xA000), 0x00);
+        camera.cycle(1);
+        assert_eq!(camera.read_ram(0xA000), 0x12);
+        camera.write_rom(0x4000, 0x10);
+        assert_eq!(camera.read_ram(0xA000), 0x00);
+    }
+
+    #[test]
+    fn test_capture_dithering() {
+        let mut camera = CartPocketCamera::new_test();
+        camera.set_image_source(Box::new(Gradient));
+        setup_capture(&mut camera, 0b00);
+        capture(&mut camera);
+
+        // Thresholds at 0x40, 0x80, 0xC0; pixel value = x * 2
+        assert_eq!(pixel(&camera, 0, 0), 3);
+        assert_eq!(pixel(&camera, 31, 5), 3);
+        assert_eq!(pixel(&camera, 32, 50), 2);
+        assert_eq!(pixel(&camera, 64, 100), 1);
+        assert_eq!(pixel(&camera, 96, 111), 0);
+        assert_eq!(pixel(&camera, 127, 111), 0);
+    }
+
+    #[test]
+    fn test_capture_invert() {
+        let mut camera = CartPocketCamera::new_test();
+        camera.set_image_source(Box::new(StillImage::blank(0xFF)));
+        setup_capture(&mut camera, 0b00);
+        camera.write_ram(0xA004, 0b0000_1000);
+        capture(&mut camera);
+        assert!((0..CAMERA_WIDTH).all(|x| pixel(&camera, x, 20) == 3));
+    }
+
+    #[test]
+    fn test_capture_edge_enhancement() {
+        // A vertical line at x = 64
+        let mut line = vec![0x60_u8; CAMERA_WIDTH * CAMERA_HEIGHT];
+        for y in 0..CAMERA_HEIGHT {
+            line[y * CAMERA_WIDTH + 64] = 0xA0;
+        }
+
+        // Horizontal enhancement darkens the neighbours of the line
+        let mut camera = CartPocketCamera::new_test();
+        camera.set_image_source(Box::new(StillImage::new(
+            CAMERA_WIDTH,
+            CAMERA_HEIGHT,
+            &line,
+        )));
+        setup_capture(&mut camera, 0b01);
+        capture(&mut camera);
+        assert_eq!(pixel(&camera, 10, 10), 2);
+        assert_eq!(pixel(&camera, 63, 10), 3);
+        assert_eq!(pixel(&camera, 64, 10), 0);
+        assert_eq!(pixel(&camera, 65, 10), 3);
+
+        // Vertical enhancement doesn't see a vertical line
+        let mut camera = CartPocketCamera::new_test();
+        camera.set_image_source(Box::new(StillImage::new(
+            CAMERA_WIDTH,
+            CAMERA_HEIGHT,
+            &line,
+        )));
+        setup_capture(&mut camera, 0b10);
+        capture(&mut camera);
+        assert_eq!(pixel(&camera, 63, 10), 2);
+        assert_eq!(pixel(&camera, 64, 10), 1);
+        assert_eq!(pixel(&camera, 65, 10), 2);
+    }
+}
@@ -0,0 +1,236 @@
+//! Synthetic ROM images for tests.
+use super::{
+    header::{checksum_fn, global_checksum_fn, rom_size_fn},
+    CgbFlag, Destination, LOGO, ROM_BANK_SIZE,
+};
+
+/// Entry point: NOP, JP 0x0150.
+const ENTRY_POINT: [u8; 4] = [0x00, 0xC3, 0x50, 0x01];
+
+/// Builds ROM images with a valid header for any cartridge type.
+///
+/// The header & global checksums are calculated when the ROM is built.
+///
+/// ```
+/// use rbca_core::{Cpu, RomBuilder};
+///
+/// // MBC1+RAM+BATTERY, 64 KiB ROM, 8 KiB RAM
+/// let rom = RomBuilder::new(0x03)
+///     .rom_size(0x01)
+///     .ram_size(0x02)
+///     .title("EXAMPLE")
+///     // LD A,0x42; JR -2
+///     .code(0, 0x0150, &[0x3E, 0x42, 0x18, 0xFE])
+///     .build();
+/// let cpu = Cpu::new_cart_bytes(rom).unwrap();
+/// assert!(cpu.mmu.cart.header().global_checksum_valid);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RomBuilder {
+    cart_type: u8,
+    rom_size: u8,
+    ram_size: u8,
+    title: String,
+    cgb_flag: CgbFlag,
+    sgb_flag: bool,
+    destination: Destination,
+    old_licensee_code: u8,
+    new_licensee_code: [u8; 2],
+    version_number: u8,
+    /// Data to place in the ROM, by ROM offset.
+    data: Vec<(usize, Vec<u8>)>,
+}
+impl RomBuilder {
+    /// Start building a 32 KiB ROM of the given cartridge type (the raw header value at 0x0147).
+    pub fn new(cart_type: u8) -> Self {
+        Self {
+            cart_type,
+            rom_size: 0x00,
+            ram_size: 0x00,
+            title: String::new(),
+            cgb_flag: CgbFlag::Dmg,
+            sgb_flag: false,
+            destination: Destination::Japan,
+            old_licensee_code: 0x00,
+            new_licensee_code: [0x00; 2],
+            version_number: 0x00,
+            data: vec![],
+        }
+    }
+
+    /// Set the ROM size (the raw header value at 0x0148). The built ROM is this size. Unknown
+    /// values produce a 32 KiB ROM.
+    pub fn rom_size(mut self, value: u8) -> Self {
+        self.rom_size = value;
+        self
+    }
+
+    /// Set the RAM size (the raw header value at 0x0149).
+    pub fn ram_size(mut self, value: u8) -> Self {
+        self.ram_size = value;
+        self
+    }
+
+    /// Set the title. Titles longer than the title area are cut off.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    /// Set the CGB flag. CGB cartridges have 15-character titles.
+    pub fn cgb_flag(mut self, cgb_flag: CgbFlag) -> Self {
+        self.cgb_flag = cgb_flag;
+        self
+    }
+
+    /// Set whether the cartridge supports SGB functions.
+    pub fn sgb_flag(mut self, sgb_flag: bool) -> Self {
+        self.sgb_flag = sgb_flag;
+        self
+    }
+
+    /// Set the destination.
+    pub fn destination(mut self, destination: Destination) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Set the old licensee code.
+    pub fn old_licensee_code(mut self, code: u8) -> Self {
+        self.old_licensee_code = code;
+        self
+    }
+
+    /// Set the two-character new licensee code. Also sets the old licensee code to 0x33 so the
+    /// new code is used.
+    pub fn new_licensee_code(mut self, code: [u8; 2]) -> Self {
+        self.old_licensee_code = 0x33;
+        self.new_licensee_code = code;
+        self
+    }
+
+    /// Set the version number.
+    pub fn version_number(mut self, version_number: u8) -> Self {
+        self.version_number = version_number;
+        self
+    }
+
+    /// Place code at the given address as seen by the CPU when the given ROM bank is mapped in.
+    /// Bank 0 is at 0x0000-0x3FFF & every other bank is at 0x4000-0x7FFF.
+    ///
+    /// Panics when the ROM is built if the code doesn't fit in the ROM.
+    pub fn code(self, bank: usize, address: u16, code: &[u8]) -> Self {
+        self.data(
+            bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE),
+            code,
+        )
+    }
+
+    /// Place raw data at the given ROM offset. Data placed in the header overrides the header
+    /// fields.
+    ///
+    /// Panics when the ROM is built if the data doesn't fit in the ROM.
+    pub fn data(mut self, offset: usize, data: &[u8]) -> Self {
+        self.data.push((offset, data.to_vec()));
+        self
+    }
+
+    /// Build the ROM image.
+    pub fn build(&self) -> Vec<u8> {
+        let size = (rom_size_fn(self.rom_size) as usize).max(2 * ROM_BANK_SIZE);
+        let mut rom = vec![0x00_u8; size];
+
+        rom[0x0100..=0x0103].copy_from_slice(&ENTRY_POINT);
+        rom[0x0104..=0x0133].copy_from_slice(&LOGO);
+        let title_len = self.title.len().min(0x10);
+        rom[0x0134..(0x0134 + title_len)].copy_from_slice(&self.title.as_bytes()[..title_len]);
+        if self.cgb_flag != CgbFlag::Dmg {
+            rom[0x0143] = self.cgb_flag.to_byte();
+        }
+        rom[0x0144..=0x0145].copy_from_slice(&self.new_licensee_code);
+        rom[0x0146] = if self.sgb_flag { 0x03 } else { 0x00 };
+        rom[0x0147] = self.cart_type;
+        rom[0x0148] = self.rom_size;
+        rom[0x0149] = self.ram_size;
+        rom[0x014A] = match self.destination {
+            Destination::Japan => 0x00,
+            Destination::Overseas => 0x01,
+        };
+        rom[0x014B] = self.old_licensee_code;
+        rom[0x014C] = self.version_number;
+
+        for (offset, data) in &self.data {
+            rom[*offset..(offset + data.len())].copy_from_slice(data);
+        }
+
+        rom[0x014D] = checksum_fn(&rom);
+        let global_checksum = global_checksum_fn(&rom, 0);
+        rom[0x014E..=0x014F].copy_from_slice(&global_checksum.to_be_bytes());
+        rom
+    }
+}
+impl Default for RomBuilder {
+    /// A 32 KiB ROM-only cartridge.
+    fn default() -> Self {
+        Self::new(0x00)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::super::{load_cartridge_bytes, CartridgeHeader};
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let rom = RomBuilder::new(0x1B)
+            .rom_size(0x02)
+            .ram_size(0x03)
+            .title("A VERY LONG TITLE")
+            .cgb_flag(CgbFlag::CgbBkwd)
+            .sgb_flag(true)
+            .destination(Destination::Overseas)
+            .new_licensee_code(*b"01")
+            .version_number(0x01)
+            .build();
+        assert_eq!(rom.len(), 0x20000);
+
+        let header = CartridgeHeader::from_data(&rom);
+        assert_eq!(header.title, "A VERY LONG TIT");
+        assert_eq!(header.cgb_flag, CgbFlag::CgbBkwd);
+        assert!(header.sgb_flag);
+        assert!(header.cart_features.mbc5 && header.cart_features.battery);
+        assert_eq!(header.rom_size, 0x20000);
+        assert_eq!(header.ram_size, 0x8000);
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.publisher, Some("Nintendo Research & Development 1"));
+        assert_eq!(header.version_number, 0x01);
+        assert!(header.logo_valid);
+        assert!(header.header_checksum_valid);
+        assert!(header.global_checksum_valid);
+    }
+
+    #[test]
+    fn test_code() {
+        let rom = RomBuilder::new(0x01)
+            .rom_size(0x02)
+            .code(0, 0x0150, &[0x01, 0x02])
+            .code(5, 0x4000, &[0x03])
+            .code(7, 0x7FFF, &[0x04])
+            .build();
+        assert_eq!(rom[0x0150..=0x0151], [0x01, 0x02]);
+        assert_eq!(rom[0x14000], 0x03);
+        assert_eq!(rom[0x1FFFF], 0x04);
+
+        let (cart, _) = load_cartridge_bytes(rom).unwrap();
+        assert!(cart.header().global_checksum_valid);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_code_too_big() {
+        RomBuilder::default().code(2, 0x4000, &[0x00]).build();
+    }
+}
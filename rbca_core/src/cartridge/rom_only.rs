@@ -1,17 +1,29 @@
-use super::{CartFeatures, Cartridge};
+use super::{load_save_section, Cartridge, CartridgeHeader};
 
-/// A ROM-only cartridge.
+/// A ROM-only cartridge, optionally with unbanked RAM.
 #[derive(Debug)]
 pub struct CartRomOnly {
     rom: Vec<u8>,
-    cart_features: CartFeatures,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
 }
 impl CartRomOnly {
-    pub fn new(data: Vec<u8>, cart_features: CartFeatures) -> Self {
-        Self {
+    pub fn new(data: Vec<u8>, header: CartridgeHeader) -> Self {
+        let mut rom_only = Self {
             rom: data,
-            cart_features,
+            ram: vec![],
+            header,
+            save_dirty: false,
+        };
+
+        // Allocate RAM based on RAM size denoted in cartridge header
+        if rom_only.header.cart_features.ram {
+            rom_only.ram = vec![0x00_u8; rom_only.header.ram_size as usize];
         }
+
+        rom_only
     }
 }
 impl Cartridge for CartRomOnly {
@@ -19,7 +31,72 @@ impl Cartridge for CartRomOnly {
         &self.rom
     }
 
-    fn cart_features(&self) -> &CartFeatures {
-        &self.cart_features
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if self.header.cart_features.battery {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.header.cart_features.battery {
+            load_save_section(&mut self.ram, data);
+        }
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        *self
+            .ram
+            .get((address as usize) - 0xA000)
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        let addr = (address as usize) - 0xA000;
+        if addr < self.ram.len() {
+            self.ram[addr] = value;
+            self.save_dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_no_ram() {
+        let data = super::super::RomBuilder::new(0x00).build();
+        let header = CartridgeHeader::from_data(&data);
+        let mut rom_only = CartRomOnly::new(data, header);
+        rom_only.write_ram(0xA000, 0x12);
+        assert_eq!(rom_only.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_ram() {
+        let data = super::super::RomBuilder::new(0x09).ram_size(0x02).build();
+        let header = CartridgeHeader::from_data(&data);
+        let mut rom_only = CartRomOnly::new(data, header);
+        assert_eq!(rom_only.ram.len(), 0x2000);
+        rom_only.write_ram(0xA000, 0x12);
+        rom_only.write_ram(0xBFFF, 0x34);
+        assert_eq!(rom_only.read_ram(0xA000), 0x12);
+        assert_eq!(rom_only.read_ram(0xBFFF), 0x34);
     }
 }
@@ -0,0 +1,678 @@
+use std::time::{Duration, SystemTime};
+
+use super::{
+    from_unix_timestamp, load_save_section, to_unix_timestamp, Cartridge, CartridgeError,
+    CartridgeHeader, MapperState, BYTES_IN_KIB, ROM_BANK_SIZE,
+};
+
+/// Size of the internal RAM.
+const TAMA5_RAM_SIZE: usize = 32;
+/// Size of the clock data appended to save files.
+const RTC_SAVE_SIZE: usize = 19;
+/// Number of writable TAMA5 registers.
+const NUM_REGISTERS: usize = 8;
+
+/// TAMA5 register: ROM bank number bits 0-3.
+const REG_BANK_LO: u8 = 0x0;
+/// TAMA5 register: ROM bank number bit 4.
+const REG_BANK_HI: u8 = 0x1;
+/// TAMA5 register: data to write, bits 0-3.
+const REG_WRITE_LO: u8 = 0x4;
+/// TAMA5 register: data to write, bits 4-7.
+const REG_WRITE_HI: u8 = 0x5;
+/// TAMA5 register: bit 0 = address bit 4, bits 1-3 = command.
+const REG_ADDR_HI: u8 = 0x6;
+/// TAMA5 register: address bits 0-3. Writing this register executes the command.
+const REG_ADDR_LO: u8 = 0x7;
+/// TAMA5 register: ready flag.
+const REG_ACTIVE: u8 = 0xA;
+/// TAMA5 register: result of the last read command, bits 0-3.
+const REG_READ_LO: u8 = 0xC;
+/// TAMA5 register: result of the last read command, bits 4-7.
+const REG_READ_HI: u8 = 0xD;
+
+/// A Bandai TAMA5 cartridge.
+///
+/// All access goes through two registers. Writing to 0xA001 selects a TAMA5 register, & reading
+/// or writing 0xA000 accesses its lower nibble. The internal RAM & the clock are reached by
+/// setting up the data & address registers, then writing the low address nibble to run a command.
+#[derive(Debug)]
+pub struct CartTama5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    header: CartridgeHeader,
+    /// Set when saved memory is written. Cleared by [Cartridge::take_save_dirty].
+    save_dirty: bool,
+    /// The register selected through 0xA001.
+    selected_register: u8,
+    registers: [u8; NUM_REGISTERS],
+    /// The result of the last read command.
+    read_result: u8,
+    rtc: Rtc,
+}
+impl CartTama5 {
+    pub fn new(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_data(&data);
+
+        let tama5 = Self {
+            rom: data,
+            ram: vec![0x00_u8; TAMA5_RAM_SIZE],
+            header,
+            save_dirty: false,
+            selected_register: 0x00,
+            registers: [0x00; NUM_REGISTERS],
+            read_result: 0x00,
+            rtc: Rtc::new(),
+        };
+
+        // Validate proper parameters
+        // Cart features include TAMA5
+        if !tama5.header.cart_features.bandai_tama5 {
+            return Err(CartridgeError::UnsupportedMapper(format!("New TAMA5: According to the header, this cartridge is not a TAMA5. Cart type from header: {}", tama5.header.cart_features)));
+        }
+
+        // Allocated ROM size matches ROM size in header
+        if tama5.rom().len() != tama5.header.rom_size as usize {
+            return Err(CartridgeError::SizeMismatch(format!("New TAMA5: ROM size in header does not match provided data ({} bytes of ROM data != {} bytes in header)", tama5.rom().len(), tama5.header.rom_size)));
+        }
+
+        // Max ROM size
+        if tama5.rom().len() > (512 * BYTES_IN_KIB) as usize {
+            return Err(CartridgeError::SizeMismatch(format!(
+                "New TAMA5: ROM size too big ({} KiB > 512 KiB)",
+                tama5.rom().len() / (BYTES_IN_KIB as usize)
+            )));
+        }
+
+        Ok(tama5)
+    }
+
+    fn num_rom_banks(&self) -> u8 {
+        (self.header.rom_size / 0x4000).try_into().unwrap()
+    }
+
+    fn get_rom_bank_number(&self) -> u8 {
+        let rbn = ((self.registers[REG_BANK_HI as usize] & 0b0001) << 4)
+            | self.registers[REG_BANK_LO as usize];
+        // Bank numbers higher than the number of banks in the cart are masked.
+        rbn & (self.num_rom_banks() - 1)
+    }
+
+    fn internal_addr(&self, address: u16) -> usize {
+        let mut result: u32 = 0x0000_0000;
+        match address {
+            0x0000..=0x3FFF => {
+                result |= address as u32;
+            }
+            0x4000..=0x7FFF => {
+                result |= (self.get_rom_bank_number() as u32) << 14;
+                result |= (address as u32) & 0b0011_1111_1111_1111;
+            }
+            _ => panic!(
+                "Tried to get internal cart address of address {:#06X}.",
+                address
+            ),
+        };
+        result as usize
+    }
+
+    /// Run the command set up in the address & data registers.
+    ///
+    /// 0x0 = write RAM, 0x1 = read RAM, 0x2 = write clock, 0x3 = read clock, 0x4 = write alarm,
+    /// 0x5 = read alarm.
+    fn execute(&mut self) {
+        let addr_hi = self.registers[REG_ADDR_HI as usize];
+        let address = (((addr_hi & 0b0001) << 4) | self.registers[REG_ADDR_LO as usize]) as usize;
+        let data =
+            (self.registers[REG_WRITE_HI as usize] << 4) | self.registers[REG_WRITE_LO as usize];
+
+        match addr_hi >> 1 {
+            0x0 => {
+                self.ram[address] = data;
+                self.save_dirty = true;
+            }
+            0x1 => self.read_result = self.ram[address],
+            0x2 => {
+                self.rtc.update();
+                self.rtc.write_clock(address & 0x0F, data & 0x0F);
+                self.save_dirty = true;
+            }
+            0x3 => {
+                self.rtc.update();
+                self.read_result = self.rtc.read_clock(address & 0x0F);
+            }
+            0x4 => {
+                self.rtc.update();
+                self.rtc.write_alarm(address & 0x0F, data & 0x0F);
+                self.save_dirty = true;
+            }
+            0x5 => {
+                self.rtc.update();
+                self.read_result = self.rtc.read_alarm(address & 0x0F);
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg(test)]
+    fn new_test(rom_size_value: u8) -> Self {
+        CartTama5::new(
+            super::RomBuilder::new(0xFD)
+                .rom_size(rom_size_value)
+                .build(),
+        )
+        .unwrap()
+    }
+}
+impl Cartridge for CartTama5 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn mapper_state(&self) -> MapperState {
+        MapperState {
+            rom_bank_0000: 0,
+            rom_bank_4000: self.internal_addr(0x4000) / ROM_BANK_SIZE,
+            // RAM is only accessed through the registers.
+            ram_bank: None,
+            ram_enabled: false,
+            mode: 0,
+            rtc_latch: None,
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        let mut data = self.ram.clone();
+        data.extend(self.rtc.save_bytes());
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let rest = load_save_section(&mut self.ram, data);
+        self.rtc.load_bytes(rest);
+    }
+
+    fn take_save_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        *self
+            .rom
+            .get(self.internal_addr(address))
+            .unwrap_or(&0xFF_u8)
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        // Only the data port can be read.
+        if (address & 0b0001) != 0 {
+            return 0xFF_u8;
+        }
+        // The upper nibble always reads as 0xF.
+        match self.selected_register {
+            REG_ACTIVE => 0xF1,
+            REG_READ_LO => 0xF0 | (self.read_result & 0x0F),
+            REG_READ_HI => 0xF0 | (self.read_result >> 4),
+            _ => 0xFF_u8,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, _value: u8) {
+        match address {
+            // No registers here.
+            0x0000..=0x7FFF => {}
+            _ => panic!("TAMA5 Cart: Cannot write to {:#06X}", address),
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if (address & 0b0001) != 0 {
+            self.selected_register = value & 0x0F;
+            return;
+        }
+
+        let register = self.selected_register;
+        if (register as usize) >= NUM_REGISTERS {
+            return;
+        }
+        self.registers[register as usize] = value & 0x0F;
+        if register == REG_ADDR_LO {
+            self.execute();
+        }
+    }
+}
+
+/// The TAMA5's real-time clock. Keeps time using the host system clock.
+///
+/// The clock registers are nibbles in BCD: 0x0-0x1 = seconds, 0x2-0x3 = minutes, 0x4-0x5 =
+/// hours, 0x6 = day of the week, 0x7-0x8 = day of the month, 0x9-0xA = month, 0xB-0xC = year.
+///
+/// The alarm registers are 0x0 = flags (bit 0 = enable, bit 1 = alarm went off, write 0 to
+/// clear), 0x2-0x3 = minutes, & 0x4-0x5 = hours, also in BCD.
+#[derive(Debug, Clone)]
+struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 0-6.
+    weekday: u8,
+    /// 1-31.
+    day: u8,
+    /// 1-12.
+    month: u8,
+    /// 0-99, years since 2000.
+    year: u8,
+    alarm_minutes: u8,
+    alarm_hours: u8,
+    alarm_enable: bool,
+    alarm_fired: bool,
+    /// The host time up to which the clock has been advanced.
+    last_update: SystemTime,
+}
+impl Rtc {
+    fn new() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            weekday: 0,
+            day: 1,
+            month: 1,
+            year: 0,
+            alarm_minutes: 0,
+            alarm_hours: 0,
+            alarm_enable: false,
+            alarm_fired: false,
+            last_update: SystemTime::now(),
+        }
+    }
+
+    /// Get the clock data appended to save files: the Unix time the clock was current at as a
+    /// 64-bit little-endian value, followed by the seconds, minutes, hours, weekday, day, month,
+    /// year, alarm minutes, alarm hours, alarm enable & alarm fired bytes.
+    fn save_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RTC_SAVE_SIZE);
+        bytes.extend(to_unix_timestamp(self.last_update).to_le_bytes());
+        bytes.extend([
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.weekday,
+            self.day,
+            self.month,
+            self.year,
+            self.alarm_minutes,
+            self.alarm_hours,
+            self.alarm_enable as u8,
+            self.alarm_fired as u8,
+        ]);
+        bytes
+    }
+
+    /// Restore the clock from save file clock data, then catch up to the current time.
+    fn load_bytes(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE {
+            return;
+        }
+        let timestamp = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        self.seconds = data[8] % 60;
+        self.minutes = data[9] % 60;
+        self.hours = data[10] % 24;
+        self.weekday = data[11] % 7;
+        self.day = data[12].clamp(1, 31);
+        self.month = data[13].clamp(1, 12);
+        self.year = data[14] % 100;
+        self.alarm_minutes = data[15] % 60;
+        self.alarm_hours = data[16] % 24;
+        self.alarm_enable = data[17] != 0;
+        self.alarm_fired = data[18] != 0;
+        self.last_update = from_unix_timestamp(timestamp);
+        self.update();
+    }
+
+    /// Advance the clock to the current host time.
+    fn update(&mut self) {
+        let now = SystemTime::now();
+        // If the host clock went backwards, don't move the RTC.
+        let elapsed = now.duration_since(self.last_update).unwrap_or_default();
+        let elapsed_secs = elapsed.as_secs();
+        self.last_update += Duration::from_secs(elapsed_secs);
+        self.advance(elapsed_secs);
+    }
+
+    /// Advance the clock by the given number of seconds.
+    fn advance(&mut self, secs: u64) {
+        if secs == 0 {
+            return;
+        }
+
+        // Check whether the alarm time is passed. Any full day passes it.
+        let alarm_minute_of_day = (self.alarm_hours as u64) * 60 + (self.alarm_minutes as u64);
+        let start_secs_of_day =
+            (self.hours as u64) * 3600 + (self.minutes as u64) * 60 + (self.seconds as u64);
+        let secs_until_alarm = ((alarm_minute_of_day * 60) + 86400 - start_secs_of_day) % 86400;
+        let secs_until_alarm = if secs_until_alarm == 0 {
+            86400
+        } else {
+            secs_until_alarm
+        };
+        if self.alarm_enable && secs >= secs_until_alarm {
+            self.alarm_fired = true;
+        }
+
+        let total_secs = start_secs_of_day + secs;
+        self.seconds = (total_secs % 60) as u8;
+        self.minutes = ((total_secs / 60) % 60) as u8;
+        self.hours = ((total_secs / 3600) % 24) as u8;
+        for _ in 0..(total_secs / 86400) {
+            self.next_day();
+        }
+    }
+
+    fn next_day(&mut self) {
+        self.weekday = (self.weekday + 1) % 7;
+        self.day += 1;
+        if self.day > self.days_in_month() {
+            self.day = 1;
+            self.month += 1;
+            if self.month > 12 {
+                self.month = 1;
+                self.year = (self.year + 1) % 100;
+            }
+        }
+    }
+
+    fn days_in_month(&self) -> u8 {
+        match self.month {
+            2 if self.year.is_multiple_of(4) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    fn read_clock(&self, register: usize) -> u8 {
+        match register {
+            0x0 => self.seconds % 10,
+            0x1 => self.seconds / 10,
+            0x2 => self.minutes % 10,
+            0x3 => self.minutes / 10,
+            0x4 => self.hours % 10,
+            0x5 => self.hours / 10,
+            0x6 => self.weekday,
+            0x7 => self.day % 10,
+            0x8 => self.day / 10,
+            0x9 => self.month % 10,
+            0xA => self.month / 10,
+            0xB => self.year % 10,
+            0xC => self.year / 10,
+            _ => 0x0,
+        }
+    }
+
+    fn write_clock(&mut self, register: usize, value: u8) {
+        match register {
+            0x0 => self.seconds = set_ones(self.seconds, value).min(59),
+            0x1 => self.seconds = set_tens(self.seconds, value).min(59),
+            0x2 => self.minutes = set_ones(self.minutes, value).min(59),
+            0x3 => self.minutes = set_tens(self.minutes, value).min(59),
+            0x4 => self.hours = set_ones(self.hours, value).min(23),
+            0x5 => self.hours = set_tens(self.hours, value).min(23),
+            0x6 => self.weekday = value % 7,
+            0x7 => self.day = set_ones(self.day, value).clamp(1, 31),
+            0x8 => self.day = set_tens(self.day, value).clamp(1, 31),
+            0x9 => self.month = set_ones(self.month, value).clamp(1, 12),
+            0xA => self.month = set_tens(self.month, value).clamp(1, 12),
+            0xB => self.year = set_ones(self.year, value),
+            0xC => self.year = set_tens(self.year, value),
+            _ => {}
+        }
+    }
+
+    fn read_alarm(&self, register: usize) -> u8 {
+        match register {
+            0x0 => (self.alarm_enable as u8) | ((self.alarm_fired as u8) << 1),
+            0x2 => self.alarm_minutes % 10,
+            0x3 => self.alarm_minutes / 10,
+            0x4 => self.alarm_hours % 10,
+            0x5 => self.alarm_hours / 10,
+            _ => 0x0,
+        }
+    }
+
+    fn write_alarm(&mut self, register: usize, value: u8) {
+        match register {
+            0x0 => {
+                self.alarm_enable = (value & 0b0001) != 0;
+                self.alarm_fired &= (value & 0b0010) != 0;
+            }
+            0x2 => self.alarm_minutes = set_ones(self.alarm_minutes, value).min(59),
+            0x3 => self.alarm_minutes = set_tens(self.alarm_minutes, value).min(59),
+            0x4 => self.alarm_hours = set_ones(self.alarm_hours, value).min(23),
+            0x5 => self.alarm_hours = set_tens(self.alarm_hours, value).min(23),
+            _ => {}
+        }
+    }
+}
+
+/// Replace the ones digit of a number.
+fn set_ones(number: u8, digit: u8) -> u8 {
+    (number / 10) * 10 + digit.min(9)
+}
+
+/// Replace the tens digit of a number.
+fn set_tens(number: u8, digit: u8) -> u8 {
+    digit.min(9) * 10 + number % 10
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn write_register(tama5: &mut CartTama5, register: u8, value: u8) {
+        tama5.write_ram(0xA001, register);
+        tama5.write_ram(0xA000, value);
+    }
+
+    fn run_command(tama5: &mut CartTama5, command: u8, address: u8, data: u8) {
+        write_register(tama5, REG_WRITE_LO, data & 0x0F);
+        write_register(tama5, REG_WRITE_HI, data >> 4);
+        write_register(tama5, REG_ADDR_HI, (command << 1) | (address >> 4));
+        write_register(tama5, REG_ADDR_LO, address & 0x0F);
+    }
+
+    fn read_result(tama5: &mut CartTama5) -> u8 {
+        tama5.write_ram(0xA001, REG_READ_LO);
+        let lo = tama5.read_ram(0xA000);
+        tama5.write_ram(0xA001, REG_READ_HI);
+        let hi = tama5.read_ram(0xA000);
+        assert_eq!(lo & 0xF0, 0xF0);
+        assert_eq!(hi & 0xF0, 0xF0);
+        ((hi & 0x0F) << 4) | (lo & 0x0F)
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let data = super::super::RomBuilder::new(0xFC).rom_size(0x04).build();
+        assert!(matches!(
+            CartTama5::new(data),
+            Err(CartridgeError::UnsupportedMapper(_))
+        ));
+    }
+
+    #[test]
+    fn test_new() {
+        let tama5 = CartTama5::new_test(0x04);
+        assert!(tama5.header().logo_valid);
+        assert_eq!(tama5.num_rom_banks(), 32);
+        assert!(tama5.cart_features().bandai_tama5);
+    }
+
+    #[test]
+    fn test_ready() {
+        let mut tama5 = CartTama5::new_test(0x04);
+        tama5.write_ram(0xA001, REG_ACTIVE);
+        assert_eq!(tama5.read_ram(0xA000), 0xF1);
+        assert_eq!(tama5.read_ram(0xA001), 0xFF);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut tama5 = CartTama5::new_test(0x04);
+        for bank_num in 0..32 {
+            tama5.rom[(bank_num * 0x4000) + 0x1234] = bank_num as u8;
+        }
+        for bank_num in 0..32 {
+            write_register(&mut tama5, REG_BANK_LO, bank_num & 0x0F);
+            write_register(&mut tama5, REG_BANK_HI, bank_num >> 4);
+            assert_eq!(tama5.read_rom(0x5234), bank_num);
+            assert_eq!(tama5.read_rom(0x1234), 0x00);
+        }
+
+        // Writes to the ROM area do nothing
+        tama5.write_rom(0x2000, 0x01);
+        assert_eq!(tama5.read_rom(0x5234), 31);
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut tama5 = CartTama5::new_test(0x04);
+        for address in 0..32 {
+            run_command(&mut tama5, 0x0, address, 0xA0 | address);
+        }
+        assert_eq!(tama5.ram[0x1F], 0xBF);
+        for address in 0..32 {
+            run_command(&mut tama5, 0x1, address, 0x00);
+            assert_eq!(read_result(&mut tama5), 0xA0 | address);
+        }
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut tama5 = CartTama5::new_test(0x04);
+
+        // 2024-02-28 23:59:58
+        for (register, value) in [
+            (0x0, 8),
+            (0x1, 5),
+            (0x2, 9),
+            (0x3, 5),
+            (0x4, 3),
+            (0x5, 2),
+            (0x6, 3),
+            (0x7, 8),
+            (0x8, 2),
+            (0x9, 2),
+            (0xA, 0),
+            (0xB, 4),
+            (0xC, 2),
+        ] {
+            run_command(&mut tama5, 0x2, register, value);
+        }
+        assert_eq!(tama5.rtc.hours, 23);
+        assert_eq!(tama5.rtc.year, 24);
+
+        // Leap day
+        tama5.rtc.advance(2);
+        let read_clock = |tama5: &mut CartTama5, register: u8| {
+            run_command(tama5, 0x3, register, 0x00);
+            read_result(tama5)
+        };
+        assert_eq!(read_clock(&mut tama5, 0x0), 0);
+        assert_eq!(read_clock(&mut tama5, 0x4), 0);
+        assert_eq!(read_clock(&mut tama5, 0x6), 4);
+        assert_eq!(read_clock(&mut tama5, 0x7), 9);
+        assert_eq!(read_clock(&mut tama5, 0x8), 2);
+        assert_eq!(read_clock(&mut tama5, 0x9), 2);
+
+        // 2 days later
+        tama5.rtc.advance(2 * 86400 + 3661);
+        assert_eq!(
+            (
+                tama5.rtc.day,
+                tama5.rtc.month,
+                tama5.rtc.hours,
+                tama5.rtc.minutes,
+                tama5.rtc.seconds
+            ),
+            (2, 3, 1, 1, 1)
+        );
+        assert_eq!(tama5.rtc.weekday, 6);
+
+        // Year wraps after 99
+        tama5.rtc.year = 99;
+        tama5.rtc.month = 12;
+        tama5.rtc.day = 31;
+        tama5.rtc.advance(86400);
+        assert_eq!((tama5.rtc.year, tama5.rtc.month, tama5.rtc.day), (0, 1, 1));
+    }
+
+    #[test]
+    fn test_alarm() {
+        let mut tama5 = CartTama5::new_test(0x04);
+
+        // Alarm at 07:30
+        run_command(&mut tama5, 0x4, 0x2, 0);
+        run_command(&mut tama5, 0x4, 0x3, 3);
+        run_command(&mut tama5, 0x4, 0x4, 7);
+        run_command(&mut tama5, 0x4, 0x0, 0b0001);
+        assert_eq!((tama5.rtc.alarm_hours, tama5.rtc.alarm_minutes), (7, 30));
+
+        tama5.rtc.advance(7 * 3600 + 29 * 60 + 59);
+        run_command(&mut tama5, 0x5, 0x0, 0x00);
+        assert_eq!(read_result(&mut tama5), 0b0001);
+
+        tama5.rtc.advance(1);
+        run_command(&mut tama5, 0x5, 0x0, 0x00);
+        assert_eq!(read_result(&mut tama5), 0b0011);
+
+        // Clear the flag
+        run_command(&mut tama5, 0x4, 0x0, 0b0001);
+        run_command(&mut tama5, 0x5, 0x0, 0x00);
+        assert_eq!(read_result(&mut tama5), 0b0001);
+
+        // A full day always passes the alarm
+        tama5.rtc.advance(86400);
+        assert!(tama5.rtc.alarm_fired);
+
+        // Disabled alarm doesn't go off
+        run_command(&mut tama5, 0x4, 0x0, 0b0000);
+        tama5.rtc.advance(86400);
+        assert!(!tama5.rtc.alarm_fired);
+    }
+
+    #[test]
+    fn test_save_data() {
+        let mut tama5 = CartTama5::new_test(0x04);
+        tama5.ram[0x1F] = 0x0A;
+        tama5.rtc.hours = 12;
+        tama5.rtc.day = 31;
+        tama5.rtc.month = 12;
+        tama5.rtc.year = 99;
+        tama5.rtc.alarm_minutes = 30;
+        tama5.rtc.alarm_enable = true;
+
+        let data = tama5.save_data().unwrap();
+        assert_eq!(data.len(), TAMA5_RAM_SIZE + RTC_SAVE_SIZE);
+
+        let mut loaded = CartTama5::new_test(0x04);
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.ram[0x1F], 0x0A);
+        assert_eq!(loaded.rtc.hours, 12);
+        assert_eq!(loaded.rtc.day, 31);
+        assert_eq!(loaded.rtc.month, 12);
+        assert_eq!(loaded.rtc.year, 99);
+        assert_eq!(loaded.rtc.alarm_minutes, 30);
+        assert!(loaded.rtc.alarm_enable);
+    }
+}
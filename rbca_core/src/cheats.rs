@@ -0,0 +1,315 @@
+//! Game Genie & GameShark cheats.
+use std::{fmt::Display, str::FromStr};
+
+/// A single cheat code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Cheat {
+    /// Replaces the byte read from ROM at the given address. If there is a compare byte, the
+    /// replacement only happens when the original byte matches it, so only the intended ROM bank
+    /// is affected.
+    GameGenie {
+        /// ROM address to patch.
+        address: u16,
+        /// Replacement value.
+        value: u8,
+        /// Value the original byte must have for the patch to apply.
+        compare: Option<u8>,
+    },
+    /// Writes the given value to the given RAM address every frame.
+    GameShark {
+        /// The code type. Selects the RAM bank: 0x00 & 0x01 write to whatever is mapped in,
+        /// 0x80-0x87 only write to external RAM bank 0-7 & 0x90-0x97 only write to WRAM bank 0-7.
+        code_type: u8,
+        /// RAM address to write to.
+        address: u16,
+        /// Value to write.
+        value: u8,
+    },
+}
+impl FromStr for Cheat {
+    type Err = CheatError;
+
+    /// Parse a Game Genie code (ABC-DEF or ABC-DEF-GHI) or a GameShark code (TTVVAAAA).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim();
+        let err = || CheatError(code.to_owned());
+
+        let digits: Vec<u8> = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(err)?;
+        let byte = |i: usize| (digits[i] << 4) | digits[i + 1];
+
+        match (code.contains('-'), digits.len()) {
+            // Game Genie: VV AAA(A^F) [C?C]
+            (_, 6) | (_, 9) => {
+                let value = byte(0);
+                let address = (((digits[5] ^ 0xF) as u16) << 12)
+                    | ((digits[2] as u16) << 8)
+                    | ((digits[3] as u16) << 4)
+                    | (digits[4] as u16);
+                if address >= 0x8000 {
+                    return Err(err());
+                }
+                // The 8th digit is unused.
+                let compare = (digits.len() == 9)
+                    .then(|| ((digits[6] << 4) | digits[8]).rotate_right(2) ^ 0xBA);
+                Ok(Self::GameGenie {
+                    address,
+                    value,
+                    compare,
+                })
+            }
+            // GameShark: TT VV AAAA, address little-endian
+            (false, 8) => {
+                let code_type = byte(0);
+                if !matches!(code_type, 0x00 | 0x01 | 0x80..=0x87 | 0x90..=0x97) {
+                    return Err(err());
+                }
+                Ok(Self::GameShark {
+                    code_type,
+                    value: byte(2),
+                    address: u16::from_le_bytes([byte(4), byte(6)]),
+                })
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+/// Error returned when a cheat code can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatError(
+    /// The invalid code.
+    pub String,
+);
+impl Display for CheatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid cheat code: \"{}\"", self.0)
+    }
+}
+impl std::error::Error for CheatError {}
+
+/// A cheat code & whether it's turned on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatEntry {
+    /// The code as entered.
+    pub code: String,
+    /// The parsed code.
+    pub cheat: Cheat,
+    /// Whether the cheat is applied.
+    pub enabled: bool,
+}
+
+/// The list of cheats applied by the [Mmu](crate::Mmu).
+#[derive(Debug, Default, Clone)]
+pub struct Cheats {
+    entries: Vec<CheatEntry>,
+}
+impl Cheats {
+    /// Create an empty list of cheats.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse & add a cheat code. New cheats are turned on.
+    pub fn add(&mut self, code: &str) -> Result<(), CheatError> {
+        let cheat = code.parse()?;
+        self.entries.push(CheatEntry {
+            code: code.trim().to_owned(),
+            cheat,
+            enabled: true,
+        });
+        Ok(())
+    }
+
+    /// Remove all cheats.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Number of cheats.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no cheats.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the cheats.
+    pub fn iter(&self) -> impl Iterator<Item = &CheatEntry> {
+        self.entries.iter()
+    }
+
+    /// Turn the cheat at the given index on or off.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.enabled = enabled;
+        }
+    }
+
+    /// Toggle the cheat at the given index. Return its new state, or None if there is no such
+    /// cheat.
+    pub fn toggle(&mut self, index: usize) -> Option<bool> {
+        let entry = self.entries.get_mut(index)?;
+        entry.enabled = !entry.enabled;
+        Some(entry.enabled)
+    }
+
+    /// Apply the enabled Game Genie codes to a byte read from ROM.
+    pub(crate) fn patch_rom(&self, address: u16, rom_value: u8) -> u8 {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .find_map(|entry| match entry.cheat {
+                Cheat::GameGenie {
+                    address: cheat_address,
+                    value,
+                    compare,
+                } if cheat_address == address
+                    && compare.is_none_or(|compare| compare == rom_value) =>
+                {
+                    Some(value)
+                }
+                _ => None,
+            })
+            .unwrap_or(rom_value)
+    }
+
+    /// Get the RAM writes of the enabled GameShark codes. Codes for a RAM bank other than the
+    /// given mapped external RAM bank (0xA000-0xBFFF) & WRAM bank (0xD000-0xDFFF) are skipped.
+    pub(crate) fn gameshark_writes(
+        &self,
+        ram_bank: Option<usize>,
+        wram_bank: usize,
+    ) -> Vec<(u16, u8)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .filter_map(|entry| match entry.cheat {
+                Cheat::GameShark {
+                    code_type,
+                    address,
+                    value,
+                } => {
+                    let bank = (code_type & 0x0F) as usize;
+                    let bank_mapped = match (code_type & 0xF0, address) {
+                        (0x80, 0xA000..=0xBFFF) => ram_bank == Some(bank),
+                        // WRAM bank 0 can't be mapped to 0xD000, so it selects bank 1.
+                        (0x90, 0xD000..=0xDFFF) => bank.max(1) == wram_bank,
+                        _ => true,
+                    };
+                    bank_mapped.then_some((address, value))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_game_genie() {
+        assert_eq!(
+            "00A-17B-C49".parse(),
+            Ok(Cheat::GameGenie {
+                address: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8),
+            })
+        );
+        assert_eq!(
+            "3EF-0BF".parse(),
+            Ok(Cheat::GameGenie {
+                address: 0x0F0B,
+                value: 0x3E,
+                compare: None,
+            })
+        );
+        // Lowercase & surrounding whitespace
+        assert_eq!(" 3ef-0bf ".parse::<Cheat>(), "3EF-0BF".parse::<Cheat>());
+
+        // Not in ROM
+        assert!("00A-170-C49".parse::<Cheat>().is_err());
+        assert!("00A-17B-C4".parse::<Cheat>().is_err());
+        assert!("00G-17B-C49".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn test_parse_gameshark() {
+        assert_eq!(
+            "010138CD".parse(),
+            Ok(Cheat::GameShark {
+                code_type: 0x01,
+                value: 0x01,
+                address: 0xCD38,
+            })
+        );
+        assert_eq!(
+            "870138CD".parse(),
+            Ok(Cheat::GameShark {
+                code_type: 0x87,
+                value: 0x01,
+                address: 0xCD38,
+            })
+        );
+        assert!("0A0138CD".parse::<Cheat>().is_err());
+        assert!("880138CD".parse::<Cheat>().is_err());
+        assert!("980138CD".parse::<Cheat>().is_err());
+        assert!("01-0138CD".parse::<Cheat>().is_err());
+        assert!("010138C".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn test_patch_rom() {
+        let mut cheats = Cheats::new();
+        cheats.add("00A-17B-C49").unwrap();
+        cheats.add("3EF-0BF").unwrap();
+        cheats.add("01FF00C0").unwrap();
+
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0x00);
+        // Compare byte doesn't match: different bank
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC9), 0xC9);
+        assert_eq!(cheats.patch_rom(0x0F0B, 0x12), 0x3E);
+        assert_eq!(cheats.patch_rom(0x0F0C, 0x12), 0x12);
+
+        assert_eq!(cheats.toggle(1), Some(false));
+        assert_eq!(cheats.patch_rom(0x0F0B, 0x12), 0x12);
+        assert_eq!(cheats.toggle(3), None);
+
+        assert_eq!(cheats.gameshark_writes(None, 1), vec![(0xC000, 0xFF)]);
+        cheats.set_enabled(2, false);
+        assert_eq!(cheats.gameshark_writes(None, 1), vec![]);
+    }
+
+    #[test]
+    fn test_gameshark_banks() {
+        let mut cheats = Cheats::new();
+        // External RAM bank 2
+        cheats.add("824200A0").unwrap();
+        // WRAM bank 3 & WRAM bank 0 (= bank 1)
+        cheats.add("934300D0").unwrap();
+        cheats.add("904401D0").unwrap();
+        // Bank is ignored outside the banked areas.
+        cheats.add("824500C0").unwrap();
+
+        assert_eq!(
+            cheats.gameshark_writes(Some(0), 1),
+            vec![(0xD001, 0x44), (0xC000, 0x45)]
+        );
+        assert_eq!(
+            cheats.gameshark_writes(Some(2), 3),
+            vec![(0xA000, 0x42), (0xD000, 0x43), (0xC000, 0x45)]
+        );
+        assert_eq!(cheats.gameshark_writes(None, 1).len(), 2);
+    }
+}
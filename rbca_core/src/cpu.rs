@@ -1,15 +1,20 @@
 //! All functionality related to the emulated CPU of the Game Boy.
 use std::{default::Default, fmt::Display};
 
-use camino::{Utf8Path, Utf8PathBuf};
+use camino::Utf8Path;
 
 use crate::{
     instructions::execute_opcode,
-    Button, Mmu, RegFlag, Registers,
+    Button, Cartridge, CartridgeError, ImageSource, Mmu, RegFlag, Registers,
     Target::{A, B, C, D, E, H, L},
     DISPLAY_HEIGHT, DISPLAY_WIDTH,
 };
 
+/// Number of T-cycles in one M-cycle.
+const T_CYCLES_PER_M_CYCLE: u32 = 4;
+/// What the LCD shows while the CPU is stopped.
+static BLANK_SCREEN: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT] = [0x00; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+
 /// The emulated CPU of the Game Boy.
 #[derive(Debug)]
 pub struct Cpu {
@@ -23,8 +28,11 @@ pub struct Cpu {
     pub mmu: Mmu,
     /// Halted
     pub is_halted: bool,
-    /// Stopped
-    // TODO
+    /// Whether PC fails to increment after the next opcode is fetched, because HALT was executed
+    /// with interrupts disabled & an interrupt pending.
+    pub halt_bug: bool,
+    /// Stopped. The machine's clock is stopped & the LCD is blank until a selected joypad line
+    /// goes low.
     pub is_stopped: bool,
     /// Countdown until interrupts are disabled.
     pub di_countdown: usize,
@@ -32,62 +40,103 @@ pub struct Cpu {
     pub ei_countdown: usize,
     /// Whether interrupts are enabled or not.
     pub interrupts_enabled: bool,
+    /// Set when the CPU locks up after executing an illegal opcode. The CPU stays locked, but the
+    /// rest of the machine keeps running.
+    pub fault: Option<CpuFault>,
+    /// T-cycles the rest of the machine has been advanced by during the current instruction.
+    instr_t_cycles: u32,
+    /// PPU T-cycles that have passed during the current instruction. Half of
+    /// [instr_t_cycles](Self::instr_t_cycles) in double-speed mode.
+    ppu_t_cycles: u32,
 }
 impl Cpu {
     /// Create a new [Cpu] with no boot ROM or cartridge.
     pub fn new() -> Self {
-        Self::new_no_boot_helper(None::<Utf8PathBuf>)
+        Self::new_no_boot_helper(Mmu::new())
     }
 
     /// Create a new [Cpu] with no boot ROM.
-    pub fn new_cart<P: AsRef<Utf8Path>>(cart_path: P) -> Self {
-        Self::new_no_boot_helper(Some(cart_path))
+    pub fn new_cart<P: AsRef<Utf8Path>>(cart_path: P) -> Result<Self, CartridgeError> {
+        Ok(Self::new_no_boot_helper(Mmu::new_cart(cart_path)?))
     }
 
     /// Create a new [Cpu] with no cartridge.
-    pub fn new_boot<P: AsRef<Utf8Path>>(boot_rom_path: P) -> Self {
-        Self::new_with_boot_helper(None, boot_rom_path)
+    pub fn new_boot<P: AsRef<Utf8Path>>(boot_rom_path: P) -> Result<Self, CartridgeError> {
+        Ok(Self::new_with_boot_helper(Mmu::new_boot(boot_rom_path)?))
     }
 
     /// Create a new [Cpu] with a cartridge and a boot ROM.
-    pub fn new_boot_cart<P: AsRef<Utf8Path>>(cart_path: P, boot_rom_path: P) -> Self {
-        Self::new_with_boot_helper(Some(cart_path), boot_rom_path)
+    pub fn new_boot_cart<P: AsRef<Utf8Path>>(
+        cart_path: P,
+        boot_rom_path: P,
+    ) -> Result<Self, CartridgeError> {
+        Ok(Self::new_with_boot_helper(Mmu::new_boot_cart(
+            cart_path,
+            boot_rom_path,
+        )?))
+    }
+
+    /// Create a new [Cpu] from raw cartridge data with no boot ROM.
+    pub fn new_cart_bytes<D: Into<Vec<u8>>>(cart_data: D) -> Result<Self, CartridgeError> {
+        Ok(Self::new_no_boot_helper(Mmu::new_cart_bytes(cart_data)?))
+    }
+
+    /// Create a new [Cpu] from raw boot ROM data with no cartridge.
+    ///
+    /// [DMG_BOOT](crate::DMG_BOOT) can be used as the boot ROM.
+    pub fn new_boot_bytes(boot_rom_data: &[u8]) -> Result<Self, CartridgeError> {
+        Ok(Self::new_with_boot_helper(Mmu::new_boot_bytes(
+            boot_rom_data,
+        )?))
     }
 
-    fn new_with_boot_helper<P: AsRef<Utf8Path>>(cart_path: Option<P>, boot_rom_path: P) -> Self {
+    /// Create a new [Cpu] from raw cartridge data and raw boot ROM data.
+    ///
+    /// [DMG_BOOT](crate::DMG_BOOT) can be used as the boot ROM.
+    pub fn new_boot_cart_bytes<D: Into<Vec<u8>>>(
+        cart_data: D,
+        boot_rom_data: &[u8],
+    ) -> Result<Self, CartridgeError> {
+        Ok(Self::new_with_boot_helper(Mmu::new_boot_cart_bytes(
+            cart_data,
+            boot_rom_data,
+        )?))
+    }
+
+    fn new_with_boot_helper(mmu: Mmu) -> Self {
         // TODO random vals
         Self {
             regs: Registers::new(),
             pc: 0x0000,
             sp: 0x0000,
-            mmu: if let Some(cp) = cart_path {
-                Mmu::new_boot_cart(cp, boot_rom_path)
-            } else {
-                Mmu::new_boot(boot_rom_path)
-            },
+            mmu,
             is_halted: false,
+            halt_bug: false,
             is_stopped: false,
             ei_countdown: 0,
             di_countdown: 0,
             interrupts_enabled: false,
+            fault: None,
+            instr_t_cycles: 0,
+            ppu_t_cycles: 0,
         }
     }
 
-    fn new_no_boot_helper<P: AsRef<Utf8Path>>(cart_path: Option<P>) -> Self {
+    fn new_no_boot_helper(mmu: Mmu) -> Self {
         let mut cpu = Self {
             regs: Registers::new_after_boot_rom(),
             pc: 0x0100,
             sp: 0xFFFE,
-            mmu: if let Some(cp) = cart_path {
-                Mmu::new_cart(cp)
-            } else {
-                Mmu::new()
-            },
+            mmu,
             is_halted: false,
+            halt_bug: false,
             is_stopped: false,
             ei_countdown: 0,
             di_countdown: 0,
             interrupts_enabled: false,
+            fault: None,
+            instr_t_cycles: 0,
+            ppu_t_cycles: 0,
         };
         cpu.regs.set_reg(A, 0x01);
         cpu.regs.set_reg(B, 0x00);
@@ -112,6 +161,9 @@ impl Cpu {
     /// 2 -> Dark grey
     /// 3 -> Black
     pub fn get_pixels(&self) -> &[u8; DISPLAY_WIDTH * DISPLAY_HEIGHT] {
+        if self.is_stopped {
+            return &BLANK_SCREEN;
+        }
         &self.mmu.ppu.data_output
     }
 
@@ -131,8 +183,62 @@ impl Cpu {
         }
     }
 
+    /// Set the tilt of the cartridge in g. Positive x = tilted right, positive y = tilted down.
+    /// Only affects cartridges with an accelerometer.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mmu.cart.set_tilt(x, y);
+    }
+
+    /// Get the cartridge's battery-backed save data in the raw .sav layout, if it has any.
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        self.mmu.cart.save_data()
+    }
+
+    /// Load battery-backed save data in the raw .sav layout into the cartridge.
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.mmu.cart.load_save_data(data);
+    }
+
+    /// Get a file-name-safe identifier for the loaded game.
+    pub fn game_id(&self) -> String {
+        self.mmu.cart.game_id()
+    }
+
+    /// Get the file name the cartridge's save data should be stored under.
+    pub fn save_file_name(&self) -> String {
+        self.mmu.cart.save_file_name()
+    }
+
+    /// Replace the cartridge without resetting the machine. Return the outgoing cartridge.
+    pub fn swap_cartridge(&mut self, new: Box<dyn Cartridge>) -> Box<dyn Cartridge> {
+        self.mmu.swap_cartridge(new)
+    }
+
+    /// Set the source of images for the cartridge's camera. Only affects cartridges with a camera.
+    pub fn set_image_source(&mut self, image_source: Box<dyn ImageSource>) {
+        self.mmu.cart.set_image_source(image_source);
+    }
+
     /// Perform one cycle. Return number of T-cycles taken and any debug info.
+    ///
+    /// The rest of the machine is advanced one M-cycle at a time as the instruction accesses
+    /// memory, so each read & write happens at the right moment relative to the timer, PPU & OAM
+    /// DMA.
+    ///
+    /// T-cycles are counted at the speed the PPU runs at, so in double-speed mode an instruction
+    /// takes half as many.
     pub fn cycle(&mut self, debug: bool, get_state: bool) -> (u32, Option<EmuState>) {
+        if self.fault.is_some() {
+            self.ppu_t_cycles = 0;
+            self.internal_cycle();
+            return (self.ppu_t_cycles, None);
+        }
+        if self.is_stopped {
+            return (self.stopped_cycle(), None);
+        }
+
+        self.instr_t_cycles = 0;
+        self.ppu_t_cycles = 0;
         self.update_interrupt_countdown();
         let interrupt = self.handle_interrupt();
         let cycles_and_state = if interrupt != 0 {
@@ -140,45 +246,131 @@ impl Cpu {
             emu_state.update(0, interrupt, "INTERRUPT".to_owned());
             (interrupt, Some(emu_state))
         } else if self.is_halted {
+            // With interrupts disabled, a pending interrupt still ends HALT, but isn't
+            // dispatched. Execution continues after the HALT on the next cycle.
+            if self.pending_interrupts() != 0 {
+                self.is_halted = false;
+            }
             (4, None)
         } else {
-            let opcode = self.mmu.read_byte(self.pc);
+            let opcode = self.read_cycle(self.pc);
+            if self.halt_bug {
+                // The opcode is read again as the first byte after it.
+                self.halt_bug = false;
+                self.pc = self.pc.wrapping_sub(1);
+            }
             execute_opcode(self, opcode, debug, get_state)
         };
 
-        self.mmu.cycle(cycles_and_state.0);
-        (cycles_and_state.0, cycles_and_state.1)
+        // Spend the instruction's remaining M-cycles, which don't access memory.
+        debug_assert!(
+            self.instr_t_cycles <= cycles_and_state.0,
+            "Instruction took {} T-cycles, but accessed memory for {} T-cycles",
+            cycles_and_state.0,
+            self.instr_t_cycles
+        );
+        while self.instr_t_cycles < cycles_and_state.0 {
+            self.internal_cycle();
+        }
+        (self.ppu_t_cycles, cycles_and_state.1)
     }
 
-    // Handle interrupts
-    fn handle_interrupt(&mut self) -> u32 {
-        if !self.interrupts_enabled && !self.is_halted {
-            return 0;
+    /// Lock up the CPU after executing the given illegal opcode at PC.
+    pub(crate) fn lock(&mut self, opcode: u8) {
+        let mapper_state = self.mmu.cart.mapper_state();
+        let rom_bank = match self.pc {
+            0x0000..=0x3FFF => Some(mapper_state.rom_bank_0000),
+            0x4000..=0x7FFF => Some(mapper_state.rom_bank_4000),
+            _ => None,
+        };
+        self.fault = Some(CpuFault {
+            opcode,
+            pc: self.pc,
+            rom_bank,
+        });
+    }
+
+    /// Perform one M-cycle while stopped. A selected joypad line going low ends STOP.
+    fn stopped_cycle(&mut self) -> u32 {
+        if self.mmu.joypad.input_low() {
+            self.is_stopped = false;
         }
+        self.mmu.stopped_cycle(T_CYCLES_PER_M_CYCLE)
+    }
 
-        let interrupt_enable_register = 0b0001_1111 & self.mmu.read_byte(0xFFFF);
-        let interrupt_flag_register = 0b0001_1111 & self.mmu.read_byte(0xFF0F);
-        let interrupt_activated = interrupt_enable_register & interrupt_flag_register;
-        if interrupt_activated == 0 {
+    /// Read a byte as part of an instruction. The rest of the machine is advanced by one M-cycle.
+    pub(crate) fn read_cycle(&mut self, address: u16) -> u8 {
+        let value = self.mmu.read_byte(address);
+        self.internal_cycle();
+        value
+    }
+
+    /// Write a byte as part of an instruction. The rest of the machine is advanced by one
+    /// M-cycle.
+    pub(crate) fn write_cycle(&mut self, address: u16, value: u8) {
+        self.mmu.write_byte(address, value);
+        self.internal_cycle();
+    }
+
+    /// Spend one M-cycle without accessing memory. The rest of the machine is advanced.
+    pub(crate) fn internal_cycle(&mut self) {
+        self.ppu_t_cycles += self.mmu.cycle(T_CYCLES_PER_M_CYCLE);
+        self.instr_t_cycles += T_CYCLES_PER_M_CYCLE;
+    }
+
+    /// Dispatch the highest-priority pending interrupt if interrupts are enabled, waking the CPU
+    /// from HALT if needed. Return the number of T-cycles taken, or 0 if no interrupt was
+    /// dispatched.
+    ///
+    /// Dispatch takes 5 M-cycles, plus one to exit HALT: two internal M-cycles, one for each byte
+    /// of PC pushed, & one to jump. The interrupt is chosen after the high byte of PC is pushed.
+    /// If that push overwrites IE (SP = 0x0000) & cancels the pending interrupts, PC is set to
+    /// 0x0000 instead.
+    fn handle_interrupt(&mut self) -> u32 {
+        if !self.interrupts_enabled || self.pending_interrupts() == 0 {
             return 0;
         }
 
+        let was_halted = self.is_halted;
         self.is_halted = false;
-        if !self.interrupts_enabled {
-            return 0;
-        }
         self.interrupts_enabled = false;
+        if self.halt_bug {
+            // EI; HALT with an interrupt pending: the interrupt returns to the HALT.
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
+        }
 
-        // Prioritise lowest activated interrupt
-        let offset = interrupt_activated.trailing_zeros();
-        if offset >= 5 {
-            panic!("Invalid interrupt triggered: {:#010b}", interrupt_activated);
+        if was_halted {
+            self.internal_cycle();
         }
-        self.mmu
-            .write_byte(0xFF0F, interrupt_flag_register & !(0b1 << offset));
-        self.push_stack(self.pc);
-        self.pc = 0x0040 | ((offset as u16) << 3);
-        20
+        self.internal_cycle();
+        self.internal_cycle();
+
+        let [low, high] = self.pc.to_le_bytes();
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_cycle(self.sp, high);
+        let interrupt_activated = self.pending_interrupts();
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_cycle(self.sp, low);
+
+        self.pc = if interrupt_activated == 0 {
+            // Cancelled by the push.
+            0x0000
+        } else {
+            // Prioritise lowest activated interrupt
+            let offset = interrupt_activated.trailing_zeros();
+            let interrupt_flag_register = self.mmu.read_byte(0xFF0F);
+            self.mmu
+                .write_byte(0xFF0F, interrupt_flag_register & !(0b1 << offset));
+            0x0040 | ((offset as u16) << 3)
+        };
+        self.internal_cycle();
+        self.instr_t_cycles
+    }
+
+    /// Get the interrupts that are both requested & enabled.
+    pub(crate) fn pending_interrupts(&self) -> u8 {
+        0b0001_1111 & self.mmu.read_byte(0xFFFF) & self.mmu.read_byte(0xFF0F)
     }
 
     fn update_interrupt_countdown(&mut self) {
@@ -208,27 +400,35 @@ impl Cpu {
         }
     }
 
-    /// Get next byte.
+    /// Read the byte after the opcode. Takes one M-cycle.
     pub fn get_next_byte(&mut self) -> u8 {
-        self.mmu.read_byte(self.pc + 1)
+        self.read_cycle(self.pc + 1)
     }
 
-    /// Get next two bytes (little-endian).
+    /// Read the two bytes after the opcode (little-endian). Takes two M-cycles.
     pub fn get_next_2_bytes(&mut self) -> u16 {
-        self.mmu.read_2_bytes(self.pc + 1)
+        let low = self.read_cycle(self.pc + 1);
+        let high = self.read_cycle(self.pc + 2);
+        u16::from_le_bytes([low, high])
     }
 
-    /// Push to stack.
+    /// Push to stack. Takes three M-cycles: one to decrement SP & one for each byte.
     pub fn push_stack(&mut self, value: u16) {
-        self.sp = self.sp.wrapping_sub(2);
-        self.mmu.write_2_bytes(self.sp, value);
+        let [low, high] = value.to_le_bytes();
+        self.internal_cycle();
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_cycle(self.sp, high);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_cycle(self.sp, low);
     }
 
-    /// Pop from stack.
+    /// Pop from stack. Takes two M-cycles.
     pub fn pop_stack(&mut self) -> u16 {
-        let popped_val = self.mmu.read_2_bytes(self.sp);
-        self.sp = self.sp.wrapping_add(2);
-        popped_val
+        let low = self.read_cycle(self.sp);
+        self.sp = self.sp.wrapping_add(1);
+        let high = self.read_cycle(self.sp);
+        self.sp = self.sp.wrapping_add(1);
+        u16::from_le_bytes([low, high])
     }
 
     /// Get some emulator info formatted as a nice String.
@@ -257,6 +457,26 @@ impl Default for Cpu {
     }
 }
 
+/// Why the CPU locked up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CpuFault {
+    /// The illegal opcode that was executed.
+    pub opcode: u8,
+    /// The address of the illegal opcode.
+    pub pc: u16,
+    /// The ROM bank mapped in at PC. None if PC isn't in ROM.
+    pub rom_bank: Option<usize>,
+}
+impl Display for CpuFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Illegal opcode {:#04X} at {:#06X}", self.opcode, self.pc)?;
+        if let Some(rom_bank) = self.rom_bank {
+            write!(f, " (ROM bank {rom_bank})")?;
+        }
+        Ok(())
+    }
+}
+
 /// The state of the emulator at a specific point in time.
 pub struct EmuState {
     /// Program counter.
@@ -398,6 +618,7 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::DMG_BOOT;
 
     #[test]
     fn test_next_2_bytes() {
@@ -407,4 +628,128 @@ mod tests {
         cpu.pc = 0x0000;
         assert_eq!(cpu.get_next_2_bytes(), 0x4523);
     }
+
+    #[test]
+    fn test_new_bytes() {
+        let mut rom = vec![0x00_u8; 0x8000];
+        rom[0x0100] = 0x42;
+
+        let cpu = Cpu::new_cart_bytes(rom.as_slice()).unwrap();
+        assert_eq!(cpu.pc, 0x0100);
+        assert_eq!(cpu.mmu.read_byte(0x0100), 0x42);
+
+        let cpu = Cpu::new_boot_cart_bytes(rom, &DMG_BOOT).unwrap();
+        assert_eq!(cpu.pc, 0x0000);
+        assert_eq!(cpu.mmu.read_byte(0x0000), DMG_BOOT[0]);
+        assert_eq!(cpu.mmu.read_byte(0x0100), 0x42);
+
+        let cpu = Cpu::new_boot_bytes(&DMG_BOOT).unwrap();
+        assert_eq!(cpu.mmu.read_byte(0x00FF), DMG_BOOT[0xFF]);
+
+        assert!(matches!(
+            Cpu::new_boot_bytes(&DMG_BOOT[..0x80]),
+            Err(CartridgeError::TruncatedHeader { .. })
+        ));
+        assert!(matches!(
+            Cpu::new_cart_bytes(vec![0x00; 0x100]),
+            Err(CartridgeError::TruncatedHeader { .. })
+        ));
+    }
+
+    fn interrupt_cpu(pc: u16, sp: u16, ie: u8, if_reg: u8) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.pc = pc;
+        cpu.sp = sp;
+        cpu.interrupts_enabled = true;
+        cpu.mmu.write_byte(0xFFFF, ie);
+        cpu.mmu.write_byte(0xFF0F, if_reg);
+        cpu
+    }
+
+    #[test]
+    fn test_interrupt_dispatch() {
+        let mut cpu = interrupt_cpu(0xC123, 0xDFF0, 0b0000_0101, 0b0000_0101);
+        assert_eq!(cpu.cycle(false, false).0, 20);
+        assert_eq!(cpu.pc, 0x0040);
+        assert_eq!(cpu.sp, 0xDFEE);
+        assert_eq!(cpu.mmu.read_byte(0xDFEF), 0xC1);
+        assert_eq!(cpu.mmu.read_byte(0xDFEE), 0x23);
+        assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0000_0100);
+        assert!(!cpu.interrupts_enabled);
+
+        // Exiting HALT takes an extra M-cycle.
+        let mut cpu = interrupt_cpu(0xC123, 0xDFF0, 0b0000_0100, 0b0000_0100);
+        cpu.is_halted = true;
+        assert_eq!(cpu.cycle(false, false).0, 24);
+        assert_eq!(cpu.pc, 0x0050);
+        assert!(!cpu.is_halted);
+    }
+
+    #[test]
+    fn test_interrupt_ie_push() {
+        // The high byte of PC (0x02) overwrites IE, cancelling the VBlank interrupt.
+        let mut cpu = interrupt_cpu(0x0234, 0x0000, 0b0000_0001, 0b0000_0001);
+        assert_eq!(cpu.cycle(false, false).0, 20);
+        assert_eq!(cpu.pc, 0x0000);
+        assert_eq!(cpu.mmu.read_byte(0xFFFF), 0x02);
+        assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0000_0001);
+
+        // The high byte of PC (0x04) redirects the interrupt to the timer.
+        let mut cpu = interrupt_cpu(0x0434, 0x0000, 0b0000_0001, 0b0000_0101);
+        cpu.cycle(false, false);
+        assert_eq!(cpu.pc, 0x0050);
+        assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0000_0001);
+    }
+
+    #[test]
+    fn test_interrupt_ie_push_redirect() {
+        // The high byte of PC (0x10) leaves only the joypad enabled: the lowest priority
+        // interrupt is dispatched instead of VBlank, which stays requested.
+        let mut cpu = interrupt_cpu(0x1034, 0x0000, 0b0001_0001, 0b0001_0001);
+        assert_eq!(cpu.cycle(false, false).0, 20);
+        assert_eq!(cpu.pc, 0x0060);
+        assert_eq!(cpu.sp, 0xFFFE);
+        assert_eq!(cpu.mmu.read_byte(0xFFFF), 0x10);
+        assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0000_0001);
+        assert!(!cpu.interrupts_enabled);
+
+        // The high byte of PC (0x0A) enables STAT & serial: the highest priority of the two
+        // wins.
+        let mut cpu = interrupt_cpu(0x0A34, 0x0000, 0b0000_0001, 0b0000_1011);
+        cpu.cycle(false, false);
+        assert_eq!(cpu.pc, 0x0048);
+        assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0000_1001);
+
+        // The low byte of PC is pushed to IE after the vector has been chosen, so it doesn't
+        // change which interrupt is dispatched.
+        let mut cpu = interrupt_cpu(0x1234, 0x0001, 0b0000_0001, 0b0001_0001);
+        cpu.cycle(false, false);
+        assert_eq!(cpu.pc, 0x0040);
+        assert_eq!(cpu.sp, 0xFFFF);
+        assert_eq!(cpu.mmu.read_byte(0xFFFF), 0x34);
+        assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0001_0000);
+    }
+
+    #[test]
+    fn test_ei_delay() {
+        // EI; NOP; NOP: the interrupt is dispatched after the instruction following EI.
+        let mut cpu = interrupt_cpu(0xC000, 0xDFF0, 0b0000_0001, 0b0000_0001);
+        cpu.interrupts_enabled = false;
+        cpu.load(0xC000, &[0xFB, 0x00, 0x00]);
+        cpu.cycle(false, false);
+        cpu.cycle(false, false);
+        assert_eq!(cpu.pc, 0xC002);
+        cpu.cycle(false, false);
+        assert_eq!(cpu.pc, 0x0040);
+        assert_eq!(cpu.pop_stack(), 0xC002);
+
+        // EI; DI: the interrupt is never dispatched.
+        let mut cpu = interrupt_cpu(0xC000, 0xDFF0, 0b0000_0001, 0b0000_0001);
+        cpu.interrupts_enabled = false;
+        cpu.load(0xC000, &[0xFB, 0xF3, 0x00, 0x00]);
+        for _ in 0..4 {
+            cpu.cycle(false, false);
+        }
+        assert_eq!(cpu.pc, 0xC004);
+    }
 }
@@ -5,6 +5,9 @@ use crate::{
     VirtTarget::{self, AF, BC, DE, HL},
 };
 
+/// M-cycles the CPU is paused for while switching between normal & double speed.
+const SPEED_SWITCH_M_CYCLES: u32 = 2050;
+
 /// Execute a given opcode. Return the amount of cycles the instruction takes and any debug info.
 pub fn execute_opcode(
     cpu: &mut Cpu,
@@ -303,10 +306,7 @@ pub fn execute_opcode(
         0x76 => halt(cpu),
 
         // STOP
-        0x10 => match cpu.get_next_byte() {
-            0x00 => stop(cpu),
-            other => panic!("Illegal operation: {:#04X}", 0x1000 | (other as u16)),
-        },
+        0x10 => stop(cpu),
 
         // DI
         0xF3 => di(cpu),
@@ -380,7 +380,7 @@ pub fn execute_opcode(
 
         // CB-Opcodes
         0xCB => {
-            let ext_opcode = cpu.mmu.read_byte(cpu.pc + 1);
+            let ext_opcode = cpu.read_cycle(cpu.pc + 1);
             match ext_opcode {
                 // SWAP n
                 0x37 => swap_n(cpu, A),
@@ -714,7 +714,7 @@ pub fn execute_opcode(
 
         // Illegal opcodes
         0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
-            panic!("Illegal opcode {:#02X} at {:#04X}", opcode, cpu.pc)
+            illegal(cpu, opcode)
         }
 
         // Unimplemented instruction
@@ -749,7 +749,8 @@ fn ld_nn_n(cpu: &mut Cpu, target: Target) -> (u16, u32, String) {
     let cycles = 8;
     let instruction_string = format!("LD {target},n");
 
-    cpu.regs.set_reg(target, cpu.mmu.read_byte(cpu.pc + 1));
+    let value = cpu.get_next_byte();
+    cpu.regs.set_reg(target, value);
     cpu.pc += size;
     (size, cycles, instruction_string)
 }
@@ -771,7 +772,7 @@ fn ld_r1_hl(cpu: &mut Cpu, r1: Target) -> (u16, u32, String) {
     let instruction_string = format!("LD {r1},(HL)");
 
     let address = cpu.regs.get_virt_reg(HL);
-    let value = cpu.mmu.read_byte(address);
+    let value = cpu.read_cycle(address);
     cpu.regs.set_reg(r1, value);
 
     cpu.pc += size;
@@ -783,7 +784,7 @@ fn ld_hl_r2(cpu: &mut Cpu, r2: Target) -> (u16, u32, String) {
     let instruction_string = format!("LD (HL),{r2}");
 
     let address = cpu.regs.get_virt_reg(HL);
-    cpu.mmu.write_byte(address, cpu.regs.get_reg(r2));
+    cpu.write_cycle(address, cpu.regs.get_reg(r2));
 
     cpu.pc += size;
     (size, cycles, instruction_string)
@@ -795,7 +796,7 @@ fn ld_hl_n(cpu: &mut Cpu) -> (u16, u32, String) {
 
     let address = cpu.regs.get_virt_reg(HL);
     let value = cpu.get_next_byte();
-    cpu.mmu.write_byte(address, value);
+    cpu.write_cycle(address, value);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -808,7 +809,7 @@ fn ld_a_vr(cpu: &mut Cpu, target: VirtTarget) -> (u16, u32, String) {
     let instruction_string = format!("LD A,{target}");
 
     let address = cpu.regs.get_virt_reg(target);
-    let value = cpu.mmu.read_byte(address);
+    let value = cpu.read_cycle(address);
     ld_a_n_helper(cpu, value);
 
     cpu.pc += size;
@@ -820,7 +821,7 @@ fn ld_a_nn(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LD A,nn";
 
     let address = cpu.get_next_2_bytes();
-    let value = cpu.mmu.read_byte(address);
+    let value = cpu.read_cycle(address);
     ld_a_n_helper(cpu, value);
 
     cpu.pc += size;
@@ -858,7 +859,7 @@ fn ld_vr_a(cpu: &mut Cpu, target: VirtTarget) -> (u16, u32, String) {
     let instruction_string = format!("LD {target},A");
 
     let address = cpu.regs.get_virt_reg(target);
-    cpu.mmu.write_byte(address, cpu.regs.get_reg(A));
+    cpu.write_cycle(address, cpu.regs.get_reg(A));
 
     cpu.pc += size;
     (size, cycles, instruction_string)
@@ -869,7 +870,7 @@ fn ld_nn_a(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LD nn,A";
 
     let address = cpu.get_next_2_bytes();
-    cpu.mmu.write_byte(address, cpu.regs.get_reg(A));
+    cpu.write_cycle(address, cpu.regs.get_reg(A));
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -882,7 +883,8 @@ fn ld_a_c(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LD A,(C)";
 
     let address = 0xFF00 | (cpu.regs.get_reg(C) as u16);
-    cpu.regs.set_reg(A, cpu.mmu.read_byte(address));
+    let value = cpu.read_cycle(address);
+    cpu.regs.set_reg(A, value);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -895,7 +897,7 @@ fn ld_c_a(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LD (C),A";
 
     let address = 0xFF00 | (cpu.regs.get_reg(C) as u16);
-    cpu.mmu.write_byte(address, cpu.regs.get_reg(A));
+    cpu.write_cycle(address, cpu.regs.get_reg(A));
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -951,14 +953,15 @@ fn ld_hli_a(cpu: &mut Cpu) -> (u16, u32, String) {
 
 fn ld_a_hl_helper(cpu: &mut Cpu, is_inc: bool) {
     let address = cpu.regs.get_virt_reg(HL);
-    cpu.regs.set_reg(A, cpu.mmu.read_byte(address));
+    let value = cpu.read_cycle(address);
+    cpu.regs.set_reg(A, value);
 
     let new_val = if is_inc { address + 1 } else { address - 1 };
     cpu.regs.set_virt_reg(HL, new_val);
 }
 fn ld_hl_a_helper(cpu: &mut Cpu, is_inc: bool) {
     let address = cpu.regs.get_virt_reg(HL);
-    cpu.mmu.write_byte(address, cpu.regs.get_reg(A));
+    cpu.write_cycle(address, cpu.regs.get_reg(A));
 
     let new_val = if is_inc { address + 1 } else { address - 1 };
     cpu.regs.set_virt_reg(HL, new_val);
@@ -971,7 +974,7 @@ fn ldh_n_a(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LDH (n),A";
 
     let address = 0xFF00 | (cpu.get_next_byte() as u16);
-    cpu.mmu.write_byte(address, cpu.regs.get_reg(A));
+    cpu.write_cycle(address, cpu.regs.get_reg(A));
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -984,7 +987,8 @@ fn ldh_a_n(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LDH A,(n)";
 
     let address = 0xFF00 | (cpu.get_next_byte() as u16);
-    cpu.regs.set_reg(A, cpu.mmu.read_byte(address));
+    let value = cpu.read_cycle(address);
+    cpu.regs.set_reg(A, value);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1044,7 +1048,7 @@ fn ld_hl_sp_n(cpu: &mut Cpu) -> (u16, u32, String) {
 }
 fn sp_n_helper(cpu: &mut Cpu) -> u16 {
     let n_i = cpu.get_next_byte() as i8;
-    let n_u = cpu.get_next_byte() as i8 as i16 as u16;
+    let n_u = n_i as i16 as u16;
 
     cpu.regs.reset_flags();
     let h_val;
@@ -1071,7 +1075,9 @@ fn ld_nn_sp(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "LD (nn),SP";
 
     let address = cpu.get_next_2_bytes();
-    cpu.mmu.write_2_bytes(address, cpu.sp);
+    let [low, high] = cpu.sp.to_le_bytes();
+    cpu.write_cycle(address, low);
+    cpu.write_cycle(address.wrapping_add(1), high);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1118,7 +1124,7 @@ fn add_a_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let cycles = 8;
     let instruction_string = "ADD A,(HL)";
 
-    let n = cpu.mmu.read_byte(cpu.regs.get_virt_reg(HL));
+    let n = cpu.read_cycle(cpu.regs.get_virt_reg(HL));
     add_a_n_helper(cpu, n, false);
 
     cpu.pc += size;
@@ -1173,7 +1179,7 @@ fn adc_a_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let cycles = 8;
     let instruction_string = "ADC A,(HL)";
 
-    let n = cpu.mmu.read_byte(cpu.regs.get_virt_reg(HL));
+    let n = cpu.read_cycle(cpu.regs.get_virt_reg(HL));
     add_a_n_helper(cpu, n, true);
 
     cpu.pc += size;
@@ -1207,7 +1213,7 @@ fn sub_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let cycles = 8;
     let instruction_string = "SUB (HL)";
 
-    let n = cpu.mmu.read_byte(cpu.regs.get_virt_reg(HL));
+    let n = cpu.read_cycle(cpu.regs.get_virt_reg(HL));
     sub_n_helper(cpu, n, false);
 
     cpu.pc += size;
@@ -1260,7 +1266,7 @@ fn sbc_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let cycles = 8;
     let instruction_string = "SBC A,(HL)";
 
-    let n = cpu.mmu.read_byte(cpu.regs.get_virt_reg(HL));
+    let n = cpu.read_cycle(cpu.regs.get_virt_reg(HL));
     sub_n_helper(cpu, n, true);
 
     cpu.pc += size;
@@ -1295,7 +1301,7 @@ fn and_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "AND (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let n = cpu.mmu.read_byte(address);
+    let n = cpu.read_cycle(address);
     and_n_helper(cpu, n);
 
     cpu.pc += size;
@@ -1339,7 +1345,7 @@ fn or_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "OR (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let n = cpu.mmu.read_byte(address);
+    let n = cpu.read_cycle(address);
     or_n_helper(cpu, n);
 
     cpu.pc += size;
@@ -1382,7 +1388,7 @@ fn xor_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "XOR (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let n = cpu.mmu.read_byte(address);
+    let n = cpu.read_cycle(address);
     xor_n_helper(cpu, n);
 
     cpu.pc += size;
@@ -1425,7 +1431,7 @@ fn cp_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "CP (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let n = cpu.mmu.read_byte(address);
+    let n = cpu.read_cycle(address);
     cp_n_helper(cpu, n);
 
     cpu.pc += size;
@@ -1470,12 +1476,12 @@ fn inc_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "INC (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let val = cpu.mmu.read_byte(address);
+    let val = cpu.read_cycle(address);
     let result = val.wrapping_add(1);
 
     inc_n_set_flags(cpu, val, result);
 
-    cpu.mmu.write_byte(address, result);
+    cpu.write_cycle(address, result);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1508,12 +1514,12 @@ fn dec_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "DEC (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let val = cpu.mmu.read_byte(address);
+    let val = cpu.read_cycle(address);
     let result = val.wrapping_sub(1);
 
     dec_n_set_flags(cpu, val, result);
 
-    cpu.mmu.write_byte(address, result);
+    cpu.write_cycle(address, result);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1636,9 +1642,9 @@ fn swap_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "SWAP (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let val = cpu.mmu.read_byte(address);
+    let val = cpu.read_cycle(address);
     let result = swap_n_helper(cpu, val);
-    cpu.mmu.write_byte(address, result);
+    cpu.write_cycle(address, result);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1758,25 +1764,61 @@ fn nop(cpu: &mut Cpu) -> (u16, u32, String) {
     (size, cycles, String::from(instruction_string))
 }
 
+// Illegal opcode: Lock up the CPU. PC is left pointing at the opcode.
+fn illegal(cpu: &mut Cpu, opcode: u8) -> (u16, u32, String) {
+    let size = 1;
+    let cycles = 4;
+    let instruction_string = format!("ILLEGAL {opcode:#04X}");
+
+    cpu.lock(opcode);
+
+    (size, cycles, instruction_string)
+}
+
 // HALT: Power down CPU until interrupt.
 fn halt(cpu: &mut Cpu) -> (u16, u32, String) {
     let size = 1;
     let cycles = 4;
     let instruction_string = "HALT";
 
-    cpu.is_halted = true;
+    if !cpu.interrupts_enabled && cpu.pending_interrupts() != 0 {
+        // HALT bug: the CPU doesn't halt, & PC isn't incremented after the next opcode is read.
+        cpu.halt_bug = true;
+    } else {
+        cpu.is_halted = true;
+    }
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
 }
 
-// STOP: Halt CPU & LCD display until button pressed.
+// STOP: Halt CPU & LCD display until button pressed. In CGB mode, switch speed if KEY1 is armed.
+//
+// What actually happens depends on whether a selected joypad line is low & whether an interrupt
+// is pending. STOP is followed by a padding byte, which is skipped unless an interrupt is pending.
 fn stop(cpu: &mut Cpu) -> (u16, u32, String) {
-    let size = 2;
-    let cycles = 4;
+    let interrupt_pending = cpu.pending_interrupts() != 0;
+    let size = if interrupt_pending { 1 } else { 2 };
+    let mut cycles = 4;
     let instruction_string = "STOP";
 
-    cpu.is_stopped = true;
+    if cpu.mmu.joypad.input_low() {
+        // STOP would end straight away. Act as HALT (or NOP) instead & leave DIV alone.
+        cpu.is_halted = !interrupt_pending;
+    } else {
+        cpu.mmu.write_byte(0xFF04, 0x00);
+        if cpu.mmu.speed_switch_armed() {
+            // With an interrupt pending & IME=1, real hardware glitches. This is treated the same
+            // as IME=0.
+            cpu.mmu.switch_speed();
+            if !interrupt_pending {
+                // The CPU is paused until the switch is done.
+                cycles += SPEED_SWITCH_M_CYCLES * 4;
+            }
+        } else {
+            cpu.is_stopped = true;
+        }
+    }
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1886,9 +1928,9 @@ fn rlc_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "RLC (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let rotated_l = rlc_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, rotated_l);
+    cpu.write_cycle(address, rotated_l);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1922,9 +1964,9 @@ fn rl_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "RL (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let rotated_l = rl_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, rotated_l);
+    cpu.write_cycle(address, rotated_l);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1958,9 +2000,9 @@ fn rrc_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "RRC (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let rotated_r = rrc_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, rotated_r);
+    cpu.write_cycle(address, rotated_r);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -1994,9 +2036,9 @@ fn rr_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "RR (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let rotated_r = rr_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, rotated_r);
+    cpu.write_cycle(address, rotated_r);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -2035,9 +2077,9 @@ fn sla_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "SLA (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let result = sla_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, result);
+    cpu.write_cycle(address, result);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -2072,9 +2114,9 @@ fn sra_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "SRA (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let result = sra_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, result);
+    cpu.write_cycle(address, result);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -2109,9 +2151,9 @@ fn srl_n_hl(cpu: &mut Cpu) -> (u16, u32, String) {
     let instruction_string = "SRL (HL)";
 
     let address = cpu.regs.get_virt_reg(HL);
-    let original_val = cpu.mmu.read_byte(address);
+    let original_val = cpu.read_cycle(address);
     let result = srl_n_helper(cpu, original_val);
-    cpu.mmu.write_byte(address, result);
+    cpu.write_cycle(address, result);
 
     cpu.pc += size;
     (size, cycles, String::from(instruction_string))
@@ -2143,7 +2185,7 @@ fn bit_b_r_hl(cpu: &mut Cpu, b: usize) -> (u16, u32, String) {
     let cycles = 12;
     let instruction_string = format!("BIT {b},(HL)");
 
-    let target_byte = cpu.mmu.read_byte(cpu.regs.get_virt_reg(HL));
+    let target_byte = cpu.read_cycle(cpu.regs.get_virt_reg(HL));
     bit_b_r_helper(cpu, b, target_byte);
 
     cpu.pc += size;
@@ -2174,8 +2216,8 @@ fn set_b_r_hl(cpu: &mut Cpu, b: usize) -> (u16, u32, String) {
     let instruction_string = format!("SET {b},(HL)");
 
     let address = cpu.regs.get_virt_reg(HL);
-    let byte = cpu.mmu.read_byte(address);
-    cpu.mmu.write_byte(address, byte | (0x01 << b));
+    let byte = cpu.read_cycle(address);
+    cpu.write_cycle(address, byte | (0x01 << b));
 
     cpu.pc += size;
     (size, cycles, instruction_string)
@@ -2199,8 +2241,8 @@ fn res_b_r_hl(cpu: &mut Cpu, b: usize) -> (u16, u32, String) {
     let instruction_string = format!("RES {b},(HL)");
 
     let address = cpu.regs.get_virt_reg(HL);
-    let byte = cpu.mmu.read_byte(address);
-    cpu.mmu.write_byte(address, byte & !(0x01 << b));
+    let byte = cpu.read_cycle(address);
+    cpu.write_cycle(address, byte & !(0x01 << b));
 
     cpu.pc += size;
     (size, cycles, instruction_string)
@@ -2229,9 +2271,10 @@ fn jp_cc_nn(cpu: &mut Cpu, flag: RegFlag, expected_value: bool) -> (u16, u32, St
         _ => panic!("jr_cc_n: Cannot use flag {:?}. C or Z flags only.", flag),
     };
 
+    // The address is read whether or not the jump is taken.
+    let nn = cpu.get_next_2_bytes();
     if test_val == expected_value {
         cycles = 16;
-        let nn = cpu.get_next_2_bytes();
         jp_helper(cpu, nn);
     } else {
         cycles = 12;
@@ -2274,8 +2317,9 @@ fn jr_cc_n(cpu: &mut Cpu, flag: RegFlag, expected_value: bool) -> (u16, u32, Str
         _ => panic!("jr_cc_n: Cannot use flag {:?}. C or Z flags only.", flag),
     };
 
+    // The offset is read whether or not the jump is taken.
+    let n = cpu.get_next_byte() as i8;
     if test_val == expected_value {
-        let n = cpu.get_next_byte() as i8;
         cycles = 12;
         cpu.pc += size;
         jp_helper(cpu, ((cpu.pc as u32 as i32) + (n as i32)) as u16);
@@ -2297,9 +2341,10 @@ fn call_nn(cpu: &mut Cpu) -> (u16, u32, String) {
     let cycles = 24;
     let instruction_string = "CALL nn";
 
-    cpu.push_stack(cpu.pc + 3);
+    let nn = cpu.get_next_2_bytes();
+    cpu.push_stack(cpu.pc + size);
 
-    cpu.pc = cpu.get_next_2_bytes();
+    cpu.pc = nn;
     (size, cycles, String::from(instruction_string))
 }
 
@@ -2314,10 +2359,12 @@ fn call_cc_nn(cpu: &mut Cpu, flag: RegFlag, expected_value: bool) -> (u16, u32,
         RegFlag::Z | RegFlag::C => cpu.regs.get_flag(flag),
         _ => panic!("call_cc_nn: Cannot use flag {:?}. C or Z flags only.", flag),
     };
+    // The address is read whether or not the call is made.
+    let nn = cpu.get_next_2_bytes();
     if test_val == expected_value {
         cycles = 24;
         cpu.push_stack(cpu.pc + size);
-        cpu.pc = cpu.get_next_2_bytes();
+        cpu.pc = nn;
     } else {
         cycles = 12;
         cpu.pc += size;
@@ -2359,6 +2406,8 @@ fn ret_cc(cpu: &mut Cpu, flag: RegFlag, expected_value: bool) -> (u16, u32, Stri
         RegFlag::Z | RegFlag::C => cpu.regs.get_flag(flag),
         _ => panic!("call_cc_nn: Cannot use flag {:?}. C or Z flags only.", flag),
     };
+    // The condition is checked during its own M-cycle.
+    cpu.internal_cycle();
     if test_val == expected_value {
         cycles = 20;
         cpu.pc = cpu.pop_stack();
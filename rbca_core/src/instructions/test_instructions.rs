@@ -2,6 +2,7 @@ use pretty_assertions::assert_eq;
 use strum::IntoEnumIterator;
 
 use super::*;
+use crate::{Button, CgbFlag, CpuFault, RomBuilder};
 
 #[test]
 fn test_ld_n_nn() {
@@ -1821,13 +1822,14 @@ fn test_good_stop() {
 }
 
 #[test]
-#[should_panic]
-fn test_bad_stop() {
+fn test_stop_padding() {
+    // The byte after STOP is skipped, whatever it is.
     let mut cpu = Cpu::new();
-    cpu.pc = 0x0000;
-    cpu.mmu.write_byte(0x0000, 0x10);
-    cpu.mmu.write_byte(0x0001, 0xFF);
+    cpu.load(0xC000, &[0x10, 0xFF]);
+    cpu.pc = 0xC000;
     cpu.cycle(true, false);
+    assert!(cpu.is_stopped);
+    assert_eq!(cpu.pc, 0xC002);
 }
 
 #[test]
@@ -2621,11 +2623,318 @@ fn test_reti() {
 }
 
 #[test]
-#[should_panic]
+fn test_mem_timing() {
+    // TIMA increments every 4 M-cycles, so it shows which M-cycle an access lands on.
+    fn timer_cpu(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(0xC000, program);
+        cpu.pc = 0xC000;
+        cpu.mmu.write_byte(0xFF07, 0b0000_0101);
+        cpu
+    }
+
+    // NOP; LD A,(0xFF05): the read is on the last M-cycle, after TIMA increments.
+    let mut cpu = timer_cpu(&[0x00, 0xFA, 0x05, 0xFF]);
+    cpu.cycle(false, false);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.regs.get_reg(A), 0x01);
+
+    // NOP; LD (0xFF05),A: the write is on the last M-cycle, after TIMA increments.
+    let mut cpu = timer_cpu(&[0x00, 0xEA, 0x05, 0xFF]);
+    cpu.regs.set_reg(A, 0x80);
+    cpu.cycle(false, false);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.mmu.read_byte(0xFF05), 0x80);
+
+    // NOP; NOP; INC (HL): TIMA increments between the read & the write.
+    let mut cpu = timer_cpu(&[0x00, 0x00, 0x34]);
+    cpu.regs.set_virt_reg(HL, 0xFF05);
+    for _ in 0..3 {
+        cpu.cycle(false, false);
+    }
+    assert_eq!(cpu.mmu.read_byte(0xFF05), 0x01);
+}
+
+#[test]
+fn test_m_cycles() {
+    // Cpu::cycle asserts that no instruction accesses memory for more M-cycles than it takes.
+    for opcode in 0x00..=0xFF {
+        if matches!(
+            opcode,
+            0x10 | 0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD
+        ) {
+            continue;
+        }
+        let ext_opcodes = if opcode == 0xCB {
+            0x00..=0xFF
+        } else {
+            0x00..=0x00
+        };
+        for ext_opcode in ext_opcodes {
+            // Taken & not taken branches.
+            for flag_val in [false, true] {
+                let mut cpu = Cpu::new();
+                cpu.load(0xC000, &[opcode, ext_opcode, 0xC0]);
+                cpu.pc = 0xC000;
+                cpu.sp = 0xDFF0;
+                cpu.regs.set_virt_reg(HL, 0xC100);
+                cpu.regs.set_flag(RegFlag::Z, flag_val);
+                cpu.regs.set_flag(RegFlag::C, flag_val);
+                cpu.cycle(false, false);
+            }
+        }
+    }
+}
+
+#[test]
 fn illegal_opcode() {
-    let mut cpu = Cpu::new();
-    let data = [0xD3];
-    cpu.load(0x0000, &data);
-    cpu.pc = 0x0000;
-    cpu.cycle(true, false);
+    for opcode in [
+        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+    ] {
+        let mut cpu = Cpu::new();
+        cpu.load(0xC000, &[opcode]);
+        cpu.pc = 0xC000;
+        cpu.cycle(true, false);
+        assert_eq!(
+            cpu.fault,
+            Some(CpuFault {
+                opcode,
+                pc: 0xC000,
+                rom_bank: None
+            })
+        );
+    }
+
+    // MBC1, 64 KiB ROM
+    let rom = RomBuilder::new(0x01)
+        .rom_size(0x01)
+        .code(2, 0x4100, &[0xFD])
+        .build();
+    let mut cpu = Cpu::new_cart_bytes(rom).unwrap();
+    cpu.mmu.write_byte(0x2000, 0x02);
+    cpu.pc = 0x4100;
+    cpu.cycle(false, false);
+    let fault = cpu.fault.unwrap();
+    assert_eq!(fault.rom_bank, Some(2));
+    assert_eq!(
+        fault.to_string(),
+        "Illegal opcode 0xFD at 0x4100 (ROM bank 2)"
+    );
+
+    // The CPU stays locked & ignores interrupts, but the rest of the machine keeps running.
+    cpu.interrupts_enabled = true;
+    cpu.mmu.write_byte(0xFFFF, 0b0000_0100);
+    cpu.mmu.write_byte(0xFF05, 0xFF);
+    cpu.mmu.write_byte(0xFF07, 0b0000_0101);
+    for _ in 0..8 {
+        assert_eq!(cpu.cycle(false, false).0, 4);
+    }
+    assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0000_0100, 0b0000_0100);
+    assert_eq!(cpu.pc, 0x4100);
+    assert_eq!(cpu.sp, 0xFFFE);
+    assert_eq!(cpu.fault, Some(fault));
+}
+
+#[test]
+fn test_halt() {
+    fn halt_cpu(program: &[u8], interrupts_enabled: bool, if_reg: u8) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(0xC000, program);
+        cpu.pc = 0xC000;
+        cpu.sp = 0xDFF0;
+        cpu.interrupts_enabled = interrupts_enabled;
+        cpu.mmu.write_byte(0xFFFF, 0b0000_0001);
+        cpu.mmu.write_byte(0xFF0F, if_reg);
+        cpu
+    }
+
+    // HALT; INC A: the CPU halts until an interrupt is requested, then dispatches it.
+    let mut cpu = halt_cpu(&[0x76, 0x3C], true, 0b0000_0000);
+    cpu.cycle(false, false);
+    assert!(cpu.is_halted);
+    assert_eq!(cpu.cycle(false, false).0, 4);
+    assert_eq!(cpu.pc, 0xC001);
+    cpu.mmu.write_byte(0xFF0F, 0b0000_0001);
+    assert_eq!(cpu.cycle(false, false).0, 24);
+    assert_eq!(cpu.pc, 0x0040);
+    assert_eq!(cpu.pop_stack(), 0xC001);
+
+    // HALT; INC A with interrupts disabled: the interrupt ends HALT without being dispatched.
+    let mut cpu = halt_cpu(&[0x76, 0x3C], false, 0b0000_0000);
+    cpu.regs.set_reg(A, 0x00);
+    cpu.cycle(false, false);
+    assert!(cpu.is_halted);
+    cpu.mmu.write_byte(0xFF0F, 0b0000_0001);
+    assert_eq!(cpu.cycle(false, false).0, 4);
+    assert!(!cpu.is_halted);
+    assert_eq!(cpu.pc, 0xC001);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.pc, 0xC002);
+    assert_eq!(cpu.regs.get_reg(A), 0x01);
+    assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b0001_1111, 0b0000_0001);
+}
+
+#[test]
+fn test_halt_bug() {
+    fn halt_bug_cpu(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(0xC000, program);
+        cpu.pc = 0xC000;
+        cpu.sp = 0xDFF0;
+        cpu.mmu.write_byte(0xFFFF, 0b0000_0001);
+        cpu.mmu.write_byte(0xFF0F, 0b0000_0001);
+        cpu
+    }
+
+    // HALT; INC A: INC A is executed twice.
+    let mut cpu = halt_bug_cpu(&[0x76, 0x3C, 0x00]);
+    cpu.regs.set_reg(A, 0x00);
+    cpu.cycle(false, false);
+    assert!(!cpu.is_halted);
+    assert_eq!(cpu.pc, 0xC001);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.pc, 0xC001);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.pc, 0xC002);
+    assert_eq!(cpu.regs.get_reg(A), 0x02);
+
+    // HALT; LD A,0x14: the opcode is read as the operand, then 0x14 (INC D) is executed.
+    let mut cpu = halt_bug_cpu(&[0x76, 0x3E, 0x14]);
+    cpu.regs.set_reg(D, 0x00);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.cycle(false, false).0, 8);
+    assert_eq!(cpu.regs.get_reg(A), 0x3E);
+    assert_eq!(cpu.pc, 0xC002);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.pc, 0xC003);
+    assert_eq!(cpu.regs.get_reg(D), 0x01);
+
+    // EI; HALT: the interrupt is dispatched & returns to the HALT.
+    let mut cpu = halt_bug_cpu(&[0xFB, 0x76, 0x00]);
+    cpu.cycle(false, false);
+    cpu.cycle(false, false);
+    assert!(!cpu.is_halted);
+    assert_eq!(cpu.cycle(false, false).0, 20);
+    assert_eq!(cpu.pc, 0x0040);
+    assert_eq!(cpu.pop_stack(), 0xC001);
+    assert!(!cpu.halt_bug);
+}
+
+#[test]
+fn test_stop() {
+    fn stop_cpu(if_reg: u8) -> Cpu {
+        let mut cpu = Cpu::new();
+        // 64 NOPs so DIV is 0x01, then STOP; NOP
+        let mut program = vec![0x00; 64];
+        program.extend([0x10, 0x00, 0x00]);
+        cpu.load(0xC000, &program);
+        cpu.pc = 0xC000;
+        for _ in 0..64 {
+            cpu.cycle(false, false);
+        }
+        assert_eq!(cpu.mmu.read_byte(0xFF04), 0x01);
+        cpu.mmu.write_byte(0xFFFF, 0b0000_0100);
+        cpu.mmu.write_byte(0xFF0F, if_reg);
+        cpu
+    }
+
+    // No button held, no interrupt pending: enter STOP mode until a selected button is pressed.
+    let mut cpu = stop_cpu(0b0000_0000);
+    cpu.mmu.write_byte(0xFF00, 0b0001_0000);
+    cpu.cycle(false, false);
+    assert!(cpu.is_stopped);
+    assert_eq!(cpu.pc, 0xC042);
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0x00);
+    assert!(cpu.get_pixels().iter().all(|pixel| *pixel == 0));
+    // The machine's clock is stopped.
+    for _ in 0..128 {
+        assert_eq!(cpu.cycle(false, false).0, 4);
+    }
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0x00);
+    assert_eq!(cpu.pc, 0xC042);
+    // Pressing an unselected button does nothing.
+    cpu.button_down(Button::Up, false);
+    cpu.cycle(false, false);
+    assert!(cpu.is_stopped);
+    cpu.button_down(Button::A, false);
+    cpu.cycle(false, false);
+    assert!(!cpu.is_stopped);
+    cpu.cycle(false, false);
+    assert_eq!(cpu.pc, 0xC043);
+
+    // No button held, interrupt pending: STOP is 1 byte.
+    let mut cpu = stop_cpu(0b0000_0100);
+    cpu.cycle(false, false);
+    assert!(cpu.is_stopped);
+    assert_eq!(cpu.pc, 0xC041);
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0x00);
+
+    // Button held, no interrupt pending: enter HALT mode & leave DIV alone.
+    let mut cpu = stop_cpu(0b0000_0000);
+    cpu.mmu.write_byte(0xFF00, 0b0001_0000);
+    cpu.button_down(Button::Start, false);
+    cpu.cycle(false, false);
+    assert!(!cpu.is_stopped);
+    assert!(cpu.is_halted);
+    assert_eq!(cpu.pc, 0xC042);
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0x01);
+
+    // Button held, interrupt pending: STOP is a 1-byte NOP.
+    let mut cpu = stop_cpu(0b0000_0100);
+    cpu.mmu.write_byte(0xFF00, 0b0001_0000);
+    cpu.button_down(Button::Start, false);
+    cpu.cycle(false, false);
+    assert!(!cpu.is_stopped && !cpu.is_halted);
+    assert_eq!(cpu.pc, 0xC041);
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0x01);
+}
+
+#[test]
+fn test_speed_switch() {
+    fn speed_switch_cpu(cgb_flag: CgbFlag) -> Cpu {
+        let rom = RomBuilder::new(0x00).cgb_flag(cgb_flag).build();
+        let mut cpu = Cpu::new_cart_bytes(rom).unwrap();
+        // STOP; NOP; STOP; NOP
+        cpu.load(0xC000, &[0x10, 0x00, 0x00, 0x10, 0x00, 0x00]);
+        cpu.pc = 0xC000;
+        cpu.mmu.write_byte(0xFF0F, 0b0000_0000);
+        cpu
+    }
+
+    let mut cpu = speed_switch_cpu(CgbFlag::CgbBkwd);
+    assert_eq!(cpu.mmu.read_byte(0xFF4D), 0b0111_1110);
+    cpu.mmu.write_byte(0xFF4D, 0b0000_0001);
+    assert_eq!(cpu.mmu.read_byte(0xFF4D), 0b0111_1111);
+
+    // The CPU is paused during the switch. Only the opcode fetch is at normal speed.
+    assert_eq!(cpu.cycle(false, false).0, 4 + SPEED_SWITCH_M_CYCLES * 2);
+    assert!(cpu.mmu.double_speed());
+    assert!(!cpu.is_stopped && !cpu.is_halted);
+    assert_eq!(cpu.mmu.read_byte(0xFF4D), 0b1111_1110);
+    assert_eq!(cpu.pc, 0xC002);
+
+    // NOP takes half as long relative to the PPU, but the timer runs at CPU speed.
+    cpu.mmu.write_byte(0xFF04, 0x00);
+    assert_eq!(cpu.cycle(false, false).0, 2);
+    cpu.pc = 0xC002;
+    for _ in 0..63 {
+        cpu.cycle(false, false);
+        cpu.pc = 0xC002;
+    }
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0x01);
+
+    // Switch back to normal speed.
+    cpu.pc = 0xC003;
+    cpu.mmu.write_byte(0xFF4D, 0b0000_0001);
+    cpu.cycle(false, false);
+    assert!(!cpu.mmu.double_speed());
+    assert_eq!(cpu.cycle(false, false).0, 4);
+
+    // DMG cartridges can't switch speed, so STOP enters STOP mode.
+    let mut cpu = speed_switch_cpu(CgbFlag::Dmg);
+    cpu.mmu.write_byte(0xFF4D, 0b0000_0001);
+    assert_eq!(cpu.mmu.read_byte(0xFF4D), 0xFF);
+    cpu.cycle(false, false);
+    assert!(!cpu.mmu.double_speed());
+    assert!(cpu.is_stopped);
 }
@@ -38,6 +38,11 @@ impl Joypad {
         self.update();
     }
 
+    /// Whether any of the selected input lines are low, i.e. a selected button is pressed.
+    pub fn input_low(&self) -> bool {
+        self.data.read_byte() & 0b0000_1111 != 0b0000_1111
+    }
+
     /// Update Joypad state to match the pressed buttons.
     fn update(&mut self) {
         let old_vals = self.data.read_byte() & 0b0000_1111;
@@ -4,6 +4,7 @@
 mod audio;
 mod boot;
 mod cartridge;
+mod cheats;
 mod cpu;
 mod flags;
 mod ie_register;
@@ -17,8 +18,13 @@ mod timer;
 // Re-exports
 pub use audio::Audio;
 pub use boot::{DMG_BOOT, DMG_BOOT_SIZE};
-pub use cartridge::Cartridge;
-pub use cpu::{Cpu, EmuState};
+pub use cartridge::{
+    apply_patch, load_cartridge, load_cartridge_bytes, load_cartridge_rom, read_rom_file,
+    CartFeatures, Cartridge, CartridgeError, CartridgeHeader, CgbFlag, Destination, ImageSource,
+    LoadWarning, MapperState, RomBuilder, StillImage, CAMERA_HEIGHT, CAMERA_WIDTH,
+};
+pub use cheats::{Cheat, CheatEntry, CheatError, Cheats};
+pub use cpu::{Cpu, CpuFault, EmuState};
 pub use flags::*;
 pub use joypad::{Button, Joypad};
 pub use mmu::Mmu;
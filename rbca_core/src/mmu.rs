@@ -3,8 +3,8 @@ use std::{fs::File, io::Read};
 use camino::Utf8Path;
 
 use crate::{
-    cartridge::{self, CartEmpty, Cartridge},
-    Audio, Flags, FlagsEnum, Joypad, Timer, PPU,
+    cartridge::{self, CartEmpty, Cartridge, CartridgeError, LoadWarning},
+    Audio, CgbFlag, Cheats, Flags, FlagsEnum, Joypad, Timer, PPU,
 };
 
 const OAM_DMA_TRANSFER_T_CYCLES: u16 = 640;
@@ -42,36 +42,87 @@ pub struct Mmu {
     ie_reg: Flags,
     /// Remaining OAM DMA transfer cycles. 0 = no transfer currently happening.
     oam_dma_remaining_cycles: u16,
+    /// Set when cartridge save data has changed. Cleared by the frontend once saved.
+    pub cart_ram_dirty: bool,
+    /// Active cheat codes.
+    pub cheats: Cheats,
+    /// Problems with the cartridge & boot ROM dumps that were worked around when the [Mmu] was
+    /// created. Left for the frontend to report.
+    pub load_warnings: Vec<LoadWarning>,
+    /// Whether the machine runs in CGB mode. Decided by the cartridge at power-on.
+    cgb_mode: bool,
+    /// (CGB mode only) Whether the CPU runs at double speed.
+    double_speed: bool,
+    /// (CGB mode only) KEY1 bit 0. If 1, the next STOP switches speed.
+    speed_switch_armed: bool,
 }
 impl Mmu {
     /// Create a new [Mmu] with a cartridge and a boot ROM.
-    pub fn new_boot_cart<P: AsRef<Utf8Path>>(cart_path: P, boot_rom_path: P) -> Self {
-        Self::new_helper(
-            cartridge::load_cartridge(cart_path),
-            Some(load_boot_rom(boot_rom_path)),
-        )
+    pub fn new_boot_cart<P: AsRef<Utf8Path>>(
+        cart_path: P,
+        boot_rom_path: P,
+    ) -> Result<Self, CartridgeError> {
+        let (cart, mut load_warnings) = cartridge::load_cartridge(cart_path)?;
+        let (boot_rom, boot_rom_warning) = load_boot_rom(boot_rom_path)?;
+        load_warnings.extend(boot_rom_warning);
+        Ok(Self::new_helper(cart, Some(boot_rom), load_warnings))
     }
 
     /// Create a new [Mmu] without loading a cartridge.
-    pub fn new_boot<P: AsRef<Utf8Path>>(boot_rom_path: P) -> Self {
-        Self::new_helper(
+    pub fn new_boot<P: AsRef<Utf8Path>>(boot_rom_path: P) -> Result<Self, CartridgeError> {
+        let (boot_rom, boot_rom_warning) = load_boot_rom(boot_rom_path)?;
+        Ok(Self::new_helper(
             Box::new(CartEmpty::new()),
-            Some(load_boot_rom(boot_rom_path)),
-        )
+            Some(boot_rom),
+            boot_rom_warning.into_iter().collect(),
+        ))
     }
 
     /// Create a new [Mmu] without a boot ROM.
-    pub fn new_cart<P: AsRef<Utf8Path>>(cart_path: P) -> Self {
-        Self::new_helper(cartridge::load_cartridge(cart_path), None)
+    pub fn new_cart<P: AsRef<Utf8Path>>(cart_path: P) -> Result<Self, CartridgeError> {
+        let (cart, load_warnings) = cartridge::load_cartridge(cart_path)?;
+        Ok(Self::new_helper(cart, None, load_warnings))
+    }
+
+    /// Create a new [Mmu] with a cartridge and a boot ROM from raw data.
+    pub fn new_boot_cart_bytes<D: Into<Vec<u8>>>(
+        cart_data: D,
+        boot_rom_data: &[u8],
+    ) -> Result<Self, CartridgeError> {
+        let (cart, mut load_warnings) = cartridge::load_cartridge_rom(cart_data.into())?;
+        let (boot_rom, boot_rom_warning) = boot_rom_from_bytes(boot_rom_data)?;
+        load_warnings.extend(boot_rom_warning);
+        Ok(Self::new_helper(cart, Some(boot_rom), load_warnings))
+    }
+
+    /// Create a new [Mmu] from raw boot ROM data without loading a cartridge.
+    pub fn new_boot_bytes(boot_rom_data: &[u8]) -> Result<Self, CartridgeError> {
+        let (boot_rom, boot_rom_warning) = boot_rom_from_bytes(boot_rom_data)?;
+        Ok(Self::new_helper(
+            Box::new(CartEmpty::new()),
+            Some(boot_rom),
+            boot_rom_warning.into_iter().collect(),
+        ))
+    }
+
+    /// Create a new [Mmu] from raw cartridge data without a boot ROM.
+    pub fn new_cart_bytes<D: Into<Vec<u8>>>(cart_data: D) -> Result<Self, CartridgeError> {
+        let (cart, load_warnings) = cartridge::load_cartridge_rom(cart_data.into())?;
+        Ok(Self::new_helper(cart, None, load_warnings))
     }
 
     /// Create a new [Mmu] without loading a cartridge or a boot ROM.
     pub fn new() -> Self {
-        Self::new_helper(Box::new(CartEmpty::new()), None)
+        Self::new_helper(Box::new(CartEmpty::new()), None, vec![])
     }
 
-    fn new_helper(cart: Box<dyn Cartridge>, boot_rom: Option<[u8; 0x0100]>) -> Self {
+    fn new_helper(
+        cart: Box<dyn Cartridge>,
+        boot_rom: Option<[u8; 0x0100]>,
+        load_warnings: Vec<LoadWarning>,
+    ) -> Self {
         let have_boot_rom = boot_rom.is_some();
+        let cgb_mode = cart.header().cgb_flag != CgbFlag::Dmg;
         Self {
             cart,
             boot_rom,
@@ -88,9 +139,44 @@ impl Mmu {
             hram: [0xFF; 0x007F],
             ie_reg: Flags::new(0b0000_0000),
             oam_dma_remaining_cycles: 0,
+            cart_ram_dirty: false,
+            cheats: Cheats::new(),
+            load_warnings,
+            cgb_mode,
+            double_speed: false,
+            speed_switch_armed: false,
         }
     }
 
+    /// Replace the cartridge while the machine is running, like pulling it out & pushing another
+    /// one in. Everything else, including WRAM, HRAM & VRAM, is left intact. Return the outgoing
+    /// cartridge so its save data can be flushed.
+    pub fn swap_cartridge(&mut self, new: Box<dyn Cartridge>) -> Box<dyn Cartridge> {
+        self.cart_ram_dirty = false;
+        std::mem::replace(&mut self.cart, new)
+    }
+
+    /// Whether the machine runs in CGB mode.
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    /// Whether the CPU runs at double speed. Only possible in CGB mode.
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Whether the next STOP switches speed.
+    pub(crate) fn speed_switch_armed(&self) -> bool {
+        self.speed_switch_armed
+    }
+
+    /// Switch between normal & double speed, disarming the switch.
+    pub(crate) fn switch_speed(&mut self) {
+        self.double_speed = !self.double_speed;
+        self.speed_switch_armed = false;
+    }
+
     /// Directly read the byte at the given address.
     pub fn read_byte(&self, address: u16) -> u8 {
         // Can only access HRAM during OAM DMA transfer
@@ -104,14 +190,14 @@ impl Mmu {
         match address {
             0x0000..=0x00FF => {
                 if self.read_byte(0xFF50) != 0 {
-                    return self.cart.read_rom(address);
+                    return self.read_rom(address);
                 }
                 if let Some(boot_rom) = self.boot_rom {
                     return boot_rom[address as usize];
                 }
-                self.cart.read_rom(address)
+                self.read_rom(address)
             }
-            0x0100..=0x7FFF => self.cart.read_rom(address),
+            0x0100..=0x7FFF => self.read_rom(address),
             0x8000..=0x9FFF => self.ppu.read_byte(address),
             0xA000..=0xBFFF => self.cart.read_ram(address),
             0xC000..=0xDFFF => self.wram[address as usize - 0xC000],
@@ -129,7 +215,9 @@ impl Mmu {
             0xFF10..=0xFF26 => self.audio.read_byte(address),
             0xFF27..=0xFF2F => 0xFF,
             0xFF30..=0xFF3F => self.audio.read_byte(address),
-            0xFF40..=0xFF4F => self.ppu.read_byte(address),
+            0xFF40..=0xFF4C => self.ppu.read_byte(address),
+            0xFF4D => self.read_key1(),
+            0xFF4E..=0xFF4F => self.ppu.read_byte(address),
             0xFF50 => self.disable_boot_rom,
             0xFF51..=0xFF55 => self.ppu.read_byte(address),
             0xFF56..=0xFF67 => 0xFF,
@@ -163,9 +251,16 @@ impl Mmu {
                     self.cart.write_rom(address, value);
                 }
             }
-            0x0100..=0x7FFF => self.cart.write_rom(address, value),
+            // Some carts (e.g. MBC6 flash) store save data through writes to this range
+            0x0100..=0x7FFF => {
+                self.cart.write_rom(address, value);
+                self.cart_ram_dirty |= self.cart.take_save_dirty();
+            }
             0x8000..=0x9FFF => self.ppu.write_byte(address, value),
-            0xA000..=0xBFFF => self.cart.write_ram(address, value),
+            0xA000..=0xBFFF => {
+                self.cart.write_ram(address, value);
+                self.cart_ram_dirty |= self.cart.take_save_dirty();
+            }
             0xC000..=0xDDFF => {
                 self.wram[address as usize - 0xC000] = value;
                 self.eram[address as usize - 0xC000] = value;
@@ -186,7 +281,13 @@ impl Mmu {
             0xFF30..=0xFF3F => self.audio.write_byte(address, value),
             0xFF40..=0xFF45 => self.ppu.write_byte(address, value),
             0xFF46 => self.oam_dma_transfer(value),
-            0xFF47..=0xFF4F => self.ppu.write_byte(address, value),
+            0xFF47..=0xFF4C => self.ppu.write_byte(address, value),
+            0xFF4D => {
+                if self.cgb_mode {
+                    self.speed_switch_armed = value & 0b0000_0001 != 0;
+                }
+            }
+            0xFF4E..=0xFF4F => self.ppu.write_byte(address, value),
             0xFF50 => self.disable_boot_rom = value,
             0xFF51..=0xFF55 => self.ppu.write_byte(address, value),
             0xFF56..=0xFF67 => {}
@@ -205,8 +306,17 @@ impl Mmu {
         self.write_byte(start_address + 1, (value >> 8) as u8);
     }
 
-    /// Perform one full cycle, returning the PPU t-cycles.
+    /// Perform one full cycle of the given number of CPU t-cycles, returning the PPU t-cycles.
+    ///
+    /// In double-speed mode, the timer & OAM DMA run at CPU speed, but everything else runs at
+    /// normal speed, so only half as many t-cycles pass for it.
     pub fn cycle(&mut self, t_cycles: u32) -> u32 {
+        let ppu_t_cycles = if self.double_speed {
+            t_cycles / 2
+        } else {
+            t_cycles
+        };
+
         // Advance time for OAM DMA.
         if self.oam_dma_remaining_cycles > 0 {
             self.oam_dma_remaining_cycles = self
@@ -225,20 +335,60 @@ impl Mmu {
         self.joypad.interrupt_flags.write_byte(0x00);
 
         // Cycle the PPU.
-        self.ppu.cycle(t_cycles);
+        self.ppu.cycle(ppu_t_cycles);
+        // GameShark codes are applied once per frame.
+        if self.ppu.interrupt_flags.get(If::VBlank) {
+            self.apply_gameshark_cheats();
+        }
         // Update IF register if the PPU triggered any interrupts.
         self.if_reg |= self.ppu.interrupt_flags;
         self.ppu.interrupt_flags.write_byte(0x00);
         self.ppu.stat_interrupt_line = false;
 
         // TODO cycle sound.
-        self.audio.cycle(t_cycles);
+        self.audio.cycle(ppu_t_cycles);
+
+        // Cycle the cartridge hardware. Some carts (e.g. the Pocket Camera) write save data on
+        // their own.
+        self.cart.cycle(ppu_t_cycles);
+        self.cart_ram_dirty |= self.cart.take_save_dirty();
 
         // TODO check for serial interrupts.
 
+        ppu_t_cycles
+    }
+
+    /// Perform one cycle while the CPU is stopped, returning the PPU t-cycles. The machine's clock
+    /// is stopped, so only the cartridge hardware (e.g. a real-time clock) advances.
+    pub fn stopped_cycle(&mut self, t_cycles: u32) -> u32 {
+        self.cart.cycle(t_cycles);
+        self.cart_ram_dirty |= self.cart.take_save_dirty();
         t_cycles
     }
 
+    /// Read the KEY1 (prepare speed switch) register.
+    fn read_key1(&self) -> u8 {
+        if !self.cgb_mode {
+            return 0xFF;
+        }
+        0b0111_1110 | ((self.double_speed as u8) << 7) | (self.speed_switch_armed as u8)
+    }
+
+    /// Read from cartridge ROM, applying any Game Genie codes. The codes' compare bytes are
+    /// checked against the byte in the currently mapped bank.
+    fn read_rom(&self, address: u16) -> u8 {
+        self.cheats.patch_rom(address, self.cart.read_rom(address))
+    }
+
+    /// Force the RAM values of the enabled GameShark codes.
+    fn apply_gameshark_cheats(&mut self) {
+        let ram_bank = self.cart.mapper_state().ram_bank;
+        // WRAM isn't banked, so bank 1 is always at 0xD000.
+        for (address, value) in self.cheats.gameshark_writes(ram_bank, 1) {
+            self.write_byte(address, value);
+        }
+    }
+
     /// Perform an OAM DMA transfer.
     fn oam_dma_transfer(&mut self, address: u8) {
         let start_addr = (address as u16) << 8;
@@ -252,14 +402,30 @@ impl Mmu {
     }
 }
 
-fn load_boot_rom<P: AsRef<Utf8Path>>(filepath: P) -> [u8; 0x0100] {
+/// Load a boot ROM from a file. Also return a warning if it had to be truncated.
+fn load_boot_rom<P: AsRef<Utf8Path>>(
+    filepath: P,
+) -> Result<([u8; 0x0100], Option<LoadWarning>), CartridgeError> {
     let mut file_buf = vec![];
-    if let Err(e) = File::open(filepath.as_ref()).and_then(|mut f| f.read_to_end(&mut file_buf)) {
-        panic!("Boot ROM load fail: {e} {}", filepath.as_ref());
+    File::open(filepath.as_ref())
+        .and_then(|mut f| f.read_to_end(&mut file_buf))
+        .map_err(|e| CartridgeError::io(&filepath, e))?;
+    boot_rom_from_bytes(&file_buf)
+}
+
+/// Load a boot ROM from raw data. Also return a warning if it had to be truncated.
+fn boot_rom_from_bytes(data: &[u8]) -> Result<([u8; 0x0100], Option<LoadWarning>), CartridgeError> {
+    if data.len() < 0x0100 {
+        return Err(CartridgeError::TruncatedHeader {
+            expected: 0x0100,
+            actual: data.len(),
+        });
     }
+    let warning =
+        (data.len() > 0x0100).then_some(LoadWarning::BootRomTruncated { actual: data.len() });
     let mut boot_rom_data: [u8; 0x0100] = [0x00; 0x0100];
-    boot_rom_data.copy_from_slice(&file_buf[..0x0100]);
-    boot_rom_data
+    boot_rom_data.copy_from_slice(&data[..0x0100]);
+    Ok((boot_rom_data, warning))
 }
 
 /// Interrupt flags enum. Controls whether the different interrupt handlers are being requested.
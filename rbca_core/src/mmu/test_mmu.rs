@@ -305,3 +305,139 @@ fn test_oam_dma_transfer() {
     mmu.oam_dma_transfer(0xC0);
     assert_eq!(mmu.oam_dma_remaining_cycles, 640);
 }
+
+#[test]
+fn test_cheats() {
+    let mut mmu = Mmu::new();
+    mmu.write_byte(0x4A17, 0xC8);
+    mmu.write_byte(0x0F0B, 0x12);
+    mmu.cheats.add("00A-17B-C49").unwrap();
+    mmu.cheats.add("3EF-0BF").unwrap();
+    mmu.cheats.add("01AB00C0").unwrap();
+
+    // Game Genie
+    assert_eq!(mmu.read_byte(0x4A17), 0x00);
+    assert_eq!(mmu.read_byte(0x0F0B), 0x3E);
+    // A different byte is mapped in: compare fails
+    mmu.write_byte(0x4A17, 0xC9);
+    assert_eq!(mmu.read_byte(0x4A17), 0xC9);
+
+    // GameShark is applied on the next frame
+    mmu.write_byte(0xFF40, 0b1000_0000);
+    mmu.write_byte(0xC000, 0x01);
+    assert_eq!(mmu.read_byte(0xC000), 0x01);
+    for _ in 0..70224 / 4 {
+        mmu.cycle(4);
+    }
+    assert_eq!(mmu.read_byte(0xC000), 0xAB);
+
+    mmu.cheats.clear();
+    assert_eq!(mmu.read_byte(0x0F0B), 0x12);
+}
+
+#[test]
+fn test_swap_cartridge() {
+    let rom = crate::RomBuilder::new(0x03)
+        .rom_size(0x01)
+        .ram_size(0x02)
+        .title("OLD")
+        .build();
+    let mut mmu = Mmu::new_cart_bytes(rom).unwrap();
+    mmu.write_byte(0x0000, 0x0A);
+    mmu.write_byte(0xA000, 0x42);
+    mmu.write_byte(0xC123, 0x12);
+    mmu.write_byte(0xFF85, 0x34);
+    mmu.write_byte(0x8010, 0x56);
+
+    let rom = crate::RomBuilder::new(0x00)
+        .title("NEW")
+        .code(0, 0x0150, &[0x99])
+        .build();
+    let old = mmu.swap_cartridge(cartridge::load_cartridge_bytes(rom).unwrap().0);
+    assert_eq!(old.header().title, "OLD");
+    assert_eq!(old.ram()[0], 0x42);
+    assert!(!mmu.cart_ram_dirty);
+
+    assert_eq!(mmu.cart.header().title, "NEW");
+    assert_eq!(mmu.read_byte(0x0150), 0x99);
+    assert_eq!(mmu.read_byte(0xC123), 0x12);
+    assert_eq!(mmu.read_byte(0xFF85), 0x34);
+    assert_eq!(mmu.read_byte(0x8010), 0x56);
+}
+
+#[test]
+fn test_load_warnings() {
+    let rom = crate::RomBuilder::new(0x01).rom_size(0x01).build();
+    let mmu = Mmu::new_boot_cart_bytes(rom.clone(), &crate::DMG_BOOT).unwrap();
+    assert!(mmu.load_warnings.is_empty());
+
+    let mut boot_rom = crate::DMG_BOOT.to_vec();
+    boot_rom.extend([0x00; 0x10]);
+    let mmu = Mmu::new_boot_cart_bytes(&rom[..0x8000], &boot_rom).unwrap();
+    assert_eq!(
+        mmu.load_warnings,
+        vec![
+            LoadWarning::RomResized {
+                actual: 0x8000,
+                expected: 0x1_0000
+            },
+            LoadWarning::BootRomTruncated { actual: 0x0110 },
+        ]
+    );
+}
+
+#[test]
+fn test_cart_ram_dirty() {
+    // MBC3+TIMER+RAM+BATTERY, 64 KiB ROM, 8 KiB RAM
+    let rom = crate::RomBuilder::new(0x10)
+        .rom_size(0x01)
+        .ram_size(0x02)
+        .build();
+    let mut mmu = Mmu::new_cart_bytes(rom).unwrap();
+
+    // Bank switches & writes to disabled RAM don't change save data.
+    mmu.write_byte(0x2000, 0x02);
+    mmu.write_byte(0x4000, 0x00);
+    mmu.write_byte(0x6000, 0x00);
+    mmu.write_byte(0x6000, 0x01);
+    mmu.write_byte(0xA000, 0x42);
+    mmu.cycle(4);
+    assert!(!mmu.cart_ram_dirty);
+
+    // Enabled RAM writes do.
+    mmu.write_byte(0x0000, 0x0A);
+    assert!(!mmu.cart_ram_dirty);
+    mmu.write_byte(0xA000, 0x42);
+    assert!(mmu.cart_ram_dirty);
+    assert_eq!(mmu.read_byte(0xA000), 0x42);
+
+    // So do clock register writes.
+    mmu.cart_ram_dirty = false;
+    mmu.write_byte(0x4000, 0x08);
+    assert!(!mmu.cart_ram_dirty);
+    mmu.write_byte(0xA000, 0x05);
+    assert!(mmu.cart_ram_dirty);
+}
+
+#[test]
+fn test_gameshark_ram_bank() {
+    // MBC5+RAM+BATTERY, 64 KiB ROM, 32 KiB RAM
+    let rom = crate::RomBuilder::new(0x1B)
+        .rom_size(0x01)
+        .ram_size(0x03)
+        .build();
+    let mut mmu = Mmu::new_cart_bytes(rom).unwrap();
+    mmu.cheats.add("824200A0").unwrap();
+    mmu.write_byte(0x0000, 0x0A);
+
+    // Bank 0 is mapped: the bank 2 code doesn't touch it.
+    mmu.write_byte(0x4000, 0x00);
+    mmu.apply_gameshark_cheats();
+    assert_eq!(mmu.read_byte(0xA000), 0x00);
+
+    mmu.write_byte(0x4000, 0x02);
+    mmu.apply_gameshark_cheats();
+    assert_eq!(mmu.read_byte(0xA000), 0x42);
+    assert_eq!(mmu.cart.ram_bank(0).unwrap()[0], 0x00);
+    assert_eq!(mmu.cart.ram_bank(2).unwrap()[0], 0x42);
+}
@@ -12,7 +12,7 @@ fn test_boot_rom() {
     const SLOW: bool = false;
     const WAIT_MS: u64 = 10;
 
-    let mut cpu = Cpu::new_boot_cart("../roms/test_mbc1.gb", "../dmg-boot.bin");
+    let mut cpu = Cpu::new_boot_cart("../roms/test_mbc1.gb", "../dmg-boot.bin").unwrap();
     println!("{}", cpu.mmu.cart.header_info());
     println!(
         "Instruction Debug: {}, Slow: {}",
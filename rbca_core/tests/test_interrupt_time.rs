@@ -2,13 +2,10 @@
 
 mod common;
 
-use common::blargg_test_common;
+use common::blargg_test_headless;
 
 #[test]
 #[ignore]
 fn test_interrupt_timing() {
-    blargg_test_common(
-        "INTERRUPT_TIME",
-        "../roms/gb-test-roms/interrupt_time/interrupt_time.gb",
-    );
+    blargg_test_headless("../roms/gb-test-roms/interrupt_time/interrupt_time.gb");
 }
@@ -0,0 +1,22 @@
+#![cfg(test)]
+
+use rbca_core::{Cpu, RomBuilder};
+
+#[test]
+fn test_banked_code() {
+    // MBC1, 128 KiB ROM
+    let rom = RomBuilder::new(0x01)
+        .rom_size(0x02)
+        // LD A,0x05; LD (0x2000),A; JP 0x4000
+        .code(0, 0x0150, &[0x3E, 0x05, 0xEA, 0x00, 0x20, 0xC3, 0x00, 0x40])
+        // LD A,0x42; LD (0xC000),A; JR -2
+        .code(5, 0x4000, &[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x18, 0xFE])
+        .build();
+    let mut cpu = Cpu::new_cart_bytes(rom).unwrap();
+    assert!(cpu.mmu.cart.header().global_checksum_valid);
+
+    for _ in 0..10 {
+        cpu.cycle(false, false);
+    }
+    assert_eq!(cpu.mmu.read_byte(0xC000), 0x42);
+}
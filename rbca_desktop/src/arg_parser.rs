@@ -15,4 +15,11 @@ pub struct Args {
     /// If no ROM is provided, it will boot into the boot ROM. If no boot ROM is provided, it will
     /// still boot but do nothing.
     pub rom_path: Option<Utf8PathBuf>,
+
+    /// Path to an IPS, UPS or BPS patch to apply to the ROM.
+    ///
+    /// If no patch is provided, a patch next to the ROM with the same name is applied if there is
+    /// one.
+    #[arg(short, long, requires = "rom_path")]
+    pub patch: Option<Utf8PathBuf>,
 }
@@ -32,6 +32,30 @@ pub struct KeyBindings {
     pub start: Scancode,
     #[serde(with = "scancodes")]
     pub select: Scancode,
+    #[serde(with = "scancodes", default = "default_tilt_up")]
+    pub tilt_up: Scancode,
+    #[serde(with = "scancodes", default = "default_tilt_down")]
+    pub tilt_down: Scancode,
+    #[serde(with = "scancodes", default = "default_tilt_left")]
+    pub tilt_left: Scancode,
+    #[serde(with = "scancodes", default = "default_tilt_right")]
+    pub tilt_right: Scancode,
+}
+
+fn default_tilt_up() -> Scancode {
+    Scancode::Kp8
+}
+
+fn default_tilt_down() -> Scancode {
+    Scancode::Kp2
+}
+
+fn default_tilt_left() -> Scancode {
+    Scancode::Kp4
+}
+
+fn default_tilt_right() -> Scancode {
+    Scancode::Kp6
 }
 
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -39,6 +63,7 @@ pub struct KeyBindings {
 pub struct PathSettings {
     pub boot_rom_path: Option<Utf8PathBuf>,
     pub saves_dir: Utf8PathBuf,
+    pub camera_image_path: Option<Utf8PathBuf>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -178,11 +203,36 @@ impl UserConfig {
         &self.key_bindings.select
     }
 
+    /// Directly access the "Tilt Up" scancode.
+    pub fn tilt_up_code(&self) -> &Scancode {
+        &self.key_bindings.tilt_up
+    }
+
+    /// Directly access the "Tilt Down" scancode.
+    pub fn tilt_down_code(&self) -> &Scancode {
+        &self.key_bindings.tilt_down
+    }
+
+    /// Directly access the "Tilt Left" scancode.
+    pub fn tilt_left_code(&self) -> &Scancode {
+        &self.key_bindings.tilt_left
+    }
+
+    /// Directly access the "Tilt Right" scancode.
+    pub fn tilt_right_code(&self) -> &Scancode {
+        &self.key_bindings.tilt_right
+    }
+
     /// Directly access the boot ROM path.
     pub fn boot_rom_path(&self) -> &Option<Utf8PathBuf> {
         &self.path_settings.boot_rom_path
     }
 
+    /// Directly access the camera image path.
+    pub fn camera_image_path(&self) -> &Option<Utf8PathBuf> {
+        &self.path_settings.camera_image_path
+    }
+
     /// Directly access the saves directory.
     pub fn saves_dir(&self) -> &Utf8PathBuf {
         &self.path_settings.saves_dir
@@ -253,6 +303,9 @@ impl UserConfig {
             self.path_settings.boot_rom_path = Some(utils::expand_path(boot_rom_path)?);
         }
         self.path_settings.saves_dir = utils::expand_path(&self.path_settings.saves_dir)?;
+        if let Some(camera_image_path) = &self.path_settings.camera_image_path {
+            self.path_settings.camera_image_path = Some(utils::expand_path(camera_image_path)?);
+        }
         Ok(())
     }
 }
@@ -1,27 +1,42 @@
-use std::{collections::VecDeque, time::Instant};
+use std::{
+    collections::VecDeque,
+    fs,
+    time::{Duration, Instant},
+};
 
+use camino::Utf8PathBuf;
 use color_eyre::eyre::{self, eyre};
 use rbca_core::{
+    load_cartridge,
     Button::{self, Down, Left, Right, Select, Start, Up, A, B},
-    Cpu, EmuState, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+    Cpu, CpuFault, EmuState, DISPLAY_HEIGHT, DISPLAY_WIDTH,
 };
 use sdl2::{
     event::Event, keyboard::Scancode, rect::Rect, render::Canvas, video::Window, EventPump,
 };
 use text_io::read;
 
-use super::{config::UserConfig, palette::hex_to_sdl};
+use super::{config::UserConfig, palette::hex_to_sdl, utils};
 
 const SCALE: u32 = 5;
 
 const WINDOW_WIDTH: u32 = (DISPLAY_WIDTH as u32) * SCALE;
 const WINDOW_HEIGHT: u32 = (DISPLAY_HEIGHT as u32) * SCALE;
 
+/// How long to wait after the last save flush before flushing cartridge RAM changes again.
+const SAVE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct Emulator<'a> {
     cpu: Cpu,
     canvas: Canvas<Window>,
     event_pump: EventPump,
     config: &'a UserConfig,
+    tilt_keys: TiltKeys,
+    /// Where the cartridge's save data is stored. None = cartridge has no save data.
+    save_path: Option<Utf8PathBuf>,
+    /// The save data as of the last flush.
+    last_save_data: Option<Vec<u8>>,
+    last_save_flush: Instant,
 }
 impl<'a> Emulator<'a> {
     pub fn new(cpu: Cpu, config: &'a UserConfig) -> eyre::Result<Self> {
@@ -48,25 +63,41 @@ impl<'a> Emulator<'a> {
             Err(e) => return Err(eyre!(e)),
         };
 
-        Ok(Self {
+        let mut emulator = Self {
             cpu,
             canvas,
             event_pump,
             config,
-        })
+            tilt_keys: TiltKeys::default(),
+            save_path: None,
+            last_save_data: None,
+            last_save_flush: Instant::now(),
+        };
+        emulator.load_save()?;
+        Ok(emulator)
     }
 
+    /// Run until the window is closed. The save data is flushed however the emulator stops, so an
+    /// error doesn't lose the latest save.
     pub fn run(&mut self) -> eyre::Result<()> {
+        let result = self.run_loop();
+        let flushed = self.flush_save();
+        result.and(flushed)
+    }
+
+    fn run_loop(&mut self) -> eyre::Result<()> {
         let mut cycles: u128 = 0;
         let mut frame_count: u128 = 0;
         let mut last_frame_time = Instant::now();
         let mut history: VecDeque<EmuState> = VecDeque::with_capacity(self.config.history());
         let mut step_forward: bool = false;
+        let mut last_fault: Option<CpuFault> = None;
 
         'main_loop: loop {
             let start = Instant::now();
 
             // Read key events
+            let mut dropped_file = None;
             for event in self.event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. }
@@ -81,6 +112,11 @@ impl<'a> Emulator<'a> {
                     } => {
                         if let Some(btn) = match_scancode_button(self.config, &sc) {
                             self.cpu.button_down(btn, self.config.btn_debug());
+                        } else if self.tilt_keys.update(self.config, &sc, true) {
+                            let (x, y) = self.tilt_keys.tilt();
+                            self.cpu.set_tilt(x, y);
+                        } else if let Some(index) = match_scancode_cheat(&sc) {
+                            toggle_cheat(&mut self.cpu, index);
                         }
                     }
                     Event::KeyUp {
@@ -88,18 +124,51 @@ impl<'a> Emulator<'a> {
                     } => {
                         if let Some(btn) = match_scancode_button(self.config, &sc) {
                             self.cpu.button_up(btn, self.config.btn_debug());
+                        } else if self.tilt_keys.update(self.config, &sc, false) {
+                            let (x, y) = self.tilt_keys.tilt();
+                            self.cpu.set_tilt(x, y);
                         }
                     }
+                    // Tilt towards the mouse, relative to the centre of the window.
+                    Event::MouseMotion { x, y, .. } => {
+                        let half_width = (WINDOW_WIDTH / 2) as f32;
+                        let half_height = (WINDOW_HEIGHT / 2) as f32;
+                        self.cpu.set_tilt(
+                            (x as f32 - half_width) / half_width,
+                            (y as f32 - half_height) / half_height,
+                        );
+                    }
+                    Event::DropFile { filename, .. } => {
+                        dropped_file = Some(filename);
+                    }
                     _ => {}
                 }
             }
 
+            // Load a ROM dragged onto the window. Hold shift to hot-swap instead of resetting.
+            if let Some(filename) = dropped_file {
+                let keyboard = self.event_pump.keyboard_state();
+                let swap = keyboard.is_scancode_pressed(Scancode::LShift)
+                    || keyboard.is_scancode_pressed(Scancode::RShift);
+                if let Err(e) = self.load_dropped_rom(Utf8PathBuf::from(&filename), swap) {
+                    eprintln!("Warning: couldn't load {filename}: {e}");
+                }
+            }
+
             // Cycle CPU
             let cycles_and_state = self
                 .cpu
                 .cycle(self.config.instr_debug(), self.config.breakpoints_enabled());
             cycles += cycles_and_state.0 as u128;
 
+            // Report the CPU locking up. The game keeps running, frozen, like on real hardware.
+            if self.cpu.fault != last_fault {
+                if let Some(fault) = self.cpu.fault {
+                    eprintln!("Warning: CPU locked up. {fault}");
+                }
+                last_fault = self.cpu.fault;
+            }
+
             if let Some(emu_state) = cycles_and_state.1 {
                 // Debug print.
                 if self.config.instr_debug() {
@@ -134,6 +203,71 @@ impl<'a> Emulator<'a> {
                 }
                 while start.elapsed().as_nanos() < 16_750_000 {}
             }
+
+            if self.cpu.mmu.cart_ram_dirty && self.last_save_flush.elapsed() >= SAVE_FLUSH_INTERVAL
+            {
+                self.flush_save()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the cartridge's save data from disk, if there is any.
+    ///
+    /// The save file is only used once it has been read, so a failure can't lead to the
+    /// cartridge's data being flushed over another game's save or the cartridge's own save.
+    fn load_save(&mut self) -> eyre::Result<()> {
+        self.save_path = None;
+        self.last_save_data = None;
+        let Some(save_path) = self
+            .cpu
+            .save_data()
+            .map(|_| self.config.saves_dir().join(self.cpu.save_file_name()))
+        else {
+            return Ok(());
+        };
+        if save_path.exists() {
+            self.cpu.load_save_data(&fs::read(&save_path)?);
+            self.last_save_data = self.cpu.save_data();
+        }
+        self.save_path = Some(save_path);
+        Ok(())
+    }
+
+    /// Load a new ROM. If `swap` is set, the cartridge is swapped without resetting the machine.
+    /// Otherwise, the machine restarts with the new cartridge. The outgoing cartridge's save data
+    /// is flushed first.
+    fn load_dropped_rom(&mut self, path: Utf8PathBuf, swap: bool) -> eyre::Result<()> {
+        self.flush_save()?;
+        if swap {
+            let (cart, load_warnings) = load_cartridge(&path)?;
+            self.cpu.swap_cartridge(cart);
+            self.cpu.mmu.load_warnings = load_warnings;
+        } else {
+            self.cpu = match self.config.boot_rom_path() {
+                Some(boot_path) => Cpu::new_boot_cart(&path, boot_path),
+                None => Cpu::new_cart(&path),
+            }?;
+        }
+        // Switch save files before anything else can fail.
+        self.load_save()?;
+        utils::setup_cart(&mut self.cpu, self.config)
+    }
+
+    /// Write the cartridge's save data to disk if it has changed since the last flush.
+    fn flush_save(&mut self) -> eyre::Result<()> {
+        self.cpu.mmu.cart_ram_dirty = false;
+        self.last_save_flush = Instant::now();
+
+        let Some(save_path) = &self.save_path else {
+            return Ok(());
+        };
+        let save_data = self.cpu.save_data();
+        if save_data != self.last_save_data {
+            if let Some(data) = &save_data {
+                fs::write(save_path, data)?;
+            }
+            self.last_save_data = save_data;
         }
         Ok(())
     }
@@ -259,3 +393,66 @@ fn match_scancode_button(config: &UserConfig, sc: &Scancode) -> Option<Button> {
         None
     }
 }
+
+/// Turn the cheat at the given index on or off.
+fn toggle_cheat(cpu: &mut Cpu, index: usize) {
+    if let Some(enabled) = cpu.mmu.cheats.toggle(index) {
+        let code = &cpu.mmu.cheats.iter().nth(index).unwrap().code;
+        println!(
+            "Cheat {} ({code}): {}",
+            index + 1,
+            if enabled { "ON" } else { "OFF" }
+        );
+    }
+}
+
+/// F1-F9 toggle the first nine cheats.
+fn match_scancode_cheat(sc: &Scancode) -> Option<usize> {
+    [
+        Scancode::F1,
+        Scancode::F2,
+        Scancode::F3,
+        Scancode::F4,
+        Scancode::F5,
+        Scancode::F6,
+        Scancode::F7,
+        Scancode::F8,
+        Scancode::F9,
+    ]
+    .iter()
+    .position(|cheat_sc| cheat_sc == sc)
+}
+
+/// The tilt keys currently held down.
+#[derive(Debug, Default)]
+struct TiltKeys {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+impl TiltKeys {
+    /// Update the key state. Return true iff the scancode is a tilt key.
+    fn update(&mut self, config: &UserConfig, sc: &Scancode, pressed: bool) -> bool {
+        if sc == config.tilt_up_code() {
+            self.up = pressed;
+        } else if sc == config.tilt_down_code() {
+            self.down = pressed;
+        } else if sc == config.tilt_left_code() {
+            self.left = pressed;
+        } else if sc == config.tilt_right_code() {
+            self.right = pressed;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Get the tilt in g.
+    fn tilt(&self) -> (f32, f32) {
+        (
+            (self.right as i8 - self.left as i8) as f32,
+            (self.down as i8 - self.up as i8) as f32,
+        )
+    }
+}
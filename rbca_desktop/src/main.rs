@@ -1,6 +1,8 @@
+use std::fs;
+
 use clap::Parser;
 use color_eyre::eyre;
-use rbca_core::Cpu;
+use rbca_core::{apply_patch, read_rom_file, Cpu};
 use text_io::read;
 
 mod arg_parser;
@@ -24,12 +26,23 @@ fn main() -> eyre::Result<()> {
     let config: UserConfig = utils::setup()?;
 
     // Load ROM
-    let cpu = match (config.boot_rom_path(), &args.rom_path) {
-        (Some(boot_path), Some(rom_path)) => Cpu::new_boot_cart(rom_path, boot_path),
-        (Some(boot_path), None) => Cpu::new_boot(boot_path),
-        (None, Some(rom_path)) => Cpu::new_cart(rom_path),
-        (None, None) => Cpu::new(),
-    };
+    let mut cpu = if let (Some(rom_path), Some(patch_path)) = (&args.rom_path, &args.patch) {
+        let rom = apply_patch(&read_rom_file(rom_path)?, &fs::read(patch_path)?)?;
+        match config.boot_rom_path() {
+            Some(boot_path) => Cpu::new_boot_cart_bytes(rom, &fs::read(boot_path)?),
+            None => Cpu::new_cart_bytes(rom),
+        }
+    } else {
+        match (config.boot_rom_path(), &args.rom_path) {
+            (Some(boot_path), Some(rom_path)) => Cpu::new_boot_cart(rom_path, boot_path),
+            (Some(boot_path), None) => Cpu::new_boot(boot_path),
+            (None, Some(rom_path)) => Cpu::new_cart(rom_path),
+            (None, None) => Ok(Cpu::new()),
+        }
+    }?;
+
+    // Load camera image & cheats
+    utils::setup_cart(&mut cpu, &config)?;
 
     if config.config_debug() {
         // Pretty print the config
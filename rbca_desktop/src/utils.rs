@@ -1,9 +1,10 @@
 //! General utilities used by the frontend.
-use std::{env, fs};
+use std::{env, fs, fs::File};
 
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::{self, eyre};
 use directories::ProjectDirs;
+use rbca_core::{Cpu, StillImage};
 
 use crate::UserConfig;
 
@@ -75,6 +76,11 @@ pub fn config_dir() -> eyre::Result<Utf8PathBuf> {
     }
 }
 
+/// Get the directory where per-game cheat files are stored.
+pub fn cheats_dir() -> eyre::Result<Utf8PathBuf> {
+    Ok(config_dir()?.join("cheats"))
+}
+
 /// Potentially overwrite the saves directory. Return a reference to the path.
 pub fn saves_dir(config: &mut UserConfig) -> &Utf8PathBuf {
     if let Some(path) = get_env_var_path("SAVES") {
@@ -105,3 +111,69 @@ pub fn project_directory() -> Option<ProjectDirs> {
 pub fn expand_path<P: AsRef<Utf8Path>>(path: P) -> eyre::Result<Utf8PathBuf> {
     Ok(Utf8PathBuf::from(&shellexpand::full(&path.as_ref())?))
 }
+
+/// Load a PNG image from disk as a still image for the camera.
+pub fn load_still_image<P: AsRef<Utf8Path>>(path: P) -> eyre::Result<StillImage> {
+    let mut decoder = png::Decoder::new(File::open(path.as_ref())?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0x00_u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+
+    // Convert to greyscale.
+    let samples = info.color_type.samples();
+    let pixels: Vec<u8> = buf[..info.buffer_size()]
+        .chunks(samples)
+        .map(|p| match samples {
+            1 | 2 => p[0],
+            _ => ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8,
+        })
+        .collect();
+
+    Ok(StillImage::new(
+        info.width as usize,
+        info.height as usize,
+        &pixels,
+    ))
+}
+
+/// Report any problems with the loaded ROM dumps, then load the camera image & cheats for the
+/// loaded cartridge. Any previous cheats are removed.
+pub fn setup_cart(cpu: &mut Cpu, config: &UserConfig) -> eyre::Result<()> {
+    for warning in cpu.mmu.load_warnings.drain(..) {
+        eprintln!("Warning: {warning}");
+    }
+
+    if let Some(camera_image_path) = config.camera_image_path() {
+        cpu.set_image_source(Box::new(load_still_image(camera_image_path)?));
+    }
+
+    cpu.mmu.cheats.clear();
+    load_cheats(cpu)
+}
+
+/// Load the loaded game's cheats from `<cheats dir>/<game id>.txt`, if it exists.
+///
+/// The file has one Game Genie or GameShark code per line, optionally followed by a description.
+/// Anything after a `#` is ignored. Invalid codes are skipped with a warning.
+pub fn load_cheats(cpu: &mut Cpu) -> eyre::Result<()> {
+    let path = cheats_dir()?.join(format!("{}.txt", cpu.game_id()));
+    if !path.exists() {
+        return Ok(());
+    }
+
+    for line in fs::read_to_string(&path)?.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some(code) = line.split_whitespace().next() else {
+            continue;
+        };
+        if let Err(e) = cpu.mmu.cheats.add(code) {
+            eprintln!("Warning: {e} in {path}");
+        }
+    }
+
+    for (i, entry) in cpu.mmu.cheats.iter().enumerate() {
+        println!("Cheat {}: {} (toggle with F{})", i + 1, entry.code, i + 1);
+    }
+    Ok(())
+}